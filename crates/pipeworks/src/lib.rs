@@ -3,7 +3,7 @@ use eyre::{eyre, WrapErr};
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::str::FromStr;
-use tracing::{debug, trace, trace_span};
+use tracing::{trace, trace_span};
 use typed_path::{AbsPath, RelPath};
 use typed_uri::AssetUri;
 
@@ -12,12 +12,73 @@ pub const OP_COPY: &str = "_COPY_";
 
 pub type Result<T> = eyre::Result<T>;
 
-#[derive(Clone, Debug)]
-pub struct ShellCommand(String);
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ShellCommand {
+    command: String,
+    /// Identifies the command in logs.
+    name: Option<String>,
+    /// Environment variables set only for this command.
+    env: Vec<(String, String)>,
+    /// Runs the command from this directory, relative to the working directory of the
+    /// pipeline.
+    working_dir: Option<RelPath>,
+}
 
 impl ShellCommand {
     pub fn new<T: AsRef<str>>(cmd: T) -> Self {
-        Self(cmd.as_ref().to_string())
+        Self {
+            command: cmd.as_ref().to_string(),
+            ..Self::default()
+        }
+    }
+
+    #[must_use]
+    pub fn with_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    #[must_use]
+    pub fn with_env<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    #[must_use]
+    pub fn with_working_dir(mut self, working_dir: RelPath) -> Self {
+        self.working_dir = Some(working_dir);
+        self
+    }
+
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn env(&self) -> &[(String, String)] {
+        &self.env
+    }
+
+    pub fn working_dir(&self) -> Option<&RelPath> {
+        self.working_dir.as_ref()
+    }
+}
+
+impl std::fmt::Display for ShellCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(name) = &self.name {
+            write!(f, "[{name}] ")?;
+        }
+        if let Some(dir) = &self.working_dir {
+            write!(f, "cd {} && ", dir.display())?;
+        }
+        for (key, value) in &self.env {
+            write!(f, "{key}={value} ")?;
+        }
+        write!(f, "{}", self.command)
     }
 }
 
@@ -27,17 +88,91 @@ pub enum Operation {
     Shell(ShellCommand),
 }
 
+impl std::fmt::Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Copy => write!(f, "{OP_COPY}"),
+            Self::Shell(command) => write!(f, "{command}"),
+        }
+    }
+}
+
 impl FromStr for Operation {
     type Err = &'static str;
 
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
         match s {
             "_COPY_" => Ok(Self::Copy),
-            other => Ok(Self::Shell(ShellCommand(other.to_owned()))),
+            other => Ok(Self::Shell(ShellCommand::new(other))),
         }
     }
 }
 
+/// An extra file written by the operations, next to the target. Available to shell
+/// commands as `$OUTPUT(name)`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Output {
+    name: String,
+    file: String,
+}
+
+impl Output {
+    /// `file` is relative to the directory of the target. `$TARGET_NAME` is replaced by
+    /// the file name of the target, and `$TARGET_STEM` by the file name without its
+    /// extension.
+    pub fn new<N: Into<String>, F: Into<String>>(name: N, file: F) -> Result<Self> {
+        let name = name.into();
+        let file = file.into();
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(eyre!(
+                "output name '{name}' must only contain letters, numbers, `_`, or `-`"
+            ));
+        }
+        let relative = RelPath::new(&file)
+            .wrap_err_with(|| format!("output '{name}' must be relative to the target"))?;
+        if relative
+            .as_path()
+            .components()
+            .any(|c| c == std::path::Component::ParentDir)
+        {
+            return Err(eyre!(
+                "output '{name}' must be within the directory of the target"
+            ));
+        }
+        Ok(Self { name, file })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+
+    /// Where the output is written when generating `target_path`.
+    pub fn path(&self, target_path: &AbsPath) -> AbsPath {
+        let name = target_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let stem = target_path
+            .as_path()
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let file = self
+            .file
+            .replace("$TARGET_NAME", &name)
+            .replace("$TARGET_STEM", &stem);
+        target_path.pop().join(&RelPath::from_relative(file))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum BaseDir {
     RelativeToDoc(RelPath),
@@ -101,6 +236,7 @@ impl Paths {
 #[derive(Debug, Clone)]
 pub struct Pipeline {
     ops: Vec<Operation>,
+    outputs: Vec<Output>,
     base_dir: BaseDir,
     paths: Paths,
 }
@@ -109,6 +245,7 @@ impl Pipeline {
     pub fn new(paths: Paths, base_dir: &BaseDir) -> Result<Self> {
         Ok(Self {
             ops: vec![],
+            outputs: vec![],
             base_dir: base_dir.clone(),
             paths,
         })
@@ -117,6 +254,7 @@ impl Pipeline {
     pub fn with_ops(paths: Paths, base_dir: &BaseDir, ops: &[Operation]) -> Result<Self> {
         Ok(Self {
             ops: ops.into(),
+            outputs: vec![],
             base_dir: base_dir.clone(),
             paths,
         })
@@ -130,7 +268,30 @@ impl Pipeline {
         self.ops = ops.into();
     }
 
-    pub fn run(&self, asset_uri: &AssetUri) -> Result<()> {
+    /// Declares an extra file written by the operations.
+    pub fn add_output(&mut self, output: Output) -> Result<()> {
+        if self.outputs.iter().any(|o| o.name == output.name) {
+            return Err(eyre!("output '{}' is declared more than once", output.name));
+        }
+        self.outputs.push(output);
+        Ok(())
+    }
+
+    pub fn outputs(&self) -> &[Output] {
+        &self.outputs
+    }
+
+    /// Paths of the extra files written when generating `target_path`.
+    pub fn output_paths(&self, target_path: &AbsPath) -> Vec<AbsPath> {
+        self.outputs
+            .iter()
+            .map(|output| output.path(target_path))
+            .collect()
+    }
+
+    /// Runs the operations to generate the asset. Returns a transcript of the
+    /// operations and the output of each shell command.
+    pub fn run(&self, asset_uri: &AssetUri) -> Result<String> {
         let mut scratch_files = vec![];
         let result = self.do_run(&mut scratch_files, asset_uri);
 
@@ -139,22 +300,26 @@ impl Pipeline {
         result
     }
 
-    fn do_run(&self, scratch_files: &mut Vec<PathBuf>, asset_uri: &AssetUri) -> Result<()> {
-        let mut scratch_path = new_scratch_file(scratch_files, &[])
-            .wrap_err("Failed to created new scratch file for pipeline processing")?;
+    pub fn ops(&self) -> &[Operation] {
+        &self.ops
+    }
 
-        let working_dir: AbsPath = match &self.base_dir {
-            BaseDir::RelativeToRoot(base) => {
-                let relative_base = base.strip_prefix("/").wrap_err_with(|| {
-                    format!(
-                        "Failed to strip root prefix(/) from '{}' during pipline processing",
-                        base.display()
-                    )
-                })?;
-                let working_dir = self.paths.root().clone().join(&relative_base);
+    pub fn base_dir(&self) -> &BaseDir {
+        &self.base_dir
+    }
 
-                working_dir
-            }
+    /// Whether the operations read the source file, using `OP_COPY` or `$SOURCE`.
+    pub fn uses_source(&self) -> bool {
+        self.ops.iter().any(|op| match op {
+            Operation::Copy => true,
+            Operation::Shell(command) => command.command.contains("$SOURCE"),
+        })
+    }
+
+    /// The directory the operations are run from.
+    pub fn working_dir(&self, asset_uri: &AssetUri) -> Result<AbsPath> {
+        match &self.base_dir {
+            BaseDir::RelativeToRoot(_) => self.root_working_dir(),
             BaseDir::RelativeToDoc(relative) => {
                 let working_dir = asset_uri
                     // get HTML source file
@@ -170,15 +335,14 @@ impl Pipeline {
                     // append the relative directory
                     .join(relative);
 
-                working_dir
+                Ok(working_dir)
             }
-        };
-
-        let target_path = asset_uri
-            .to_target_sys_path(self.paths.root(), self.paths.output_dir())
-            .wrap_err("Failed to convert asset uri to SysPath for pipeline processing")?
-            .to_absolute_path();
+        }
+    }
 
+    /// The file used for `$SOURCE`.
+    pub fn source_path(&self, asset_uri: &AssetUri) -> Result<AbsPath> {
+        let working_dir = self.working_dir(asset_uri)?;
         let src_path = {
             if asset_uri.uri_fragment().starts_with('/') {
                 working_dir.join(&RelPath::from_relative(
@@ -188,6 +352,69 @@ impl Pipeline {
                 working_dir.join(&RelPath::from_relative(asset_uri.uri_fragment()))
             }
         };
+        Ok(src_path)
+    }
+
+    /// Runs the operations using the given source and target, for files which aren't
+    /// linked from a document. The working directory must be relative to the project
+    /// root.
+    pub fn run_at(&self, src_path: &AbsPath, target_path: &AbsPath) -> Result<String> {
+        let working_dir = self.root_working_dir()?;
+
+        let mut scratch_files = vec![];
+        let result = self.run_ops(&mut scratch_files, &working_dir, src_path, target_path);
+
+        clean_temp_files(&scratch_files).wrap_err("failed to cleanup pipeline scratch files")?;
+
+        result
+    }
+
+    /// The working directory when it's relative to the project root.
+    pub fn root_working_dir(&self) -> Result<AbsPath> {
+        match &self.base_dir {
+            BaseDir::RelativeToRoot(base) => {
+                let relative_base = base.strip_prefix("/").wrap_err_with(|| {
+                    format!(
+                        "Failed to strip root prefix(/) from '{}' during pipline processing",
+                        base.display()
+                    )
+                })?;
+                Ok(self.paths.root().clone().join(&relative_base))
+            }
+            BaseDir::RelativeToDoc(relative) => Err(eyre!(
+                "working directory '{}' is relative to a document, but there is no document",
+                relative.display()
+            )),
+        }
+    }
+
+    /// The file used for `$TARGET`.
+    pub fn target_path(&self, asset_uri: &AssetUri) -> Result<AbsPath> {
+        Ok(asset_uri
+            .to_target_sys_path(self.paths.root(), self.paths.output_dir())
+            .wrap_err("Failed to convert asset uri to SysPath for pipeline processing")?
+            .to_absolute_path())
+    }
+
+    fn do_run(&self, scratch_files: &mut Vec<PathBuf>, asset_uri: &AssetUri) -> Result<String> {
+        let working_dir = self.working_dir(asset_uri)?;
+
+        let target_path = self.target_path(asset_uri)?;
+
+        let src_path = self.source_path(asset_uri)?;
+
+        self.run_ops(scratch_files, &working_dir, &src_path, &target_path)
+    }
+
+    fn run_ops(
+        &self,
+        scratch_files: &mut Vec<PathBuf>,
+        working_dir: &AbsPath,
+        src_path: &AbsPath,
+        target_path: &AbsPath,
+    ) -> Result<String> {
+        let mut scratch_path = new_scratch_file(scratch_files, &[])
+            .wrap_err("Failed to created new scratch file for pipeline processing")?;
 
         // create all parent directories for target file
         std::fs::create_dir_all(&target_path.pop()).wrap_err_with(|| {
@@ -196,11 +423,24 @@ impl Pipeline {
                 &target_path.pop()
             )
         })?;
+        let output_paths = self.output_paths(target_path);
+        for path in &output_paths {
+            std::fs::create_dir_all(path.pop().as_path()).wrap_err_with(|| {
+                format!(
+                    "Failed to make parent directories for pipeline output '{}'",
+                    path.pop()
+                )
+            })?;
+        }
 
         // autocopy is enabled whenever we have a shell command that
         // does _not_ use the $TARGET token
         let mut autocopy = false;
 
+        // logged by the caller once the pipeline finishes, so the output of pipelines
+        // running at the same time isn't interleaved
+        let mut transcript = String::new();
+
         for op in &self.ops {
             match op {
                 Operation::Copy => {
@@ -210,21 +450,29 @@ impl Pipeline {
                         src_path,
                         target_path
                     );
-                    debug!(target: "pylon_user", "copy: {:?} -> {:?}", src_path, target_path);
-                    std::fs::copy(&src_path, &target_path).wrap_err_with(||format!("Failed to copy '{src_path}' -> '{target_path}' during pipeline processing"))?;
+                    transcript.push_str(&format!("copy: {src_path} -> {target_path}\n"));
+                    std::fs::copy(src_path, target_path).wrap_err_with(||format!("Failed to copy '{src_path}' -> '{target_path}' during pipeline processing"))?;
                 }
-                Operation::Shell(command) => {
-                    if command.0.contains("$TARGET") {
-                        autocopy = false;
-                    } else {
-                        autocopy = true;
+                Operation::Shell(shell) => {
+                    autocopy = !shell.command.contains("$TARGET");
+
+                    let mut command = shell.command.clone();
+                    for (output, path) in self.outputs.iter().zip(&output_paths) {
+                        command = command
+                            .replace(&format!("$OUTPUT({})", output.name), &path.to_string());
+                    }
+                    if let Some(start) = command.find("$OUTPUT(") {
+                        let token = command[start..]
+                            .split_once(')')
+                            .map_or(&command[start..], |(token, _)| token);
+                        return Err(eyre!("no output declared for '{token})' in pipeline"));
                     }
 
                     let command = {
                         command
-                            .0
                             .replace("$SOURCE", &src_path.to_string())
                             .replace("$SCRATCH", scratch_path.to_string_lossy().as_ref())
+                            .replace("$TARGET_DIR", target_path.pop().to_string().as_str())
                             .replace("$TARGET", target_path.to_string().as_str())
                     };
 
@@ -239,22 +487,58 @@ impl Pipeline {
                     let command =
                         command.replace("$NEW_SCRATCH", scratch_path.to_string_lossy().as_ref());
 
-                    // Output is ignored in pipeline processing and should always be captured
-                    // using a command token.
-                    let _command_output = run_command(command, &working_dir)?;
+                    let working_dir = match &shell.working_dir {
+                        Some(dir) => working_dir.join(dir),
+                        None => working_dir.clone(),
+                    };
+
+                    // Output is only logged in pipeline processing and should always be
+                    // captured using a command token.
+                    let output = run_command_with_env(&command, &working_dir, &shell.env);
+                    let output = match &shell.name {
+                        Some(name) => {
+                            let output = output
+                                .wrap_err_with(|| format!("Pipeline step '{name}' failed"))?;
+                            transcript.push_str(&format!("[{name}] "));
+                            output
+                        }
+                        None => output?,
+                    };
+                    transcript.push_str(&format!("$ {command}\n{output}"));
+                    if !transcript.ends_with('\n') {
+                        transcript.push('\n');
+                    }
                 }
             }
         }
 
         if autocopy {
-            std::fs::copy(&scratch_path, &target_path).wrap_err_with(||format!("Failed performing copy operation in pipeline. '{scratch_path:?}' -> '{target_path:?}'"))?;
+            std::fs::copy(&scratch_path, target_path).wrap_err_with(||format!("Failed performing copy operation in pipeline. '{scratch_path:?}' -> '{target_path:?}'"))?;
         }
 
-        Ok(())
+        for (output, path) in self.outputs.iter().zip(&output_paths) {
+            if !path.exists() {
+                return Err(eyre!(
+                    "pipeline did not write output '{}' to '{path}'",
+                    output.name
+                ));
+            }
+        }
+
+        Ok(transcript)
     }
 }
 
 pub fn run_command<S: AsRef<str>>(command: S, working_dir: &AbsPath) -> Result<String> {
+    run_command_with_env(command, working_dir, &[])
+}
+
+/// Runs the command with additional environment variables.
+pub fn run_command_with_env<S: AsRef<str>>(
+    command: S,
+    working_dir: &AbsPath,
+    env: &[(String, String)],
+) -> Result<String> {
     let command = command.as_ref();
 
     let cmd = format!(
@@ -263,11 +547,11 @@ pub fn run_command<S: AsRef<str>>(command: S, working_dir: &AbsPath) -> Result<S
         &command
     );
     trace!(command=%cmd, "execute shell command");
-    debug!(target: "pylon_user", command=%cmd, "execute shell command");
 
     let output = std::process::Command::new("sh")
         .arg("-c")
         .arg(&cmd)
+        .envs(env.iter().map(|(key, value)| (key, value)))
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .output()
@@ -507,6 +791,43 @@ mod test {
         assert_eq!(&target_content, "data");
     }
 
+    #[test]
+    fn returns_transcript() {
+        let tree = temptree! {
+            "rules.rhai": "",
+            templates: {},
+            target: {
+                "output.html": "",
+            },
+            src: {
+                "test.txt": "data",
+            },
+            syntax_themes: {},
+        };
+
+        let paths = make_paths(&tree);
+
+        let mut pipeline = Pipeline::new(paths, &BaseDir::new(".")).unwrap();
+        pipeline.push_op(Operation::from_str("echo hello").unwrap());
+        pipeline.push_op(Operation::from_str("cat test.txt > $TARGET").unwrap());
+
+        let html_file = confirmed_html_path(&tree, "target/output.html");
+        let asset_uri = Uri::new("/test.txt", "/test.txt")
+            .unwrap()
+            .to_asset_uri(&html_file);
+
+        let transcript = pipeline.run(&asset_uri).expect("failed to run pipeline");
+
+        let target = tree.path().join("target/test.txt");
+        assert_eq!(
+            transcript,
+            format!(
+                "$ echo hello\nhello\n$ cat test.txt > {}\n",
+                target.display()
+            )
+        );
+    }
+
     #[test]
     fn op_copy_with_relative_base_in_subdir() {
         let tree = temptree! {
@@ -975,4 +1296,266 @@ mod test {
         let joined = basedir.join(&RelPath::from_relative("b"));
         assert_eq!(joined, BaseDir::new("/a/b"));
     }
+
+    #[test]
+    fn finds_source_path() {
+        let tree = temptree! {
+            "rules.rhai": "",
+            templates: {},
+            target: {
+                "output.html": "",
+            },
+            src: {},
+            web: {
+                "test.txt": "data",
+            },
+            syntax_themes: {},
+        };
+
+        let paths = make_paths(&tree);
+        let pipeline =
+            Pipeline::with_ops(paths, &BaseDir::new("/web"), &[Operation::Copy]).unwrap();
+
+        let html_file = confirmed_html_path(&tree, "target/output.html");
+        let asset_uri = Uri::new("/test.txt", "/test.txt")
+            .unwrap()
+            .to_asset_uri(&html_file);
+
+        assert_eq!(
+            pipeline.source_path(&asset_uri).unwrap(),
+            AbsPath::new(tree.path().join("web/test.txt")).unwrap()
+        );
+    }
+
+    #[test]
+    fn runs_at_explicit_paths() {
+        let tree = temptree! {
+            "rules.rhai": "",
+            templates: {},
+            target: {},
+            src: {},
+            web: {
+                "test.txt": "data",
+            },
+            syntax_themes: {},
+        };
+
+        let paths = make_paths(&tree);
+        let ops = [Operation::from_str("cp $SOURCE $TARGET").unwrap()];
+        let pipeline = Pipeline::with_ops(paths.clone(), &BaseDir::new("/web"), &ops).unwrap();
+
+        let source = AbsPath::new(tree.path().join("web/test.txt")).unwrap();
+        let target = AbsPath::new(tree.path().join("target/inner/test.txt")).unwrap();
+        pipeline
+            .run_at(&source, &target)
+            .expect("failed to run pipeline");
+
+        let target_content = fs::read_to_string(tree.path().join("target/inner/test.txt")).unwrap();
+        assert_eq!(&target_content, "data");
+
+        // there is no document to be relative to
+        let pipeline = Pipeline::with_ops(paths, &BaseDir::new("."), &ops).unwrap();
+        assert!(pipeline.run_at(&source, &target).is_err());
+    }
+
+    #[test]
+    fn detects_source_usage() {
+        let tree = temptree! {};
+        let paths = make_paths(&tree);
+        let base = BaseDir::new("/");
+        let uses_source = |op: &str| {
+            Pipeline::with_ops(paths.clone(), &base, &[op.parse().unwrap()])
+                .unwrap()
+                .uses_source()
+        };
+
+        assert!(uses_source(OP_COPY));
+        assert!(uses_source("cat $SOURCE > $TARGET"));
+        assert!(!uses_source("sass main.scss > $TARGET"));
+    }
+
+    #[test]
+    fn displays_operations() {
+        for op in [OP_COPY, "echo $SOURCE"] {
+            assert_eq!(op.parse::<Operation>().unwrap().to_string(), op);
+        }
+    }
+
+    #[test]
+    fn writes_declared_outputs() {
+        let tree = temptree! {
+            "rules.rhai": "",
+            templates: {},
+            target: {
+                "output.html": "",
+            },
+            src: {},
+            "style.scss": "data",
+            syntax_themes: {},
+        };
+
+        let paths = make_paths(&tree);
+        let mut pipeline = Pipeline::new(paths, &BaseDir::new("/")).unwrap();
+        pipeline.push_op(Operation::Shell(ShellCommand::new(
+            "cp style.scss $TARGET && echo map > $OUTPUT(map) && echo gz > $TARGET_DIR/gz/style.gz",
+        )));
+        pipeline
+            .add_output(Output::new("map", "$TARGET_NAME.map").unwrap())
+            .unwrap();
+        pipeline
+            .add_output(Output::new("gz", "gz/$TARGET_STEM.gz").unwrap())
+            .unwrap();
+
+        let html_file = confirmed_html_path(&tree, "target/output.html");
+        let asset_uri = Uri::new("/style.css", "/style.css")
+            .unwrap()
+            .to_asset_uri(&html_file);
+
+        let target = pipeline.target_path(&asset_uri).unwrap();
+        assert_eq!(
+            pipeline.output_paths(&target),
+            vec![
+                AbsPath::new(tree.path().join("target/style.css.map")).unwrap(),
+                AbsPath::new(tree.path().join("target/gz/style.gz")).unwrap(),
+            ]
+        );
+
+        pipeline.run(&asset_uri).expect("failed to run pipeline");
+
+        assert_eq!(
+            fs::read_to_string(tree.path().join("target/style.css")).unwrap(),
+            "data"
+        );
+        assert_eq!(
+            fs::read_to_string(tree.path().join("target/style.css.map")).unwrap(),
+            "map\n"
+        );
+        assert!(tree.path().join("target/gz/style.gz").exists());
+    }
+
+    #[test]
+    fn fails_when_output_not_written() {
+        let tree = temptree! {
+            "rules.rhai": "",
+            templates: {},
+            target: {
+                "output.html": "",
+            },
+            src: {},
+            syntax_themes: {},
+        };
+
+        let paths = make_paths(&tree);
+        let html_file = confirmed_html_path(&tree, "target/output.html");
+        let asset_uri = Uri::new("/style.css", "/style.css")
+            .unwrap()
+            .to_asset_uri(&html_file);
+
+        let mut pipeline = Pipeline::new(paths.clone(), &BaseDir::new("/")).unwrap();
+        pipeline.push_op(Operation::Shell(ShellCommand::new("echo > $TARGET")));
+        pipeline
+            .add_output(Output::new("map", "$TARGET_NAME.map").unwrap())
+            .unwrap();
+        assert!(pipeline.run(&asset_uri).is_err());
+
+        // undeclared output
+        let mut pipeline = Pipeline::new(paths, &BaseDir::new("/")).unwrap();
+        pipeline.push_op(Operation::Shell(ShellCommand::new(
+            "echo > $TARGET && echo > $OUTPUT(map)",
+        )));
+        assert!(pipeline.run(&asset_uri).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_outputs() {
+        assert!(Output::new("map", "$TARGET_NAME.map").is_ok());
+        assert!(Output::new("", "a.map").is_err());
+        assert!(Output::new("a map", "a.map").is_err());
+        assert!(Output::new("map", "/a.map").is_err());
+        assert!(Output::new("map", "../a.map").is_err());
+
+        let tree = temptree! {};
+        let mut pipeline = Pipeline::new(make_paths(&tree), &BaseDir::new("/")).unwrap();
+        pipeline
+            .add_output(Output::new("map", "a.map").unwrap())
+            .unwrap();
+        assert!(pipeline
+            .add_output(Output::new("map", "b.map").unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn runs_shell_command_with_options() {
+        let tree = temptree! {
+            "rules.rhai": "",
+            templates: {},
+            target: {
+                "output.html": "",
+            },
+            src: {},
+            web: {
+                styles: {
+                    "main.scss": "data",
+                },
+            },
+            syntax_themes: {},
+        };
+
+        let paths = make_paths(&tree);
+        let command = ShellCommand::new("echo \"$GREETING $(cat main.scss)\" > $TARGET")
+            .with_name("greet")
+            .with_env("GREETING", "hello")
+            .with_working_dir(RelPath::from_relative("styles"));
+        let pipeline =
+            Pipeline::with_ops(paths, &BaseDir::new("/web"), &[Operation::Shell(command)]).unwrap();
+
+        let html_file = confirmed_html_path(&tree, "target/output.html");
+        let asset_uri = Uri::new("/style.css", "/style.css")
+            .unwrap()
+            .to_asset_uri(&html_file);
+
+        let transcript = pipeline.run(&asset_uri).expect("failed to run pipeline");
+        assert!(transcript.starts_with("[greet] $ echo"));
+
+        let target_content = fs::read_to_string(tree.path().join("target/style.css")).unwrap();
+        assert_eq!(&target_content, "hello data\n");
+    }
+
+    #[test]
+    fn names_failed_shell_command() {
+        let tree = temptree! {
+            "rules.rhai": "",
+            templates: {},
+            target: {
+                "output.html": "",
+            },
+            src: {},
+            syntax_themes: {},
+        };
+
+        let paths = make_paths(&tree);
+        let command = ShellCommand::new("false").with_name("compile");
+        let pipeline =
+            Pipeline::with_ops(paths, &BaseDir::new("/"), &[Operation::Shell(command)]).unwrap();
+
+        let html_file = confirmed_html_path(&tree, "target/output.html");
+        let asset_uri = Uri::new("/style.css", "/style.css")
+            .unwrap()
+            .to_asset_uri(&html_file);
+
+        let err = pipeline.run(&asset_uri).unwrap_err();
+        assert!(format!("{err:#}").contains("Pipeline step 'compile' failed"));
+    }
+
+    #[test]
+    fn displays_shell_command_options() {
+        let command = ShellCommand::new("sass $SOURCE")
+            .with_name("css")
+            .with_env("MODE", "prod")
+            .with_working_dir(RelPath::from_relative("styles"));
+        assert_eq!(
+            Operation::Shell(command).to_string(),
+            "[css] cd styles && MODE=prod sass $SOURCE"
+        );
+    }
 }
@@ -90,6 +90,13 @@ impl<T: PathMarker> TypedPath<T> {
         }
     }
 
+    /// Skips confirmation. Only for paths which intentionally have nothing on disk.
+    pub fn assume_confirmed(&self) -> ConfirmedPath<T> {
+        ConfirmedPath {
+            inner: self.clone(),
+        }
+    }
+
     pub fn as_sys_path(&self) -> &SysPath {
         &self.inner
     }
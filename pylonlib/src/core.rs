@@ -1,8 +1,20 @@
+pub mod build_manifest;
+pub mod build_outputs;
+pub mod build_report;
+pub mod content_api;
+pub mod diagnostics;
 pub mod engine;
+pub mod export;
+pub mod feed;
 pub mod library;
+pub mod link_check;
 pub mod page;
+pub mod robots;
 pub mod rules;
 pub mod script_engine;
+pub mod search;
+pub mod security_txt;
+pub mod sitemap;
 
 pub use library::Library;
 pub use page::{Page, PageKey};
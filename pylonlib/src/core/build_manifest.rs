@@ -0,0 +1,435 @@
+use std::collections::BTreeMap;
+use std::hash::Hasher;
+use std::path::Path;
+
+use eyre::WrapErr;
+use seahash::SeaHasher;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::core::engine::Engine;
+use crate::core::Page;
+use crate::{Result, USER_LOG};
+
+/// Location of the manifest, relative to the project root.
+pub const MANIFEST_FILE: &str = ".pylon/build_manifest.json";
+
+/// Hashes of everything used to render an output. The output only needs to be
+/// rendered again when one of these changes.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Inputs {
+    /// The page itself: path, frontmatter, and Markdown.
+    pub source: String,
+    /// All templates and syntax themes.
+    pub templates: String,
+    /// The library, global, and site contexts available to every template.
+    pub data: String,
+    pub rules: String,
+}
+
+/// Hashes of the files used by the pipelines which generate an asset.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssetInputs {
+    /// Hash of the definition of each pipeline.
+    pub pipelines: Vec<String>,
+    /// Hash of each input file, keyed by the path relative to the project root.
+    pub sources: BTreeMap<String, String>,
+    /// Uris of the extra files written next to the asset.
+    #[serde(default)]
+    pub outputs: Vec<String>,
+}
+
+impl AssetInputs {
+    pub fn add_pipeline<S: AsRef<str>>(&mut self, definition: S) {
+        self.pipelines.push(hash(definition.as_ref().as_bytes()));
+    }
+
+    pub fn add_source(&mut self, root: &Path, file: &Path) -> Result<()> {
+        let contents = std::fs::read(file).wrap_err_with(|| {
+            format!(
+                "Failed to read pipeline input '{}' for build manifest",
+                file.display()
+            )
+        })?;
+        let name = file.strip_prefix(root).unwrap_or(file);
+        self.sources
+            .insert(name.to_string_lossy().into_owned(), hash(&contents));
+        Ok(())
+    }
+
+    pub fn add_output<S: Into<String>>(&mut self, uri: S) {
+        self.outputs.push(uri.into());
+    }
+}
+
+/// Hashes which are the same for every output, computed once per build.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SiteHashes {
+    templates: String,
+    data: String,
+    /// The rule script and the build mode, since the script can check `BUILD_MODE`.
+    rules: String,
+}
+
+impl SiteHashes {
+    pub fn new(engine: &Engine) -> Result<Self> {
+        let paths = engine.paths();
+
+        let mut templates = SeaHasher::new();
+        hash_dir(&mut templates, paths.abs_template_dir().as_ref())?;
+        hash_dir(&mut templates, paths.abs_syntax_theme_dir().as_ref())?;
+
+        let data = serde_json::to_vec(&engine.site_wide_context()?.to_json())
+            .wrap_err("Failed serializing site-wide context for build manifest")?;
+
+        let rules = paths.abs_rule_script();
+        let mut rules = std::fs::read(&rules)
+            .wrap_err_with(|| format!("Failed to read rule script '{rules}' for build manifest"))?;
+        rules.extend_from_slice(engine.build_mode().as_str().as_bytes());
+
+        Ok(Self {
+            templates: format_hash(templates.finish()),
+            data: hash(&data),
+            rules: hash(&rules),
+        })
+    }
+
+    pub fn page_inputs(&self, page: &Page) -> Result<Inputs> {
+        let frontmatter = serde_json::to_string(page.frontmatter()).wrap_err_with(|| {
+            format!(
+                "Failed serializing frontmatter of '{}' for build manifest",
+                page.uri()
+            )
+        })?;
+        let path = page.path().as_sys_path().to_relative_path();
+        let source = [path.to_string(), frontmatter, page.raw_doc.clone()].join("\0");
+        Ok(self.inputs(source))
+    }
+
+    /// Inputs of an output rendered from `source`.
+    pub fn inputs<S: AsRef<[u8]>>(&self, source: S) -> Inputs {
+        Inputs {
+            source: hash(source.as_ref()),
+            templates: self.templates.clone(),
+            data: self.data.clone(),
+            rules: self.rules.clone(),
+        }
+    }
+}
+
+/// The inputs of each output written by the previous build, used to skip rendering
+/// pages which didn't change.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildManifest {
+    /// Manifests written by a different version of pylon are ignored.
+    version: String,
+    /// Keyed by the uri of the output.
+    outputs: BTreeMap<String, Inputs>,
+    /// Assets generated by pipelines, keyed by the uri of the asset.
+    #[serde(default)]
+    assets: BTreeMap<String, AssetInputs>,
+    /// Hash of each file as the build left it, keyed by uri. Files changed since then,
+    /// such as by `pylon serve`, are written again.
+    #[serde(default)]
+    written: BTreeMap<String, String>,
+}
+
+impl Default for BuildManifest {
+    fn default() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            outputs: BTreeMap::new(),
+            assets: BTreeMap::new(),
+            written: BTreeMap::new(),
+        }
+    }
+}
+
+impl BuildManifest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the manifest, starting over if the file is missing, unreadable, or from
+    /// a different version.
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+        let Ok(json) = std::fs::read_to_string(path) else {
+            return Self::default();
+        };
+        match serde_json::from_str::<Self>(&json) {
+            Ok(manifest) if manifest.version == env!("CARGO_PKG_VERSION") => manifest,
+            Ok(_) => Self::default(),
+            Err(e) => {
+                warn!(
+                    target: USER_LOG,
+                    "ignoring invalid build manifest at {}: {e}",
+                    path.display()
+                );
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).wrap_err_with(|| {
+                format!(
+                    "Failed to create build manifest directory {}",
+                    parent.display()
+                )
+            })?;
+        }
+        let json =
+            serde_json::to_string_pretty(self).wrap_err("Failed serializing build manifest")?;
+        std::fs::write(path, json)
+            .wrap_err_with(|| format!("Failed to write build manifest to {}", path.display()))
+    }
+
+    pub fn insert<S: Into<String>>(&mut self, uri: S, inputs: Inputs) {
+        self.outputs.insert(uri.into(), inputs);
+    }
+
+    pub fn get(&self, uri: &str) -> Option<&Inputs> {
+        self.outputs.get(uri)
+    }
+
+    /// Whether the output was built from the same inputs.
+    pub fn is_unchanged(&self, uri: &str, inputs: &Inputs) -> bool {
+        self.get(uri) == Some(inputs)
+    }
+
+    pub fn insert_asset<S: Into<String>>(&mut self, uri: S, inputs: AssetInputs) {
+        self.assets.insert(uri.into(), inputs);
+    }
+
+    pub fn asset(&self, uri: &str) -> Option<&AssetInputs> {
+        self.assets.get(uri)
+    }
+
+    /// Records the contents of the file written for `uri`. Missing files are skipped.
+    pub fn record_written<P: AsRef<Path>>(&mut self, uri: &str, file: P) -> Result<()> {
+        let file = file.as_ref();
+        if !file.exists() {
+            return Ok(());
+        }
+        let contents = std::fs::read(file).wrap_err_with(|| {
+            format!(
+                "Failed to read output '{}' for build manifest",
+                file.display()
+            )
+        })?;
+        self.written.insert(uri.to_owned(), hash(&contents));
+        Ok(())
+    }
+
+    /// Whether `file` still has the contents recorded for `uri`.
+    pub fn is_written<P: AsRef<Path>>(&self, uri: &str, file: P) -> bool {
+        let Some(recorded) = self.written.get(uri) else {
+            return false;
+        };
+        std::fs::read(file).is_ok_and(|contents| &hash(&contents) == recorded)
+    }
+
+    /// Uris of the outputs.
+    pub fn outputs(&self) -> impl Iterator<Item = &str> {
+        self.outputs.keys().map(String::as_str)
+    }
+
+    /// Uris of the assets generated by pipelines, including their extra outputs.
+    pub fn assets(&self) -> impl Iterator<Item = &str> {
+        self.assets.iter().flat_map(|(uri, inputs)| {
+            std::iter::once(uri.as_str()).chain(inputs.outputs.iter().map(String::as_str))
+        })
+    }
+
+    /// Outputs of this manifest which are no longer in `current`.
+    pub fn removed_outputs<'a>(&'a self, current: &'a Self) -> impl Iterator<Item = &'a str> {
+        self.outputs
+            .keys()
+            .filter(|uri| !current.outputs.contains_key(*uri))
+            .map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.outputs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.outputs.is_empty()
+    }
+}
+
+fn hash(bytes: &[u8]) -> String {
+    format_hash(seahash::hash(bytes))
+}
+
+fn format_hash(hash: u64) -> String {
+    format!("{hash:016x}")
+}
+
+/// Hashes the relative path and contents of each file in the directory, in a stable
+/// order.
+fn hash_dir(hasher: &mut SeaHasher, root: &Path) -> Result<()> {
+    let mut files = crate::discover::get_all_paths(root, &|_| true)?
+        .into_iter()
+        .map(|path| path.as_ref().to_path_buf())
+        .collect::<Vec<_>>();
+    files.sort();
+    for file in files {
+        let name = file.strip_prefix(root).unwrap_or(&file);
+        hasher.write(name.to_string_lossy().as_bytes());
+        let contents = std::fs::read(&file)
+            .wrap_err_with(|| format!("Failed to read '{}' for build manifest", file.display()))?;
+        hasher.write(&contents);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+
+    #![allow(warnings, unused)]
+
+    use super::*;
+    use temptree::temptree;
+
+    fn inputs(source: &str) -> Inputs {
+        Inputs {
+            source: source.to_owned(),
+            ..Inputs::default()
+        }
+    }
+
+    #[test]
+    fn detects_changed_inputs() {
+        let mut manifest = BuildManifest::new();
+        manifest.insert("/a.html", inputs("1"));
+
+        assert!(manifest.is_unchanged("/a.html", &inputs("1")));
+        assert!(!manifest.is_unchanged("/a.html", &inputs("2")));
+        assert!(!manifest.is_unchanged("/b.html", &inputs("1")));
+    }
+
+    #[test]
+    fn finds_removed_outputs() {
+        let mut previous = BuildManifest::new();
+        previous.insert("/a.html", inputs("1"));
+        previous.insert("/b.html", inputs("1"));
+
+        let mut current = BuildManifest::new();
+        current.insert("/a.html", inputs("2"));
+
+        assert_eq!(
+            previous.removed_outputs(&current).collect::<Vec<_>>(),
+            vec!["/b.html"]
+        );
+    }
+
+    #[test]
+    fn detects_changed_files() {
+        let tree = temptree! {
+            "a.html": "a",
+        };
+        let file = tree.path().join("a.html");
+        let mut manifest = BuildManifest::new();
+        assert!(!manifest.is_written("/a.html", &file));
+
+        manifest.record_written("/a.html", &file).unwrap();
+        assert!(manifest.is_written("/a.html", &file));
+
+        std::fs::write(&file, "changed").unwrap();
+        assert!(!manifest.is_written("/a.html", &file));
+        std::fs::remove_file(&file).unwrap();
+        assert!(!manifest.is_written("/a.html", &file));
+
+        manifest
+            .record_written("/b.html", tree.path().join("b.html"))
+            .unwrap();
+        assert!(!manifest.is_written("/b.html", tree.path().join("b.html")));
+    }
+
+    #[test]
+    fn saves_and_loads() {
+        let tree = temptree! {};
+        let path = tree.path().join(MANIFEST_FILE);
+        assert!(BuildManifest::load(&path).is_empty());
+
+        let mut manifest = BuildManifest::new();
+        manifest.insert("/a.html", inputs("1"));
+        manifest.save(&path).unwrap();
+        assert_eq!(BuildManifest::load(&path), manifest);
+    }
+
+    #[test]
+    fn ignores_manifest_from_other_version() {
+        let tree = temptree! {};
+        let path = tree.path().join(MANIFEST_FILE);
+
+        let mut manifest = BuildManifest::new();
+        manifest.version = "0.0.0-other".to_owned();
+        manifest.insert("/a.html", inputs("1"));
+        manifest.save(&path).unwrap();
+
+        assert!(BuildManifest::load(&path).is_empty());
+    }
+
+    #[test]
+    fn hashes_asset_inputs() {
+        let tree = temptree! {
+            "main.scss": "a",
+        };
+        let inputs = || {
+            let mut inputs = AssetInputs::default();
+            inputs.add_pipeline("sass main.scss > $TARGET");
+            inputs
+                .add_source(tree.path(), &tree.path().join("main.scss"))
+                .unwrap();
+            inputs
+        };
+
+        let before = inputs();
+        assert!(before.sources.contains_key("main.scss"));
+        assert_eq!(before, inputs());
+
+        std::fs::write(tree.path().join("main.scss"), "b").unwrap();
+        assert_ne!(before, inputs());
+    }
+
+    #[test]
+    fn lists_asset_outputs() {
+        let mut manifest = BuildManifest::new();
+        let mut inputs = AssetInputs::default();
+        inputs.add_output("/style.css.map");
+        manifest.insert_asset("/style.css", inputs);
+        manifest.insert_asset("/app.js", AssetInputs::default());
+
+        assert_eq!(
+            manifest.assets().collect::<Vec<_>>(),
+            vec!["/app.js", "/style.css", "/style.css.map"]
+        );
+    }
+
+    #[test]
+    fn hashes_directory_contents() {
+        let tree = temptree! {
+            templates: {
+                "a.tera": "a",
+                "b.tera": "b",
+            }
+        };
+        let dir = tree.path().join("templates");
+        let hash_of = || {
+            let mut hasher = SeaHasher::new();
+            hash_dir(&mut hasher, &dir).unwrap();
+            hasher.finish()
+        };
+
+        let before = hash_of();
+        assert_eq!(before, hash_of());
+
+        std::fs::write(dir.join("b.tera"), "changed").unwrap();
+        assert_ne!(before, hash_of());
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+
+use parking_lot::Mutex;
+use tracing::warn;
+
+use crate::core::rules::Mount;
+use crate::{AbsPath, RelPath, Result, USER_LOG};
+
+/// The path of the output at `uri`, relative to the output directory. Returns
+/// `None` for uris without a leading slash or which leave the output directory,
+/// such as `/../x`.
+pub fn output_path(uri: &str) -> Option<RelPath> {
+    uri.strip_prefix('/')
+        .filter(|relative| {
+            !relative.is_empty()
+                && Path::new(relative)
+                    .components()
+                    .all(|component| matches!(component, Component::Normal(_)))
+        })
+        .map(RelPath::from_relative)
+}
+
+/// Collects the files produced by a build, so that everything else in the output
+/// directory can be pruned afterwards.
+#[derive(Debug)]
+pub struct BuildOutputs {
+    output_dir: AbsPath,
+    files: Mutex<HashSet<PathBuf>>,
+}
+
+impl BuildOutputs {
+    pub fn new(output_dir: AbsPath) -> Self {
+        Self {
+            output_dir,
+            files: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Adds the output at `uri`. Invalid uris are skipped, see [`output_path`].
+    pub fn add_uri<S: AsRef<str>>(&self, uri: S) {
+        let uri = uri.as_ref();
+        if let Some(relative) = output_path(uri) {
+            self.add_path(self.output_dir.join(&relative));
+        } else {
+            warn!(target: USER_LOG, "ignoring invalid output uri '{uri}'");
+        }
+    }
+
+    pub fn add_path<P: AsRef<Path>>(&self, path: P) {
+        self.files.lock().insert(path.as_ref().to_path_buf());
+    }
+
+    /// Adds each file copied by the mount.
+    pub fn add_mount(&self, mount: &Mount) -> Result<()> {
+        for file in crate::discover::get_all_paths(mount.src(), &|_| true)? {
+            if let Ok(relative) = file.as_path().strip_prefix(mount.src()) {
+                self.add_path(mount.target().as_path().join(relative));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn contains<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.files.lock().contains(path.as_ref())
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.lock().is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    #![allow(warnings, unused)]
+
+    use super::*;
+    use temptree::temptree;
+
+    #[test]
+    fn adds_outputs() {
+        let tree = temptree! {
+            static: {
+                "a.css": "",
+                img: {
+                    "b.png": "",
+                }
+            },
+            target: {},
+        };
+        let root = AbsPath::new(tree.path()).unwrap();
+        let output_dir = root.join(&RelPath::from_relative("target"));
+        let outputs = BuildOutputs::new(output_dir.clone());
+
+        outputs.add_uri("/index.html");
+        let mount = Mount::new(
+            &root,
+            &RelPath::from_relative("target"),
+            &RelPath::from_relative("static"),
+            &RelPath::from_relative("assets"),
+        );
+        outputs.add_mount(&mount).unwrap();
+
+        assert_eq!(outputs.len(), 3);
+        assert!(outputs.contains(tree.path().join("target/index.html")));
+        assert!(outputs.contains(tree.path().join("target/assets/a.css")));
+        assert!(outputs.contains(tree.path().join("target/assets/img/b.png")));
+        assert!(!outputs.contains(tree.path().join("target/a.css")));
+    }
+
+    #[test]
+    fn skips_invalid_uris() {
+        for uri in [
+            "",
+            "/",
+            "index.html",
+            "é.html",
+            "/../x",
+            "/a/../../x",
+            "//x",
+        ] {
+            assert_eq!(output_path(uri), None, "{uri}");
+        }
+        assert_eq!(
+            output_path("/é/index.html"),
+            Some(RelPath::from_relative("é/index.html"))
+        );
+    }
+}
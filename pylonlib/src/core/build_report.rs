@@ -0,0 +1,653 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use eyre::{eyre, WrapErr};
+use parking_lot::Mutex;
+use serde::{Serialize, Serializer};
+use serde_json::json;
+
+use crate::core::diagnostics::{DiagnosticKind, Diagnostics};
+use crate::core::page::lint::{LintLevel, LintResults};
+use crate::core::page::render::RenderError;
+use crate::core::Library;
+use crate::util::escape_xml;
+use crate::Result;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportFormat {
+    Json,
+    /// Static Analysis Results Interchange Format, used for code scanning annotations.
+    Sarif,
+    Junit,
+}
+
+impl FromStr for ReportFormat {
+    type Err = eyre::Report;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "sarif" => Ok(Self::Sarif),
+            "junit" => Ok(Self::Junit),
+            other => Err(eyre!("invalid report format {other}")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportLevel {
+    Error,
+    Warning,
+}
+
+impl std::fmt::Display for ReportLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Problem {
+    /// The build step which found the problem.
+    pub step: String,
+    /// The kind of problem, such as `lint` or `missing_asset`.
+    pub rule: String,
+    pub level: ReportLevel,
+    /// Path to the source file, relative to the project root.
+    pub file: Option<String>,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct StepResult {
+    pub name: String,
+    /// Why the step failed.
+    pub error: Option<String>,
+    #[serde(rename = "seconds", serialize_with = "as_secs")]
+    pub duration: Duration,
+}
+
+/// Total time spent on several runs of the same thing, such as a pipeline.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Timing {
+    pub count: usize,
+    #[serde(rename = "seconds", serialize_with = "as_secs")]
+    pub total: Duration,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MinifyStats {
+    pub files: usize,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+impl MinifyStats {
+    pub fn add(&mut self, before: usize, after: usize) {
+        self.files += 1;
+        self.bytes_before += before as u64;
+        self.bytes_after += after as u64;
+    }
+
+    pub fn bytes_saved(&self) -> u64 {
+        self.bytes_before.saturating_sub(self.bytes_after)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct BuildStats {
+    pub pages_rendered: usize,
+    /// Number of pages skipped because their inputs didn't change.
+    pub pages_unchanged: usize,
+    /// Number of outputs deleted because their source no longer exists.
+    pub outputs_removed: usize,
+    /// Number of assets generated by a pipeline.
+    pub assets_piped: usize,
+    /// Number of assets skipped because the inputs of their pipelines didn't change.
+    pub assets_unchanged: usize,
+    pub files_minified: usize,
+    pub bytes_saved: u64,
+    /// Time spent in each pipeline, keyed by the glob of the pipeline.
+    pub pipelines: BTreeMap<String, Timing>,
+    /// Time spent rendering pages, keyed by template name.
+    pub templates: BTreeMap<String, Timing>,
+}
+
+fn as_secs<S: Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+/// Records the steps of a site build and the problems found along the way, so they
+/// can be written in a format understood by CI systems.
+#[derive(Debug, Default)]
+pub struct BuildReport {
+    steps: Mutex<Vec<StepResult>>,
+    /// When the current step started.
+    step_started: Mutex<Option<Instant>>,
+    problems: Mutex<Vec<Problem>>,
+    stats: Mutex<BuildStats>,
+}
+
+impl BuildReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ends the current step and starts timing the next one.
+    pub fn begin_step<S: Into<String>>(&self, name: S) {
+        self.finish();
+        self.steps.lock().push(StepResult {
+            name: name.into(),
+            error: None,
+            duration: Duration::ZERO,
+        });
+        *self.step_started.lock() = Some(Instant::now());
+    }
+
+    /// Ends the current step.
+    pub fn finish(&self) {
+        if let Some(started) = self.step_started.lock().take() {
+            if let Some(step) = self.steps.lock().last_mut() {
+                step.duration = started.elapsed();
+            }
+        }
+    }
+
+    /// Marks the current step as failed. Errors from rendering a page are also
+    /// reported against the Markdown file of the page.
+    pub fn fail(&self, error: &eyre::Report) {
+        self.finish();
+        let message = format!("{error:#}");
+        let step = {
+            let mut steps = self.steps.lock();
+            if let Some(step) = steps.last_mut() {
+                step.error = Some(message.clone());
+                step.name.clone()
+            } else {
+                steps.push(StepResult {
+                    name: "build".to_owned(),
+                    error: Some(message.clone()),
+                    duration: Duration::ZERO,
+                });
+                "build".to_owned()
+            }
+        };
+        self.problems.lock().push(Problem {
+            step,
+            rule: "failed_step".to_owned(),
+            level: ReportLevel::Error,
+            file: error.downcast_ref::<RenderError>().map(|e| e.file.clone()),
+            message,
+        });
+    }
+
+    pub fn add_lints(&self, lints: &LintResults) {
+        for lint in lints {
+            self.push(
+                "lint",
+                match lint.level {
+                    LintLevel::Deny => ReportLevel::Error,
+                    LintLevel::Warn => ReportLevel::Warning,
+                },
+                Some(lint.md_file.as_sys_path().to_relative_path().to_string()),
+                lint.msg.clone(),
+            );
+        }
+    }
+
+    /// Adds the diagnostics, using the library to find the Markdown file of each page.
+    pub fn add_diagnostics(&self, library: &Library, diagnostics: &Diagnostics) {
+        for diagnostic in diagnostics.to_vec() {
+            let rule = match diagnostic.kind {
+                DiagnosticKind::BrokenLink => "broken_link",
+                DiagnosticKind::MissingAsset => "missing_asset",
+                DiagnosticKind::FailedPipeline => "failed_pipeline",
+            };
+            let file = library
+                .get(&diagnostic.page.as_str().into())
+                .map(|page| page.path().as_sys_path().to_relative_path().to_string());
+            self.push(rule, ReportLevel::Error, file, diagnostic.to_string());
+        }
+    }
+
+    fn push(&self, rule: &str, level: ReportLevel, file: Option<String>, message: String) {
+        let step = self
+            .steps
+            .lock()
+            .last()
+            .map_or_else(|| "build".to_owned(), |step| step.name.clone());
+        self.problems.lock().push(Problem {
+            step,
+            rule: rule.to_owned(),
+            level,
+            file,
+            message,
+        });
+    }
+
+    pub fn record_pages(&self, count: usize) {
+        self.stats.lock().pages_rendered += count;
+    }
+
+    pub fn record_unchanged_pages(&self, count: usize) {
+        self.stats.lock().pages_unchanged += count;
+    }
+
+    pub fn record_unchanged_asset(&self) {
+        self.stats.lock().assets_unchanged += 1;
+    }
+
+    pub fn record_removed_outputs(&self, count: usize) {
+        self.stats.lock().outputs_removed += count;
+    }
+
+    pub fn record_template(&self, template: &str, duration: Duration) {
+        let mut stats = self.stats.lock();
+        let timing = stats.templates.entry(template.to_owned()).or_default();
+        timing.count += 1;
+        timing.total += duration;
+    }
+
+    pub fn record_pipeline(&self, glob: &str, duration: Duration) {
+        let mut stats = self.stats.lock();
+        let timing = stats.pipelines.entry(glob.to_owned()).or_default();
+        timing.count += 1;
+        timing.total += duration;
+    }
+
+    pub fn record_piped_asset(&self) {
+        self.stats.lock().assets_piped += 1;
+    }
+
+    pub fn record_minified(&self, minified: MinifyStats) {
+        let mut stats = self.stats.lock();
+        stats.files_minified += minified.files;
+        stats.bytes_saved += minified.bytes_saved();
+    }
+
+    pub fn stats(&self) -> BuildStats {
+        self.stats.lock().clone()
+    }
+
+    /// Sum of the time spent in each step.
+    pub fn total_duration(&self) -> Duration {
+        self.steps.lock().iter().map(|step| step.duration).sum()
+    }
+
+    /// Counts and timings of the build, for printing once the build is done.
+    pub fn summary(&self) -> String {
+        let stats = self.stats();
+        let mut lines = vec![
+            format!("built site in {:.2}s", self.total_duration().as_secs_f64()),
+            format!(
+                "  pages rendered: {} ({} unchanged)",
+                stats.pages_rendered, stats.pages_unchanged
+            ),
+            format!("  stale outputs removed: {}", stats.outputs_removed),
+            format!(
+                "  assets piped: {} ({} unchanged)",
+                stats.assets_piped, stats.assets_unchanged
+            ),
+            format!(
+                "  files minified: {} ({} bytes saved)",
+                stats.files_minified, stats.bytes_saved
+            ),
+            "  steps:".to_owned(),
+        ];
+        for step in self.steps() {
+            lines.push(format!(
+                "    {:<32}{:>8.3}s",
+                step.name,
+                step.duration.as_secs_f64()
+            ));
+        }
+        if !stats.pipelines.is_empty() {
+            lines.push("  pipelines:".to_owned());
+            for (glob, timing) in &stats.pipelines {
+                lines.push(format!(
+                    "    {glob:<32}{:>8.3}s ({} runs)",
+                    timing.total.as_secs_f64(),
+                    timing.count
+                ));
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// JSON breakdown of the time spent in each step, pipeline, and template.
+    pub fn timings(&self) -> Result<String> {
+        let stats = self.stats();
+        let steps = self
+            .steps()
+            .into_iter()
+            .map(|step| json!({ "name": step.name, "seconds": step.duration.as_secs_f64() }))
+            .collect::<Vec<_>>();
+        serde_json::to_string_pretty(&json!({
+            "seconds": self.total_duration().as_secs_f64(),
+            "steps": steps,
+            "pipelines": stats.pipelines,
+            "templates": stats.templates,
+            "pages_rendered": stats.pages_rendered,
+            "pages_unchanged": stats.pages_unchanged,
+            "outputs_removed": stats.outputs_removed,
+            "assets_piped": stats.assets_piped,
+            "assets_unchanged": stats.assets_unchanged,
+            "files_minified": stats.files_minified,
+            "bytes_saved": stats.bytes_saved,
+        }))
+        .wrap_err("Failed serializing build timings")
+    }
+
+    pub fn write_timings<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).wrap_err_with(|| {
+                format!(
+                    "Failed to create build timings directory {}",
+                    parent.display()
+                )
+            })?;
+        }
+        std::fs::write(path, self.timings()?)
+            .wrap_err_with(|| format!("Failed to write build timings to {}", path.display()))
+    }
+
+    pub fn steps(&self) -> Vec<StepResult> {
+        self.steps.lock().clone()
+    }
+
+    pub fn problems(&self) -> Vec<Problem> {
+        self.problems.lock().clone()
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.steps.lock().iter().all(|step| step.error.is_none())
+    }
+
+    pub fn render(&self, format: ReportFormat) -> Result<String> {
+        match format {
+            ReportFormat::Json => serde_json::to_string_pretty(&json!({
+                "success": self.is_success(),
+                "steps": self.steps(),
+                "problems": self.problems(),
+            }))
+            .wrap_err("Failed serializing JSON build report"),
+            ReportFormat::Sarif => serde_json::to_string_pretty(&self.sarif())
+                .wrap_err("Failed serializing SARIF build report"),
+            ReportFormat::Junit => Ok(self.junit()),
+        }
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, format: ReportFormat, path: P) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).wrap_err_with(|| {
+                format!(
+                    "Failed to create build report directory {}",
+                    parent.display()
+                )
+            })?;
+        }
+        std::fs::write(path, self.render(format)?)
+            .wrap_err_with(|| format!("Failed to write build report to {}", path.display()))
+    }
+
+    fn sarif(&self) -> serde_json::Value {
+        let problems = self.problems();
+        let rules = problems
+            .iter()
+            .map(|problem| problem.rule.as_str())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(|rule| json!({ "id": rule }))
+            .collect::<Vec<_>>();
+        let results = problems
+            .iter()
+            .map(|problem| {
+                let mut result = json!({
+                    "ruleId": problem.rule,
+                    "level": problem.level.to_string(),
+                    "message": { "text": problem.message },
+                });
+                if let Some(file) = &problem.file {
+                    result["locations"] = json!([{
+                        "physicalLocation": { "artifactLocation": { "uri": file } }
+                    }]);
+                }
+                result
+            })
+            .collect::<Vec<_>>();
+
+        json!({
+            "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+            "version": "2.1.0",
+            "runs": [{
+                "tool": {
+                    "driver": {
+                        "name": "pylon",
+                        "version": env!("CARGO_PKG_VERSION"),
+                        "rules": rules,
+                    }
+                },
+                "results": results,
+            }]
+        })
+    }
+
+    /// Each step is a test case, followed by a test case for each problem. Warnings
+    /// pass, with the message as output.
+    fn junit(&self) -> String {
+        let steps = self.steps();
+        let problems = self.problems();
+
+        let step_failures = steps.iter().filter(|step| step.error.is_some()).count();
+        let problem_failures = problems
+            .iter()
+            .filter(|problem| problem.level == ReportLevel::Error)
+            .count();
+
+        let mut xml = vec![
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#.to_owned(),
+            format!(
+                r#"<testsuites name="pylon build" tests="{}" failures="{}">"#,
+                steps.len() + problems.len(),
+                step_failures + problem_failures
+            ),
+            format!(
+                r#"  <testsuite name="steps" tests="{}" failures="{step_failures}">"#,
+                steps.len()
+            ),
+        ];
+        for step in &steps {
+            let name = escape_xml(&step.name);
+            if let Some(error) = &step.error {
+                xml.push(format!(r#"    <testcase classname="steps" name="{name}">"#));
+                xml.push(format!(
+                    r#"      <failure message="{}"/>"#,
+                    escape_xml(error)
+                ));
+                xml.push("    </testcase>".to_owned());
+            } else {
+                xml.push(format!(
+                    r#"    <testcase classname="steps" name="{name}"/>"#
+                ));
+            }
+        }
+        xml.push("  </testsuite>".to_owned());
+
+        xml.push(format!(
+            r#"  <testsuite name="problems" tests="{}" failures="{problem_failures}">"#,
+            problems.len()
+        ));
+        for problem in &problems {
+            let file = problem
+                .file
+                .as_ref()
+                .map(|file| format!(r#" file="{}""#, escape_xml(file)))
+                .unwrap_or_default();
+            let message = escape_xml(&problem.message);
+            xml.push(format!(
+                r#"    <testcase classname="{}" name="{message}"{file}>"#,
+                escape_xml(&problem.rule)
+            ));
+            match problem.level {
+                ReportLevel::Error => {
+                    xml.push(format!(r#"      <failure message="{message}"/>"#));
+                }
+                ReportLevel::Warning => {
+                    xml.push(format!("      <system-out>warning: {message}</system-out>"));
+                }
+            }
+            xml.push("    </testcase>".to_owned());
+        }
+        xml.push("  </testsuite>".to_owned());
+        xml.push("</testsuites>".to_owned());
+        xml.push(String::new());
+        xml.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    #![allow(warnings, unused)]
+
+    use super::*;
+
+    fn report() -> BuildReport {
+        let report = BuildReport::new();
+        report.begin_step("lints");
+        report.push(
+            "lint",
+            ReportLevel::Warning,
+            Some("src/doc.md".to_owned()),
+            "missing author".to_owned(),
+        );
+        report.begin_step("render");
+        report.fail(&eyre!("template error").wrap_err(RenderError {
+            uri: "/doc.html".to_owned(),
+            file: "src/doc.md".to_owned(),
+        }));
+        report
+    }
+
+    #[test]
+    fn parses_format() {
+        assert_eq!(
+            "sarif".parse::<ReportFormat>().unwrap(),
+            ReportFormat::Sarif
+        );
+        assert!("xml".parse::<ReportFormat>().is_err());
+    }
+
+    #[test]
+    fn records_failed_step() {
+        let report = report();
+        assert!(!report.is_success());
+
+        let steps = report.steps();
+        assert_eq!(steps[0].error, None);
+        assert_eq!(steps[1].name, "render");
+        assert!(steps[1].error.as_ref().unwrap().contains("template error"));
+
+        let problems = report.problems();
+        assert_eq!(problems[0].step, "lints");
+        assert_eq!(problems[1].rule, "failed_step");
+        assert_eq!(problems[1].file.as_deref(), Some("src/doc.md"));
+    }
+
+    #[test]
+    fn finds_render_error_through_context() {
+        let report = BuildReport::new();
+        report.begin_step("render");
+        let error = eyre!("template error")
+            .wrap_err(RenderError {
+                uri: "/doc.html".to_owned(),
+                file: "src/doc.md".to_owned(),
+            })
+            .wrap_err("Failed to render pages during site build");
+        report.fail(&error);
+        assert_eq!(report.problems()[0].file.as_deref(), Some("src/doc.md"));
+    }
+
+    #[test]
+    fn renders_sarif() {
+        let sarif: serde_json::Value =
+            serde_json::from_str(&report().render(ReportFormat::Sarif).unwrap()).unwrap();
+        let run = &sarif["runs"][0];
+        assert_eq!(run["tool"]["driver"]["rules"][0]["id"], "failed_step");
+        assert_eq!(run["results"][0]["level"], "warning");
+        assert_eq!(
+            run["results"][0]["locations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+            "src/doc.md"
+        );
+    }
+
+    #[test]
+    fn times_steps() {
+        let report = BuildReport::new();
+        report.begin_step("render");
+        std::thread::sleep(Duration::from_millis(10));
+        report.begin_step("pipelines");
+        report.finish();
+
+        let steps = report.steps();
+        assert!(steps[0].duration >= Duration::from_millis(10));
+        assert!(steps[1].duration < steps[0].duration);
+        assert_eq!(
+            report.total_duration(),
+            steps[0].duration + steps[1].duration
+        );
+    }
+
+    #[test]
+    fn records_stats() {
+        let report = BuildReport::new();
+        report.record_pages(2);
+        report.record_template("post.tera", Duration::from_millis(5));
+        report.record_template("post.tera", Duration::from_millis(5));
+        report.record_pipeline("**/*.png", Duration::from_millis(20));
+        report.record_piped_asset();
+
+        let mut minified = MinifyStats::default();
+        minified.add(100, 60);
+        minified.add(10, 20);
+        report.record_minified(minified);
+
+        let stats = report.stats();
+        assert_eq!(stats.pages_rendered, 2);
+        assert_eq!(stats.assets_piped, 1);
+        assert_eq!(stats.files_minified, 2);
+        assert_eq!(stats.bytes_saved, 30);
+        assert_eq!(
+            stats.templates["post.tera"],
+            Timing {
+                count: 2,
+                total: Duration::from_millis(10)
+            }
+        );
+
+        let timings: serde_json::Value = serde_json::from_str(&report.timings().unwrap()).unwrap();
+        assert_eq!(timings["pipelines"]["**/*.png"]["count"], 1);
+        assert_eq!(timings["pipelines"]["**/*.png"]["seconds"], 0.02);
+        assert!(report.summary().contains("pages rendered: 2"));
+    }
+
+    #[test]
+    fn renders_junit() {
+        let junit = report().render(ReportFormat::Junit).unwrap();
+        assert!(junit.contains(r#"<testsuites name="pylon build" tests="4" failures="2">"#));
+        assert!(junit.contains(r#"<testcase classname="steps" name="lints"/>"#));
+        assert!(junit
+            .contains(r#"<testcase classname="lint" name="missing author" file="src/doc.md">"#));
+        assert!(junit.contains("<system-out>warning: missing author</system-out>"));
+    }
+}
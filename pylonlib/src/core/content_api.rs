@@ -0,0 +1,165 @@
+use std::collections::BTreeMap;
+
+use eyre::WrapErr;
+use serde::Serialize;
+
+use crate::core::page::render::RenderedPageCollection;
+use crate::core::page::FrontMatter;
+use crate::core::{Library, Page};
+use crate::{RelPath, Result};
+
+/// Name of the file listing the pages and subdirectories of a directory.
+pub const LISTING_FILE: &str = "_index.json";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContentFile {
+    pub uri: String,
+    pub json: String,
+}
+
+#[derive(Debug, Serialize)]
+struct PageJson<'a> {
+    uri: String,
+    json: String,
+    frontmatter: &'a FrontMatter,
+    content: &'a str,
+    toc: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct ListingEntry<'a> {
+    uri: String,
+    json: String,
+    frontmatter: &'a FrontMatter,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct Listing<'a> {
+    uri: String,
+    pages: Vec<ListingEntry<'a>>,
+    /// Listings of the subdirectories.
+    directories: Vec<String>,
+}
+
+/// Location of the JSON representation of the page, next to the HTML file.
+pub fn json_uri(page: &Page) -> String {
+    format!(
+        "/{}",
+        page.target()
+            .with_base(&RelPath::from_relative(""))
+            .with_extension("json")
+            .to_relative_path()
+    )
+}
+
+/// Generates a JSON file for each page rendered from Markdown, plus a listing for each
+/// directory containing these pages.
+pub fn generate(library: &Library, rendered: &RenderedPageCollection) -> Result<Vec<ContentFile>> {
+    let mut files = vec![];
+    let mut listings: BTreeMap<String, Listing<'_>> = BTreeMap::new();
+
+    for rendered_page in rendered {
+        let Some(content) = rendered_page.content() else {
+            continue;
+        };
+        let Some(page) = library.get_with_key(rendered_page.page_key()) else {
+            continue;
+        };
+
+        let json_uri = json_uri(page);
+        let page_json = PageJson {
+            uri: page.uri().to_string(),
+            json: json_uri.clone(),
+            frontmatter: page.frontmatter(),
+            content: &content.content,
+            toc: &content.toc,
+        };
+        files.push(ContentFile {
+            uri: json_uri.clone(),
+            json: serde_json::to_string(&page_json)
+                .wrap_err_with(|| format!("Failed serializing '{}' to JSON", page.uri()))?,
+        });
+
+        let dir = parent_dir(&json_uri);
+        add_directory(&mut listings, dir);
+        if let Some(listing) = listings.get_mut(dir) {
+            listing.pages.push(ListingEntry {
+                uri: page.uri().to_string(),
+                json: json_uri.clone(),
+                frontmatter: page.frontmatter(),
+            });
+        }
+    }
+
+    for (dir, mut listing) in listings {
+        listing.pages.sort_by(|a, b| a.uri.cmp(&b.uri));
+        listing.directories.sort();
+        files.push(ContentFile {
+            uri: format!("{dir}{LISTING_FILE}"),
+            json: serde_json::to_string(&listing)
+                .wrap_err_with(|| format!("Failed serializing listing of '{dir}' to JSON"))?,
+        });
+    }
+
+    Ok(files)
+}
+
+/// The directory of the uri, with a trailing slash.
+fn parent_dir(uri: &str) -> &str {
+    match uri.rsplit_once('/') {
+        Some((dir, _)) => &uri[..=dir.len()],
+        None => "/",
+    }
+}
+
+/// Adds listings for the directory and all of its parents.
+fn add_directory(listings: &mut BTreeMap<String, Listing<'_>>, dir: &str) {
+    if listings.contains_key(dir) {
+        return;
+    }
+    listings.insert(
+        dir.to_owned(),
+        Listing {
+            uri: dir.to_owned(),
+            ..Default::default()
+        },
+    );
+    if dir == "/" {
+        return;
+    }
+    let parent = parent_dir(dir.trim_end_matches('/'));
+    add_directory(listings, parent);
+    if let Some(parent) = listings.get_mut(parent) {
+        parent.directories.push(format!("{dir}{LISTING_FILE}"));
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    #![allow(warnings, unused)]
+
+    use super::*;
+
+    #[test]
+    fn finds_parent_dir() {
+        assert_eq!(parent_dir("/blog/post.json"), "/blog/");
+        assert_eq!(parent_dir("/post.json"), "/");
+        assert_eq!(parent_dir("/blog"), "/");
+    }
+
+    #[test]
+    fn adds_parent_directories() {
+        let mut listings = BTreeMap::new();
+        add_directory(&mut listings, "/blog/2022/");
+        assert_eq!(
+            listings.keys().collect::<Vec<_>>(),
+            vec!["/", "/blog/", "/blog/2022/"]
+        );
+        assert_eq!(listings["/"].directories, vec!["/blog/_index.json"]);
+        assert_eq!(
+            listings["/blog/"].directories,
+            vec!["/blog/2022/_index.json"]
+        );
+    }
+}
@@ -0,0 +1,243 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use parking_lot::Mutex;
+use serde::Serialize;
+
+use crate::Result;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticKind {
+    /// An internal document link (`@/doc.md`) to a document which doesn't exist.
+    BrokenLink,
+    /// An asset which doesn't exist and isn't generated by a pipeline.
+    MissingAsset,
+    /// A pipeline which failed while generating an asset.
+    FailedPipeline,
+}
+
+impl std::fmt::Display for DiagnosticKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BrokenLink => write!(f, "broken link"),
+            Self::MissingAsset => write!(f, "missing asset"),
+            Self::FailedPipeline => write!(f, "failed pipeline"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    /// Uri of the page containing the link.
+    pub page: String,
+    /// The link or asset which caused the problem.
+    pub target: String,
+    pub message: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new<P, T>(kind: DiagnosticKind, page: P, target: T) -> Self
+    where
+        P: Into<String>,
+        T: Into<String>,
+    {
+        Self {
+            kind,
+            page: page.into(),
+            target: target.into(),
+            message: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_message<S: Into<String>>(mut self, message: S) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} '{}' on page '{}'", self.kind, self.target, self.page)?;
+        if let Some(message) = &self.message {
+            write!(f, ": {message}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Collects problems found while building the site, so they can all be reported at
+/// once instead of stopping at the first one.
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    items: Mutex<BTreeSet<Diagnostic>>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the diagnostic, unless the same problem was already found.
+    pub fn push(&self, diagnostic: Diagnostic) {
+        self.items.lock().insert(diagnostic);
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.lock().is_empty()
+    }
+
+    /// All diagnostics, sorted by kind, page, and target.
+    pub fn to_vec(&self) -> Vec<Diagnostic> {
+        self.items.lock().iter().cloned().collect()
+    }
+
+    pub fn by_page(&self) -> BTreeMap<String, Vec<Diagnostic>> {
+        let mut pages: BTreeMap<String, Vec<Diagnostic>> = BTreeMap::new();
+        for diagnostic in self.to_vec() {
+            pages
+                .entry(diagnostic.page.clone())
+                .or_default()
+                .push(diagnostic);
+        }
+        pages
+    }
+
+    pub fn by_target(&self) -> BTreeMap<String, Vec<Diagnostic>> {
+        let mut targets: BTreeMap<String, Vec<Diagnostic>> = BTreeMap::new();
+        for diagnostic in self.to_vec() {
+            targets
+                .entry(diagnostic.target.clone())
+                .or_default()
+                .push(diagnostic);
+        }
+        targets
+    }
+
+    /// Lists the problems grouped by page, followed by the pages affected by each
+    /// target.
+    pub fn report(&self) -> String {
+        let mut lines = vec!["by page:".to_owned()];
+        for (page, diagnostics) in self.by_page() {
+            lines.push(format!("  {page}"));
+            for diagnostic in diagnostics {
+                let mut line = format!("    {} '{}'", diagnostic.kind, diagnostic.target);
+                if let Some(message) = &diagnostic.message {
+                    line.push_str(": ");
+                    line.push_str(message);
+                }
+                lines.push(line);
+            }
+        }
+        lines.push("by target:".to_owned());
+        for (target, diagnostics) in self.by_target() {
+            let kind = diagnostics[0].kind;
+            let pages = diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.page.as_str())
+                .collect::<Vec<_>>();
+            lines.push(format!("  {target} ({kind}): {}", pages.join(", ")));
+        }
+        lines.join("\n")
+    }
+
+    /// Fails with all of the diagnostics, if there are any.
+    pub fn check(&self) -> Result<()> {
+        let items = self.to_vec();
+        if items.is_empty() {
+            return Ok(());
+        }
+        let messages = items
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n");
+        eyre::bail!("{messages}")
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    #![allow(warnings, unused)]
+
+    use super::*;
+
+    fn diagnostics() -> Diagnostics {
+        let diagnostics = Diagnostics::new();
+        diagnostics.push(Diagnostic::new(
+            DiagnosticKind::MissingAsset,
+            "/b.html",
+            "/logo.png",
+        ));
+        diagnostics.push(Diagnostic::new(
+            DiagnosticKind::BrokenLink,
+            "/a.html",
+            "@/gone.md",
+        ));
+        diagnostics.push(Diagnostic::new(
+            DiagnosticKind::MissingAsset,
+            "/a.html",
+            "/logo.png",
+        ));
+        diagnostics.push(
+            Diagnostic::new(DiagnosticKind::FailedPipeline, "/a.html", "/style.css")
+                .with_message("sass exited with status 1"),
+        );
+        diagnostics
+    }
+
+    #[test]
+    fn ignores_duplicates() {
+        let diagnostics = diagnostics();
+        diagnostics.push(Diagnostic::new(
+            DiagnosticKind::BrokenLink,
+            "/a.html",
+            "@/gone.md",
+        ));
+        assert_eq!(diagnostics.len(), 4);
+    }
+
+    #[test]
+    fn groups_by_page_and_target() {
+        let diagnostics = diagnostics();
+        let pages = diagnostics.by_page();
+        assert_eq!(pages["/a.html"].len(), 3);
+        assert_eq!(pages["/b.html"].len(), 1);
+
+        let targets = diagnostics.by_target();
+        assert_eq!(targets["/logo.png"].len(), 2);
+    }
+
+    #[test]
+    fn reports_all_problems() {
+        assert_eq!(
+            diagnostics().report(),
+            "by page:
+  /a.html
+    broken link '@/gone.md'
+    missing asset '/logo.png'
+    failed pipeline '/style.css': sass exited with status 1
+  /b.html
+    missing asset '/logo.png'
+by target:
+  /logo.png (missing asset): /a.html, /b.html
+  /style.css (failed pipeline): /a.html
+  @/gone.md (broken link): /a.html"
+        );
+    }
+
+    #[test]
+    fn fails_check_with_diagnostics() {
+        assert!(Diagnostics::new().check().is_ok());
+        let err = diagnostics().check().unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("broken link '@/gone.md' on page '/a.html'"));
+    }
+}
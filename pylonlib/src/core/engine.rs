@@ -1,26 +1,84 @@
 use eyre::WrapErr;
+use once_cell::sync::OnceCell;
 use serde::Serialize;
 use std::{net::SocketAddr, sync::Arc, thread::JoinHandle};
-use tracing::{info, trace};
+use tracing::{info, trace, warn};
 
 use crate::{
+    core::build_manifest::{BuildManifest, SiteHashes, MANIFEST_FILE},
+    core::build_outputs::BuildOutputs,
+    core::build_report::BuildReport,
+    core::diagnostics::Diagnostics,
+    core::page::render::SiteWideContext,
     core::rules::{RuleProcessor, Rules},
     core::script_engine::ScriptEngine,
     core::Library,
     devserver::{broker::RenderBehavior, DevServer, EngineBroker},
-    discover::html_asset::HtmlAssets,
     render::Renderers,
     AbsPath, RelPath, Result, USER_LOG,
 };
 
 pub mod step;
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
 pub enum PipelineBehavior {
+    /// Run pipelines for every linked asset, even when it's up to date.
     Overwrite,
+    /// Only run pipelines for assets which are missing, or whose inputs changed.
+    #[default]
     NoOverwrite,
 }
 
+/// Whether the site is built for publishing or for the development server. Pipeline
+/// operations can be limited to one of them.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum BuildMode {
+    /// `pylon build`
+    #[default]
+    Production,
+    /// `pylon serve`
+    Development,
+}
+
+impl BuildMode {
+    /// Name used in rule scripts.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Production => "prod",
+            Self::Development => "dev",
+        }
+    }
+}
+
+impl std::str::FromStr for BuildMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "prod" => Ok(Self::Production),
+            "dev" => Ok(Self::Development),
+            other => Err(format!(
+                "unknown build mode '{other}', must be either 'prod' or 'dev'"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BuildOptions {
+    pub pipeline_behavior: PipelineBehavior,
+    /// Empty the output directory before building.
+    pub clean: bool,
+    /// Delete files in the output directory which weren't produced by the build.
+    pub prune: bool,
+    /// Build into a sibling of the output directory, which replaces the output
+    /// directory only when the build succeeds.
+    pub atomic: bool,
+    /// Maximum number of assets generated by pipelines at the same time. Defaults to
+    /// the number of CPUs.
+    pub jobs: Option<usize>,
+}
+
 pub type GlobalEnginePaths = Arc<EnginePaths>;
 
 #[derive(Debug, Clone, Serialize)]
@@ -72,11 +130,17 @@ impl EnginePaths {
     pub fn project_root(&self) -> &AbsPath {
         &self.project_root
     }
+
+    pub fn abs_build_manifest(&self) -> AbsPath {
+        self.project_root
+            .join(&RelPath::from_relative(MANIFEST_FILE))
+    }
 }
 
 #[derive(Debug)]
 pub struct Engine {
     paths: GlobalEnginePaths,
+    build_mode: BuildMode,
     renderers: Renderers,
 
     // these are reset when the user script is updated
@@ -87,6 +151,9 @@ pub struct Engine {
     // Contains all the site pages. Will be updated when needed
     // if running in devserver mode.
     library: Library,
+
+    // built on first use and cleared whenever the library or rules change
+    site_wide_context: OnceCell<SiteWideContext>,
 }
 
 impl Engine {
@@ -102,9 +169,18 @@ impl Engine {
     }
 
     pub fn library_mut(&mut self) -> &mut Library {
+        self.site_wide_context.take();
         &mut self.library
     }
 
+    /// The template context shared by every page. It's built once and reused until
+    /// the library or the rules change.
+    pub fn site_wide_context(&self) -> Result<&SiteWideContext> {
+        self.site_wide_context
+            .get_or_try_init(|| SiteWideContext::new(self))
+            .wrap_err("Failed building site-wide template context")
+    }
+
     pub fn rule_processor(&self) -> &RuleProcessor {
         &self.rule_processor
     }
@@ -113,6 +189,10 @@ impl Engine {
         Arc::clone(&self.paths)
     }
 
+    pub fn build_mode(&self) -> BuildMode {
+        self.build_mode
+    }
+
     pub fn with_broker<S: Into<SocketAddr> + std::fmt::Debug>(
         paths: GlobalEnginePaths,
         bind: S,
@@ -141,6 +221,10 @@ impl Engine {
     }
 
     pub fn new(paths: GlobalEnginePaths) -> Result<Engine> {
+        Self::with_build_mode(paths, BuildMode::default())
+    }
+
+    pub fn with_build_mode(paths: GlobalEnginePaths, build_mode: BuildMode) -> Result<Engine> {
         let renderers = Renderers::new(paths.clone()).wrap_err_with(|| {
             format!(
                 "failed initializing renderers using template root '{}'",
@@ -148,23 +232,29 @@ impl Engine {
             )
         })?;
 
-        let library = step::build_library(paths.clone(), &renderers).wrap_err_with(|| {
+        let mut library = step::build_library(paths.clone(), &renderers).wrap_err_with(|| {
             format!(
                 "failed building page store when initializing engine with engine paths '{:?}'",
                 paths
             )
         })?;
 
-        let (script_engine, rule_processor, rules) = step::load_rules(paths.clone(), &library)
-            .wrap_err_with(|| {
+        let (script_engine, rule_processor, rules) =
+            step::load_rules(paths.clone(), build_mode, &library).wrap_err_with(|| {
                 format!(
                     "failed loading rule script when initializing engine with paths '{:?}'",
                     paths
                 )
             })?;
 
+        step::add_generated_pages(&rules, &mut library)
+            .wrap_err("failed adding generated pages when initializing engine")?;
+        step::assign_templates(&rules, &renderers, &mut library)
+            .wrap_err("failed assigning templates when initializing engine")?;
+
         Ok(Self {
             paths,
+            build_mode,
             renderers,
 
             script_engine,
@@ -172,17 +262,30 @@ impl Engine {
             rule_processor,
 
             library,
+
+            site_wide_context: OnceCell::new(),
         })
     }
 
     pub fn reload_rules(&mut self) -> Result<()> {
         info!(target: USER_LOG, "reloading site rules script");
 
+        self.site_wide_context.take();
+
+        // generated pages are re-added by the reloaded rule script
+        self.library.retain(|page| !page.is_virtual());
+
         let (script_engine, rule_processor, rules) =
-            step::load_rules(self.paths(), &self.library).wrap_err("failed to reload rules")?;
+            step::load_rules(self.paths(), self.build_mode, &self.library)
+                .wrap_err("failed to reload rules")?;
         self.script_engine = script_engine;
         self.rule_processor = rule_processor;
         self.rules = rules;
+
+        step::add_generated_pages(&self.rules, &mut self.library)
+            .wrap_err("failed to add generated pages after reloading rules")?;
+        step::assign_templates(&self.rules, &self.renderers, &mut self.library)
+            .wrap_err("failed to assign templates after reloading rules")?;
         Ok(())
     }
 
@@ -196,8 +299,14 @@ impl Engine {
     pub fn rebuild_library(&mut self) -> Result<()> {
         info!(target: USER_LOG, "rebuilding library");
 
+        self.site_wide_context.take();
+
         self.library = step::build_library(self.paths(), &self.renderers)
             .wrap_err("Failed to rebuild the page store")?;
+        step::add_generated_pages(&self.rules, &mut self.library)
+            .wrap_err("Failed to add generated pages after rebuilding the page store")?;
+        step::assign_templates(&self.rules, &self.renderers, &mut self.library)
+            .wrap_err("Failed to assign templates after rebuilding the page store")?;
         Ok(())
     }
 
@@ -214,30 +323,189 @@ impl Engine {
     }
 
     pub fn build_site(&self) -> Result<()> {
-        use tap::prelude::*;
+        self.build_site_with_report(&BuildReport::new())
+    }
+
+    /// Builds the site, recording each step and the problems found in the report.
+    pub fn build_site_with_report(&self, report: &BuildReport) -> Result<()> {
+        self.build_site_with_options(&BuildOptions::default(), report)
+    }
+
+    pub fn build_site_with_options(
+        &self,
+        options: &BuildOptions,
+        report: &BuildReport,
+    ) -> Result<()> {
         info!(target: USER_LOG, "building site");
 
+        let result = if options.atomic {
+            self.run_staged_build(options, report)
+        } else {
+            self.run_build_steps(options, report)
+                .and_then(|manifest| self.save_manifest(&manifest))
+        };
+        match &result {
+            Ok(()) => {
+                report.finish();
+                info!(target: USER_LOG, "{}", report.summary());
+            }
+            Err(e) => report.fail(e),
+        }
+        result
+    }
+
+    /// Builds the site into a copy of the output directory, which then replaces the
+    /// output directory. The previous output is kept next to it.
+    fn run_staged_build(&self, options: &BuildOptions, report: &BuildReport) -> Result<()> {
+        report.begin_step("stage output");
+        let paths = step::stage_output_dir(self).wrap_err("Failed to stage output directory")?;
+        let staged = paths.abs_output_dir();
+
+        let result = Engine::with_build_mode(Arc::new(paths), self.build_mode)
+            .wrap_err("Failed to create engine for staged build")
+            .and_then(|engine| engine.run_build_steps(options, report));
+        let manifest = match result {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                if let Err(e) = std::fs::remove_dir_all(&staged) {
+                    warn!(target: USER_LOG, "failed to remove staged output '{staged}': {e}");
+                }
+                return Err(e);
+            }
+        };
+
+        report.begin_step("swap output");
+        step::swap_output_dir(self, &staged).wrap_err("Failed to swap output directory")?;
+        self.save_manifest(&manifest)
+    }
+
+    fn save_manifest(&self, manifest: &BuildManifest) -> Result<()> {
+        manifest
+            .save(self.paths().abs_build_manifest())
+            .wrap_err("Failed to save build manifest")
+    }
+
+    /// Runs every build step, returning the manifest of the build. The manifest should
+    /// be saved once the output is in place.
+    #[allow(clippy::too_many_lines)]
+    fn run_build_steps(
+        &self,
+        options: &BuildOptions,
+        report: &BuildReport,
+    ) -> Result<BuildManifest> {
+        use tap::prelude::*;
+
         let pages = self.library().iter().map(|(_, page)| page);
 
+        if options.clean {
+            report.begin_step("clean");
+            step::clean_output_dir(self).wrap_err("Failed to clean output directory")?;
+        }
+
         // lints
-        step::run_lints(self, pages.clone())
-            .wrap_err("Failed getting lints while building site")
-            .and_then(|lints| step::report::lints(&lints))?;
+        report.begin_step("lints");
+        let lints = step::run_lints(self, pages.clone())
+            .wrap_err("Failed getting lints while building site")?;
+        report.add_lints(&lints);
+        step::report::lints(&lints)?;
 
         let pages = pages.filter(|page| page.frontmatter.published);
 
+        // broken links, missing assets, and failed pipelines are reported together
+        // once the pipelines have run
+        let diagnostics = Diagnostics::new();
+
+        // pages built from the same inputs as the previous build are skipped. The
+        // manifest is only saved once the whole build succeeds.
+        let previous_manifest = BuildManifest::load(self.paths().abs_build_manifest());
+        let mut manifest = BuildManifest::new();
+        let hashes = SiteHashes::new(self).wrap_err("Failed hashing inputs during site build")?;
+
+        // everything else in the output directory is deleted when pruning
+        let outputs = BuildOutputs::new(self.paths().abs_output_dir());
+
         // rendering
-        step::render(self, pages)
-            .wrap_err("Failed to render pages during site build")?
+        report.begin_step("render");
+        let total_pages = pages.clone().count();
+        let pages = step::changed_pages(self, pages, &hashes, &previous_manifest, &mut manifest)
+            .wrap_err("Failed to find changed pages during site build")?;
+        let rendered = step::render(self, pages, &diagnostics, report)
+            .wrap_err("Failed to render pages during site build")?;
+        report.record_pages(rendered.len());
+        report.record_unchanged_pages(total_pages - rendered.len());
+        rendered
             .write_to_disk()
             .wrap_err("Failed to write rendered pages to disk during site build")?;
 
+        // template outputs
+        report.begin_step("template outputs");
+        step::render_template_outputs(self)
+            .wrap_err("Failed to render template outputs during site build")?
+            .write_to_disk()
+            .wrap_err("Failed to write template outputs to disk during site build")?;
+        step::record_template_outputs(self, &hashes, &mut manifest);
+        manifest.outputs().for_each(|uri| outputs.add_uri(uri));
+
+        // outputs of pages and template outputs which no longer exist
+        report.begin_step("stale outputs");
+        step::remove_stale_outputs(self, &previous_manifest, &manifest)
+            .wrap_err("Failed to remove stale outputs during site build")
+            .map(|removed| report.record_removed_outputs(removed))?;
+
+        // 404 page
+        report.begin_step("404 page");
+        step::write_not_found_page(self, &outputs)
+            .wrap_err("Failed to write 404 page during site build")?;
+
+        // sitemap
+        report.begin_step("sitemap");
+        step::write_sitemap(self, &outputs)
+            .wrap_err("Failed to write sitemap during site build")?;
+
+        // robots.txt and security.txt
+        report.begin_step("robots.txt and security.txt");
+        step::write_robots(self, &outputs)
+            .wrap_err("Failed to write robots.txt during site build")?;
+        step::write_security_txt(self, &outputs)
+            .wrap_err("Failed to write security.txt during site build")?;
+
+        // feeds
+        report.begin_step("feeds");
+        step::write_feeds(self, &outputs).wrap_err("Failed to write feeds during site build")?;
+
+        // search index
+        report.begin_step("search index");
+        step::write_search_index(self, &outputs)
+            .wrap_err("Failed to write search index during site build")?;
+
+        // content api
+        report.begin_step("content api");
+        step::write_content_api(self, &rendered, &outputs)
+            .wrap_err("Failed to write content api during site build")?;
+
         // mounts
+        report.begin_step("mounts");
         step::mount_directories(self.rules().mounts())
             .wrap_err("Failed to process mounts during site build")?;
+        for mount in self.rules().mounts() {
+            outputs
+                .add_mount(mount)
+                .wrap_err("Failed to list mounted files during site build")?;
+        }
 
+        // build steps generate files which aren't linked from documents, so they run
+        // before looking for linked assets
+        report.begin_step("build steps");
+        step::run_build_step_pipelines(self, options.jobs, &outputs, report)
+            .wrap_err("Failed to run build steps during site build")?;
+
+        // template outputs are not scanned for assets or minified
+        report.begin_step("asset discovery");
         let html_files = step::get_all_output_files(self, pathmarker::HtmlFile)
-            .wrap_err("Failed to discover HTML files during site build")?;
+            .wrap_err("Failed to discover HTML files during site build")?
+            .into_iter()
+            .filter(|file| step::filter::not_template_output(self.rules(), file.as_sys_path()))
+            .collect::<Vec<_>>();
 
         // build list of assets needed for the site (stuff linked in HTML pages)
         let required_assets = step::build_required_asset_list(self, html_files.iter())
@@ -246,30 +514,79 @@ impl Engine {
                 // We don't care about links that exist offsite (for now)
                 assets.drop_offsite();
                 assets
-            })?
-            .into_iter()
-            // filter out stuff that already exists (possibly already mounted, etc)
-            .filter(|(target, _)| !target.exists())
-            // we only need the asset to build an HtmlAssets
-            .map(|(_, asset)| asset)
-            .collect::<HtmlAssets>();
+            })?;
+        // linked assets are kept when pruning, including ones which already exist
+        let linked_assets = required_assets
+            .iter()
+            .map(|(target, _)| target.clone())
+            .collect::<Vec<_>>();
+        // filter out stuff that already exists (possibly already mounted, etc), unless
+        // it needs to be generated again
+        let required_assets = step::drop_unchanged_assets(
+            self,
+            required_assets,
+            &previous_manifest,
+            &mut manifest,
+            options.pipeline_behavior,
+            report,
+        )
+        .wrap_err("Failed to check pipeline inputs during site build")?;
 
         // run pipelines
-        step::run_pipelines(self, &required_assets)
-            .wrap_err("Failed to run pipelines during site build")?
-            .pipe_borrow(step::find_unpipelined_assets)
-            .pipe_borrow(step::report::missing_assets)?;
+        report.begin_step("pipelines");
+        let missing_assets = step::run_pipelines(
+            self,
+            &required_assets,
+            options.jobs,
+            &outputs,
+            &diagnostics,
+            report,
+        )
+        .wrap_err("Failed to run pipelines during site build")?
+        .pipe_borrow(step::find_unpipelined_assets);
+        for target in linked_assets.iter().filter(|target| target.exists()) {
+            outputs.add_path(target.as_path());
+        }
+        step::record_missing_assets(&required_assets, &missing_assets, &diagnostics);
+        report.add_diagnostics(self.library(), &diagnostics);
+        step::report::diagnostics(&diagnostics)?;
+
+        // anchors
+        report.begin_step("anchors");
+        step::check_anchors(self, html_files.iter())
+            .wrap_err("Failed to check anchors during site build")?;
 
         // HTML minification
-        step::minify_html_files(self, html_files.iter()).wrap_err("Failed to minify HTML files")?;
+        report.begin_step("html minification");
+        step::minify_html_files(self, html_files.iter())
+            .wrap_err("Failed to minify HTML files")
+            .map(|stats| report.record_minified(stats))?;
 
+        report.begin_step("css minification");
         let css_files = step::get_all_output_files(self, pathmarker::CssFile)
-            .wrap_err("Failed to discover CSS files during site build")?;
+            .wrap_err("Failed to discover CSS files during site build")?
+            .into_iter()
+            .filter(|file| step::filter::not_template_output(self.rules(), file.as_sys_path()))
+            .collect::<Vec<_>>();
 
         // CSS minification
-        step::minify_css_files(self, css_files.iter()).wrap_err("Failed to minify CSS files")?;
+        step::minify_css_files(self, css_files.iter())
+            .wrap_err("Failed to minify CSS files")
+            .map(|stats| report.record_minified(stats))?;
+
+        // the files as this build left them
+        step::record_written_outputs(self, &mut manifest)
+            .wrap_err("Failed to record written outputs during site build")?;
+
+        if options.prune {
+            report.begin_step("prune");
+            manifest.assets().for_each(|uri| outputs.add_uri(uri));
+            step::prune_outputs(self, &outputs)
+                .wrap_err("Failed to prune output directory")
+                .map(|removed| report.record_removed_outputs(removed))?;
+        }
 
-        Ok(())
+        Ok(manifest)
     }
 
     pub fn start_devserver(
@@ -419,6 +736,74 @@ pub mod test {
         assert_eq!(engine.rules().lints().len(), 1);
     }
 
+    #[test]
+    fn reloads_generated_pages() {
+        let old_rules = r#"
+            rules.add_page("/a.html", "default.tera", #{});
+            rules.add_page("/b.html", "default.tera", #{});
+        "#;
+
+        let new_rules = r#"
+            rules.add_page("/a.html", "default.tera", #{});
+        "#;
+
+        let tree = temptree! {
+          "rules.rhai": old_rules,
+          templates: {
+              "default.tera": "",
+          },
+          target: {},
+          src: {},
+          syntax_themes: {}
+        };
+
+        let paths = crate::test::default_test_paths(&tree);
+
+        let mut engine = Engine::new(paths).unwrap();
+        assert_eq!(engine.library().iter().count(), 2);
+
+        std::fs::write(tree.path().join("rules.rhai"), new_rules)
+            .expect("failed to write new rules");
+
+        engine.reload_rules().expect("failed to reload rules");
+        assert_eq!(engine.library().iter().count(), 1);
+        assert!(engine.library().get(&"/b.html".into()).is_none());
+    }
+
+    #[test]
+    fn rebuilds_site_wide_context_after_library_changes() {
+        let tree = temptree! {
+          "rules.rhai": "",
+          templates: {
+              "default.tera": "",
+          },
+          target: {},
+          src: {
+              "a.md": "+++\n+++\n",
+              "b.md": "+++\n+++\n",
+          },
+          syntax_themes: {}
+        };
+
+        let paths = crate::test::default_test_paths(&tree);
+        let mut engine = Engine::new(paths).unwrap();
+
+        let library = |engine: &Engine| {
+            let context = engine.site_wide_context().unwrap();
+            context.get("library").unwrap().as_object().unwrap().len()
+        };
+        assert_eq!(library(&engine), 2);
+        // the same context is shared until something changes
+        assert!(std::ptr::eq(
+            engine.site_wide_context().unwrap(),
+            engine.site_wide_context().unwrap()
+        ));
+
+        std::fs::remove_file(tree.path().join("src/b.md")).unwrap();
+        engine.rebuild_library().unwrap();
+        assert_eq!(library(&engine), 1);
+    }
+
     #[test]
     fn reloads_template_engines() {
         let tree = temptree! {
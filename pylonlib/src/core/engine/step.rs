@@ -2,15 +2,20 @@ use std::{
     collections::{HashMap, HashSet},
     ffi::OsStr,
     path::Path,
+    time::Instant,
 };
 
 use eyre::WrapErr;
 use itertools::Itertools;
-use tracing::{debug, info, trace};
+use tracing::{debug, info, trace, warn};
 use typed_path::{AbsPath, ConfirmedPath, PathMarker, RelPath, SysPath};
 
 use crate::{
     core::{
+        build_manifest::{AssetInputs, BuildManifest, SiteHashes},
+        build_outputs::{self, BuildOutputs},
+        build_report::{BuildReport, MinifyStats},
+        diagnostics::{Diagnostic, DiagnosticKind, Diagnostics},
         page::{lint::LintResults, LintResult, RenderedPage, RenderedPageCollection},
         rules::{Mount, RuleProcessor, Rules},
         script_engine::{ScriptEngine, ScriptEngineConfig},
@@ -23,16 +28,16 @@ use crate::{
     Renderers, Result, USER_LOG,
 };
 
-use super::{Engine, GlobalEnginePaths};
+use super::{BuildMode, Engine, EnginePaths, GlobalEnginePaths, PipelineBehavior};
 
 pub mod report {
-    use std::collections::HashSet;
-
     use eyre::bail;
-    use tracing::{error, warn};
+    use tracing::{error, info, warn};
 
-    use crate::core::page::lint::LintResults;
-    use crate::discover::html_asset::HtmlAsset;
+    use crate::core::diagnostics::Diagnostics;
+    use crate::core::link_check::LinkReport;
+    use crate::core::page::lint::{LintLevel, LintResults};
+    use crate::discover::anchor::MissingAnchor;
     use crate::{Result, USER_LOG};
 
     pub fn lints(lints: &LintResults) -> Result<()> {
@@ -57,23 +62,65 @@ pub mod report {
         Ok(())
     }
 
-    pub fn missing_assets(assets: &HashSet<&HtmlAsset>) -> Result<()> {
-        for asset in assets {
-            error!(asset = ?asset, "missing asset or no pipeline defined");
+    pub fn offsite_links(report: &LinkReport) -> Result<()> {
+        info!(
+            target: USER_LOG,
+            "checked {} offsite links ({} cached)",
+            report.checked + report.cached,
+            report.cached
+        );
+        for (page, links) in &report.broken {
+            for (link, reason) in links {
+                error!(target: USER_LOG, link=%link, reason=%reason, page=%page);
+            }
         }
-        if !assets.is_empty() {
-            bail!("one or more assets are missing");
+        let broken = report.broken_links();
+        if broken > 0 {
+            bail!("{broken} broken offsite links found");
         }
         Ok(())
     }
+
+    pub fn missing_anchors(missing: &[MissingAnchor], level: LintLevel) -> Result<()> {
+        for anchor in missing {
+            match level {
+                LintLevel::Warn => {
+                    warn!(target: USER_LOG, link=%anchor.link, reason=%anchor.reason, page=%anchor.page);
+                }
+                LintLevel::Deny => {
+                    error!(target: USER_LOG, link=%anchor.link, reason=%anchor.reason, page=%anchor.page);
+                }
+            }
+        }
+        if level == LintLevel::Deny && !missing.is_empty() {
+            bail!("{} links to missing anchors found", missing.len());
+        }
+        Ok(())
+    }
+
+    pub fn diagnostics(diagnostics: &Diagnostics) -> Result<()> {
+        if diagnostics.is_empty() {
+            return Ok(());
+        }
+        error!(target: USER_LOG, "problems found during site build\n{}", diagnostics.report());
+        bail!("{} problems found during site build", diagnostics.len());
+    }
 }
 
 pub mod filter {
+    use crate::core::rules::Rules;
     use crate::discover::html_asset::HtmlAsset;
+    use crate::SysPath;
 
     pub fn not_on_disk(asset: &HtmlAsset) -> bool {
         !asset.asset_target_path().target().exists()
     }
+
+    pub fn not_template_output(rules: &Rules, path: &SysPath) -> bool {
+        !rules
+            .template_outputs()
+            .any(|output| output.target() == path)
+    }
 }
 
 pub fn find_unpipelined_assets<'a>(
@@ -90,11 +137,17 @@ pub fn run_lints<'a, P: IntoIterator<Item = &'a Page>>(
     engine: &Engine,
     pages: P,
 ) -> Result<LintResults> {
+    use rayon::prelude::*;
+
     info!(target: USER_LOG, "running lints");
 
+    let pages = pages.into_iter().collect::<Vec<_>>();
+    // results are collected in page order, so the first error is always the same
     let lint_results: Vec<Vec<LintResult>> = pages
-        .into_iter()
+        .par_iter()
         .map(|page| crate::core::page::lint(engine.rule_processor(), engine.rules().lints(), page))
+        .collect::<Vec<_>>()
+        .into_iter()
         .try_collect()
         .wrap_err("Failed building LintResult collection")?;
 
@@ -106,18 +159,489 @@ pub fn run_lints<'a, P: IntoIterator<Item = &'a Page>>(
 pub fn render<'a, P: IntoIterator<Item = &'a Page>>(
     engine: &Engine,
     pages: P,
+    diagnostics: &Diagnostics,
+    report: &BuildReport,
 ) -> Result<RenderedPageCollection> {
+    use rayon::prelude::*;
+
     info!(target: USER_LOG, "rendering docs");
 
+    let pages = pages.into_iter().collect::<Vec<_>>();
+    // results are collected in page order, so the first error is always the same
     let rendered: Vec<RenderedPage> = pages
+        .par_iter()
+        .map(|page| {
+            let started = Instant::now();
+            let rendered = crate::core::page::render(engine, page, diagnostics)
+                .wrap_err_with(|| crate::core::page::render::RenderError::new(page))?;
+            let template = page
+                .frontmatter
+                .template_name
+                .as_ref()
+                .map_or("(none)", |name| name.as_str());
+            report.record_template(template, started.elapsed());
+            Ok::<_, eyre::Report>(rendered)
+        })
+        .collect::<Vec<_>>()
         .into_iter()
-        .map(|page| crate::core::page::render(engine, page))
         .try_collect()
         .wrap_err("Failed building RenderedPage collection")?;
 
     Ok(RenderedPageCollection::from_vec(rendered))
 }
 
+pub fn render_template_outputs(engine: &Engine) -> Result<RenderedPageCollection> {
+    info!(target: USER_LOG, "rendering template outputs");
+
+    let rendered: Vec<RenderedPage> = engine
+        .rules()
+        .template_outputs()
+        .map(|output| crate::core::page::render::render_template_output(engine, output))
+        .try_collect()
+        .wrap_err("Failed building template output collection")?;
+
+    Ok(RenderedPageCollection::from_vec(rendered))
+}
+
+/// Pages whose inputs changed since the previous build, or whose output is missing.
+/// The inputs of every page are added to the manifest.
+pub fn changed_pages<'a, P: IntoIterator<Item = &'a Page>>(
+    engine: &Engine,
+    pages: P,
+    hashes: &SiteHashes,
+    previous: &BuildManifest,
+    manifest: &mut BuildManifest,
+) -> Result<Vec<&'a Page>> {
+    // the content api lists every page, so nothing can be skipped
+    let render_all = engine.rules().content_api_enabled();
+
+    let mut changed = vec![];
+    for page in pages {
+        let target = page.target();
+        let uri = output_uri(&target);
+        let inputs = hashes
+            .page_inputs(page)
+            .wrap_err_with(|| format!("Failed hashing inputs of page '{}'", page.uri()))?;
+        // the rendered file is also missing or changed when something else wrote it,
+        // such as `pylon serve`
+        let written = previous.is_written(&uri, target.to_absolute_path().as_path());
+        if render_all || !previous.is_unchanged(&uri, &inputs) || !written {
+            changed.push(page);
+        } else {
+            trace!(uri = %uri, "page unchanged since previous build");
+        }
+        manifest.insert(uri, inputs);
+    }
+    Ok(changed)
+}
+
+/// Adds the template outputs to the manifest, so they are removed once they are no
+/// longer in the rule script.
+pub fn record_template_outputs(engine: &Engine, hashes: &SiteHashes, manifest: &mut BuildManifest) {
+    for output in engine.rules().template_outputs() {
+        manifest.insert(
+            output_uri(output.target()),
+            hashes.inputs(output.template().as_ref().as_bytes()),
+        );
+    }
+}
+
+/// Records the contents of every output and generated asset, so the next build can
+/// tell when they were changed by something else.
+pub fn record_written_outputs(engine: &Engine, manifest: &mut BuildManifest) -> Result<()> {
+    let output_dir = engine.paths().abs_output_dir();
+    let uris = manifest
+        .outputs()
+        .chain(manifest.assets())
+        .map(ToOwned::to_owned)
+        .collect::<Vec<_>>();
+    for uri in uris {
+        if let Some(relative) = build_outputs::output_path(&uri) {
+            manifest.record_written(&uri, output_dir.join(&relative).as_path())?;
+        }
+    }
+    Ok(())
+}
+
+/// Deletes the outputs of the previous build which have no source anymore. Returns the
+/// number of files deleted.
+pub fn remove_stale_outputs(
+    engine: &Engine,
+    previous: &BuildManifest,
+    manifest: &BuildManifest,
+) -> Result<usize> {
+    let output_dir = engine.paths().abs_output_dir();
+    let mut removed = 0;
+    for uri in previous.removed_outputs(manifest) {
+        let Some(relative) = build_outputs::output_path(uri) else {
+            warn!(target: USER_LOG, "ignoring invalid output uri '{uri}'");
+            continue;
+        };
+        let target = output_dir.join(&relative);
+        if target.exists() {
+            info!(target: USER_LOG, "removing '{uri}' because its source no longer exists");
+            std::fs::remove_file(&target)
+                .wrap_err_with(|| format!("Failed to remove stale output '{target}'"))?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// Suffix of the sibling directory which an atomic build is written to.
+pub const STAGED_OUTPUT_SUFFIX: &str = ".tmp";
+
+/// Suffix of the sibling directory which keeps the output replaced by an atomic build.
+pub const PREVIOUS_OUTPUT_SUFFIX: &str = ".prev";
+
+/// The output directory with `suffix` appended to its name.
+fn sibling_output_dir(paths: &EnginePaths, suffix: &str) -> RelPath {
+    RelPath::from_relative(format!("{}{suffix}", paths.output_dir()))
+}
+
+/// Copies the output directory to a sibling directory, and returns the paths to
+/// build the site into the copy. Copying keeps unchanged pages and preserved
+/// outputs.
+pub fn stage_output_dir(engine: &Engine) -> Result<EnginePaths> {
+    use fs_extra::dir::CopyOptions;
+
+    let paths = engine.paths();
+    let output_dir = paths.abs_output_dir();
+    let staged_dir = sibling_output_dir(&paths, STAGED_OUTPUT_SUFFIX);
+    let staged = paths.project_root().join(&staged_dir);
+
+    // left over from an interrupted build
+    if staged.exists() {
+        std::fs::remove_dir_all(&staged)
+            .wrap_err_with(|| format!("Failed to remove old staged output '{staged}'"))?;
+    }
+    std::fs::create_dir_all(&staged)
+        .wrap_err_with(|| format!("Failed to create staged output '{staged}'"))?;
+
+    if output_dir.exists() {
+        debug!(target: USER_LOG, "copying '{output_dir}' to '{staged}'");
+        let options = CopyOptions {
+            copy_inside: true,
+            content_only: true,
+            ..CopyOptions::default()
+        };
+        fs_extra::dir::copy(&output_dir, &staged, &options)
+            .wrap_err_with(|| format!("Failed to copy '{output_dir}' to '{staged}'"))?;
+    }
+
+    let mut paths = EnginePaths::clone(&paths);
+    paths.output_dir = staged_dir;
+    Ok(paths)
+}
+
+/// Replaces the output directory with the staged output, moving the current output
+/// to a sibling directory.
+pub fn swap_output_dir(engine: &Engine, staged: &AbsPath) -> Result<()> {
+    let paths = engine.paths();
+    let output_dir = paths.abs_output_dir();
+    let previous = paths
+        .project_root()
+        .join(&sibling_output_dir(&paths, PREVIOUS_OUTPUT_SUFFIX));
+
+    if previous.exists() {
+        std::fs::remove_dir_all(&previous)
+            .wrap_err_with(|| format!("Failed to remove previous output '{previous}'"))?;
+    }
+    if output_dir.exists() {
+        std::fs::rename(&output_dir, &previous)
+            .wrap_err_with(|| format!("Failed to move '{output_dir}' to '{previous}'"))?;
+    }
+    if let Err(e) = std::fs::rename(staged, &output_dir) {
+        // put the previous output back, so the output directory doesn't go missing
+        if previous.exists() && !output_dir.exists() {
+            std::fs::rename(&previous, &output_dir)
+                .wrap_err_with(|| format!("Failed to restore '{output_dir}' from '{previous}'"))?;
+        }
+        return Err(e).wrap_err_with(|| format!("Failed to move '{staged}' to '{output_dir}'"));
+    }
+
+    info!(target: USER_LOG, "replaced '{output_dir}', previous output kept at '{previous}'");
+    Ok(())
+}
+
+/// Deletes every file in the output directory, except preserved outputs, along with
+/// the build manifest. Returns the number of files deleted.
+pub fn clean_output_dir(engine: &Engine) -> Result<usize> {
+    info!(target: USER_LOG, "cleaning output directory");
+
+    let manifest = engine.paths().abs_build_manifest();
+    if manifest.exists() {
+        std::fs::remove_file(&manifest)
+            .wrap_err_with(|| format!("Failed to remove build manifest '{manifest}'"))?;
+    }
+
+    remove_output_files(engine, |path| {
+        debug!(target: USER_LOG, "removing '{}'", path.display());
+        true
+    })
+}
+
+/// Deletes every file in the output directory which wasn't produced by this build,
+/// except preserved outputs. Returns the number of files deleted.
+pub fn prune_outputs(engine: &Engine, outputs: &BuildOutputs) -> Result<usize> {
+    remove_output_files(engine, |path| {
+        let prune = !outputs.contains(path);
+        if prune {
+            info!(
+                target: USER_LOG,
+                "pruning '{}' because it wasn't produced by this build",
+                path.display()
+            );
+        }
+        prune
+    })
+}
+
+/// Deletes the files in the output directory selected by `remove`, skipping preserved
+/// outputs, and then any directories left empty.
+fn remove_output_files<F>(engine: &Engine, remove: F) -> Result<usize>
+where
+    F: Fn(&Path) -> bool,
+{
+    let output_dir = engine.paths().abs_output_dir();
+    let is_preserved = |path: &Path| {
+        path.strip_prefix(&output_dir)
+            .map_or(true, |path| engine.rules().is_preserved_output(path))
+    };
+
+    let files =
+        crate::discover::get_all_paths(&output_dir, &|path| !is_preserved(path) && remove(path))?;
+    for file in &files {
+        std::fs::remove_file(file)
+            .wrap_err_with(|| format!("Failed to remove output file '{file}'"))?;
+    }
+    remove_empty_dirs(output_dir.as_path(), &is_preserved)?;
+
+    Ok(files.len())
+}
+
+fn remove_empty_dirs(dir: &Path, is_preserved: &dyn Fn(&Path) -> bool) -> Result<()> {
+    for entry in std::fs::read_dir(dir)
+        .wrap_err_with(|| format!("Failed to read directory '{}'", dir.display()))?
+    {
+        let path = entry
+            .wrap_err_with(|| format!("Failed to read directory entry in '{}'", dir.display()))?
+            .path();
+        if !path.is_dir() || is_preserved(&path) {
+            continue;
+        }
+        remove_empty_dirs(&path, is_preserved)?;
+        let is_empty = std::fs::read_dir(&path)
+            .wrap_err_with(|| format!("Failed to read directory '{}'", path.display()))?
+            .next()
+            .is_none();
+        if is_empty {
+            std::fs::remove_dir(&path).wrap_err_with(|| {
+                format!("Failed to remove empty directory '{}'", path.display())
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Copies the 404 page to `/404.html` when a different page is used.
+pub fn write_not_found_page(engine: &Engine, outputs: &BuildOutputs) -> Result<()> {
+    use crate::core::rules::NOT_FOUND_PAGE;
+
+    let uri = engine.rules().not_found_page();
+    if uri == NOT_FOUND_PAGE {
+        return Ok(());
+    }
+
+    let output_dir = engine.paths().abs_output_dir();
+    let relative = build_outputs::output_path(uri)
+        .ok_or_else(|| eyre::eyre!("invalid uri '{uri}' for 404 page"))?;
+    let source = output_dir.join(&relative);
+    if !source.exists() {
+        eyre::bail!("404 page '{uri}' was not rendered, make sure it is published");
+    }
+    let target = output_dir.join(&RelPath::from_relative(&NOT_FOUND_PAGE[1..]));
+    std::fs::copy(&source, &target)
+        .wrap_err_with(|| format!("Failed to copy 404 page from '{source}' to '{target}'"))?;
+    outputs.add_path(&target);
+
+    Ok(())
+}
+
+pub fn write_sitemap(engine: &Engine, outputs: &BuildOutputs) -> Result<()> {
+    if !engine.rules().sitemap_enabled() {
+        return Ok(());
+    }
+    if engine
+        .rules()
+        .template_outputs()
+        .any(|output| output.uri() == "/sitemap.xml")
+    {
+        debug!(target: USER_LOG, "sitemap provided by a template output");
+        return Ok(());
+    }
+    let Some(base_url) = engine.rules().base_url() else {
+        warn!(
+            target: USER_LOG,
+            "a base url is required to generate sitemap.xml, use `rules.set_base_url` in the rule script"
+        );
+        return Ok(());
+    };
+
+    info!(target: USER_LOG, "generating sitemap");
+
+    let pages = engine
+        .library()
+        .iter()
+        .map(|(_, page)| page)
+        .filter(|page| !engine.rules().is_not_found_page(page.uri().as_str()));
+    for file in crate::core::sitemap::generate(base_url, pages) {
+        write_output_file(engine, outputs, &file.uri, &file.xml)
+            .wrap_err("Failed to write sitemap")?;
+    }
+
+    Ok(())
+}
+
+pub fn write_robots(engine: &Engine, outputs: &BuildOutputs) -> Result<()> {
+    const URI: &str = "/robots.txt";
+
+    let Some(options) = engine.rules().robots() else {
+        return Ok(());
+    };
+    if engine
+        .rules()
+        .template_outputs()
+        .any(|output| output.uri() == URI)
+    {
+        debug!(target: USER_LOG, "robots.txt provided by a template output");
+        return Ok(());
+    }
+
+    info!(target: USER_LOG, "generating robots.txt");
+
+    let has_sitemap = engine.rules().sitemap_enabled()
+        || engine
+            .rules()
+            .template_outputs()
+            .any(|output| output.uri() == "/sitemap.xml");
+    let sitemap_url = engine
+        .rules()
+        .base_url()
+        .filter(|_| has_sitemap)
+        .map(|base_url| format!("{base_url}/sitemap.xml"));
+    if options.sitemap && sitemap_url.is_none() {
+        warn!(
+            target: USER_LOG,
+            "robots.txt links to the sitemap only when the sitemap is enabled and a base url is set"
+        );
+    }
+
+    let robots = crate::core::robots::generate(options, sitemap_url.as_deref());
+    write_output_file(engine, outputs, URI, &robots).wrap_err("Failed to write robots.txt")
+}
+
+pub fn write_security_txt(engine: &Engine, outputs: &BuildOutputs) -> Result<()> {
+    use crate::core::security_txt::SECURITY_TXT;
+
+    let Some(security_txt) = engine.rules().security_txt() else {
+        return Ok(());
+    };
+    if engine
+        .rules()
+        .template_outputs()
+        .any(|output| output.uri() == SECURITY_TXT)
+    {
+        debug!(target: USER_LOG, "security.txt provided by a template output");
+        return Ok(());
+    }
+    if security_txt.is_expired() {
+        warn!(
+            target: USER_LOG,
+            "security.txt expired on {}, update `expires` in the rule script",
+            security_txt.expires
+        );
+    }
+
+    info!(target: USER_LOG, "generating security.txt");
+
+    let contents = security_txt.generate(engine.rules().base_url());
+    write_output_file(engine, outputs, SECURITY_TXT, &contents)
+        .wrap_err("Failed to write security.txt")
+}
+
+pub fn write_feeds(engine: &Engine, outputs: &BuildOutputs) -> Result<()> {
+    let mut feeds = engine.rules().feeds().peekable();
+    if feeds.peek().is_none() {
+        return Ok(());
+    }
+    let base_url = engine.rules().base_url().ok_or_else(|| {
+        eyre::eyre!(
+            "a base url is required to generate feeds, use `rules.set_base_url` in the rule script"
+        )
+    })?;
+
+    info!(target: USER_LOG, "generating feeds");
+
+    for feed in feeds {
+        let xml = crate::core::feed::generate(engine, feed, base_url)
+            .wrap_err_with(|| format!("Failed to generate feed '{}'", feed.uri()))?;
+        write_output_file(engine, outputs, feed.uri(), &xml).wrap_err("Failed to write feed")?;
+    }
+
+    Ok(())
+}
+
+pub fn write_search_index(engine: &Engine, outputs: &BuildOutputs) -> Result<()> {
+    let Some(options) = engine.rules().search() else {
+        return Ok(());
+    };
+
+    info!(target: USER_LOG, "generating search index");
+
+    for file in crate::core::search::generate(engine, options)? {
+        write_output_file(engine, outputs, &file.uri, &file.json)
+            .wrap_err("Failed to write search index")?;
+    }
+
+    Ok(())
+}
+
+pub fn write_content_api(
+    engine: &Engine,
+    rendered: &RenderedPageCollection,
+    outputs: &BuildOutputs,
+) -> Result<()> {
+    if !engine.rules().content_api_enabled() {
+        return Ok(());
+    }
+
+    info!(target: USER_LOG, "generating content api");
+
+    for file in crate::core::content_api::generate(engine.library(), rendered)? {
+        write_output_file(engine, outputs, &file.uri, &file.json)
+            .wrap_err("Failed to write content api")?;
+    }
+
+    Ok(())
+}
+
+/// Writes a generated file to the output directory. `uri` must start with a slash.
+fn write_output_file(
+    engine: &Engine,
+    outputs: &BuildOutputs,
+    uri: &str,
+    contents: &str,
+) -> Result<()> {
+    let relative = build_outputs::output_path(uri)
+        .ok_or_else(|| eyre::eyre!("invalid uri '{uri}' for generated file"))?;
+    outputs.add_uri(uri);
+    let target = engine.paths().abs_output_dir().join(&relative);
+    crate::util::make_parent_dirs(&target.pop())?;
+    std::fs::write(&target, contents)
+        .wrap_err_with(|| format!("Failed to write generated file to '{target}'"))
+}
+
 pub fn mount_directories<'a, M: IntoIterator<Item = &'a Mount>>(mounts: M) -> Result<()> {
     use fs_extra::dir::CopyOptions;
 
@@ -151,6 +675,7 @@ pub fn mount_directories<'a, M: IntoIterator<Item = &'a Mount>>(mounts: M) -> Re
 
 pub fn load_rules(
     engine_paths: GlobalEnginePaths,
+    build_mode: BuildMode,
     library: &Library,
 ) -> Result<(ScriptEngine, RuleProcessor, Rules)> {
     let script_engine_config = ScriptEngineConfig::new();
@@ -167,56 +692,281 @@ pub fn load_rules(
         })?;
 
     let (rule_processor, rules) = script_engine
-        .build_rules(engine_paths, library, rule_script)
+        .build_rules(engine_paths, build_mode, library, rule_script)
         .wrap_err("failed to build Rules structure")?;
 
     Ok((script_engine, rule_processor, rules))
 }
 
+/// Drops the assets which don't need to be generated: assets which already exist,
+/// unless a pipeline generates them and its inputs changed since the previous build.
+/// The inputs of each asset generated by a pipeline are added to the manifest.
+pub fn drop_unchanged_assets(
+    engine: &Engine,
+    html_assets: HtmlAssets,
+    previous: &BuildManifest,
+    manifest: &mut BuildManifest,
+    behavior: PipelineBehavior,
+    report: &BuildReport,
+) -> Result<HtmlAssets> {
+    let root = engine.paths().project_root().clone();
+    let output_dir = engine.paths().abs_output_dir();
+
+    let mut required = HashMap::new();
+    // extra files written by pipelines are satisfied by the asset which writes them
+    let mut produced = HashSet::new();
+    for (target, assets) in html_assets {
+        // pipelines run using the first page linking to the asset
+        let Some(html) = assets.first() else {
+            continue;
+        };
+        let asset_uri = html.asset_target_uri();
+        let pipelines = engine
+            .rules()
+            .pipelines()
+            .filter(|pipeline| pipeline.is_match(asset_uri.as_str()))
+            .collect::<Vec<_>>();
+
+        if pipelines.is_empty() {
+            if !target.exists() {
+                required.insert(target, assets);
+            }
+            continue;
+        }
+
+        let mut inputs = AssetInputs::default();
+        let mut files = vec![];
+        let mut outputs = vec![];
+        // files written by the pipelines, which are checked against the manifest
+        let mut written = vec![(asset_uri.as_str().to_owned(), target.clone())];
+        for pipeline in pipelines {
+            inputs.add_pipeline(pipeline.definition());
+            for file in pipeline.input_files(asset_uri)? {
+                inputs.add_source(root.as_path(), file.as_path())?;
+                files.push(file);
+            }
+            for output in pipeline.output_paths(asset_uri)? {
+                if let Ok(relative) = output.strip_prefix(output_dir.as_path()) {
+                    let uri = format!("/{}", relative.as_path().to_string_lossy());
+                    inputs.add_output(uri.clone());
+                    written.push((uri, output.clone()));
+                }
+                outputs.push(output);
+            }
+        }
+
+        let needed = !target.exists()
+            || outputs.iter().any(|output| !output.exists())
+            || behavior == PipelineBehavior::Overwrite
+            || match previous.asset(asset_uri.as_str()) {
+                Some(recorded) => {
+                    recorded != &inputs
+                        || written
+                            .iter()
+                            .any(|(uri, file)| !previous.is_written(uri, file.as_path()))
+                }
+                // assets from before the manifest existed are compared by time
+                None => is_newer(&files, &target),
+            };
+        manifest.insert_asset(asset_uri.as_str(), inputs);
+
+        if needed {
+            if target.exists() {
+                debug!(target: USER_LOG, asset = %asset_uri.as_str(), "pipeline inputs changed");
+            }
+            required.insert(target, assets);
+        } else {
+            trace!(asset = %asset_uri.as_str(), "asset unchanged since previous build");
+            report.record_unchanged_asset();
+        }
+        produced.extend(outputs);
+    }
+    required.retain(|target, _| !produced.contains(target));
+    Ok(HtmlAssets::from_hashmap(required))
+}
+
+/// Whether any of the files were modified after the target.
+fn is_newer(files: &[AbsPath], target: &AbsPath) -> bool {
+    let modified = |path: &AbsPath| {
+        std::fs::metadata(path.as_path())
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    };
+    let Some(target) = modified(target) else {
+        return true;
+    };
+    files
+        .iter()
+        .any(|file| modified(file).is_none_or(|modified| modified > target))
+}
+
+/// Thread pool running up to `jobs` pipelines at a time, or one per CPU when `None`.
+fn pipeline_pool(jobs: Option<usize>) -> Result<rayon::ThreadPool> {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs.unwrap_or(0))
+        .build()
+        .wrap_err("Failed to create thread pool for pipelines")
+}
+
+/// Runs every build step, with up to `jobs` files generated at a time (one per CPU
+/// when `None`). The first failure cancels the files which haven't started yet.
+pub fn run_build_step_pipelines(
+    engine: &Engine,
+    jobs: Option<usize>,
+    outputs: &BuildOutputs,
+    report: &BuildReport,
+) -> Result<()> {
+    use rayon::prelude::*;
+
+    let output_dir = engine.paths().abs_output_dir();
+    let mut queue = vec![];
+    for build_step in engine.rules().build_steps() {
+        let files = build_step.jobs(&output_dir).wrap_err_with(|| {
+            format!(
+                "Failed to find files for build step '{}'",
+                build_step.name()
+            )
+        })?;
+        queue.extend(files.into_iter().map(|job| (build_step, job)));
+    }
+    if queue.is_empty() {
+        return Ok(());
+    }
+
+    info!(target: USER_LOG, "running build steps");
+
+    pipeline_pool(jobs)?.install(|| {
+        queue.par_iter().try_for_each(|(build_step, job)| {
+            let started = Instant::now();
+            let result = build_step.run(job);
+            report.record_pipeline(&build_step.name(), started.elapsed());
+            let output =
+                result.wrap_err_with(|| format!("Build step failed to generate '{}'", job.uri))?;
+
+            // output is logged once per file so it isn't interleaved with other build steps
+            debug!(target: USER_LOG, asset = %job.uri, "ran build step\n{}", output.trim_end());
+            outputs.add_path(&job.target);
+            for path in build_step.output_paths(job) {
+                outputs.add_path(path);
+            }
+            report.record_piped_asset();
+            Ok(())
+        })
+    })
+}
+
+/// Runs the pipelines of each asset, with up to `jobs` assets at a time (one per CPU
+/// when `None`). Once a pipeline fails, pipelines which haven't started yet are
+/// cancelled. Colocated assets which get copied are added to `outputs`. Returns the
+/// assets which have no pipeline and couldn't be found.
+#[allow(clippy::too_many_lines)]
 pub fn run_pipelines<'a>(
     engine: &Engine,
     html_assets: &'a HtmlAssets,
+    jobs: Option<usize>,
+    outputs: &BuildOutputs,
+    diagnostics: &Diagnostics,
+    report: &BuildReport,
 ) -> Result<HashSet<&'a HtmlAsset>> {
+    use rayon::prelude::*;
+    use std::fmt::Write;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
     info!(target: USER_LOG, "running pipelines");
 
     let mut missing_assets: HashMap<&AbsPath, Vec<&HtmlAsset>> = HashMap::new();
 
     // first pass: try to run user-defined pipelines
     {
-        for (target_asset, html_files) in html_assets {
-            // we only need to run the pipeline one time per target asset, so
-            // we just grab the first entry
-            let html = html_files.get(0).unwrap();
-
-            // TODO: https://github.com/jayson-lennon/pylon/issues/144
-            if html.url_type() == &UrlType::Offsite
-                || html.asset_target_uri().uri_fragment().ends_with('/')
-                || html.asset_target_uri().uri_fragment().ends_with("html")
-            {
-                continue;
-            }
-
-            let mut asset_processed = false;
+        let queue = html_assets
+            .into_iter()
+            .filter_map(|(target_asset, html_files)| {
+                // we only need to run the pipeline one time per target asset, so
+                // we just grab the first entry
+                let html = html_files.first()?;
+
+                // TODO: https://github.com/jayson-lennon/pylon/issues/144
+                if html.url_type() == &UrlType::Offsite
+                    || html.asset_target_uri().uri_fragment().ends_with('/')
+                    || html.asset_target_uri().uri_fragment().ends_with("html")
+                {
+                    return None;
+                }
+                Some((target_asset, html_files, html))
+            })
+            .collect::<Vec<_>>();
+
+        let pool = pipeline_pool(jobs)?;
+        let failed = AtomicBool::new(false);
+        let cancelled = AtomicUsize::new(0);
+
+        let unprocessed = pool.install(|| {
+            queue
+                .par_iter()
+                .filter_map(|(target_asset, html_files, html)| {
+                    let asset_uri = html.asset_target_uri();
+                    let pipelines = engine
+                        .rules()
+                        .pipelines()
+                        .filter(|pipeline| pipeline.is_match(asset_uri.as_str()))
+                        .collect::<Vec<_>>();
+                    if pipelines.is_empty() {
+                        // all assets that weren't processed need to be reported later.
+                        return Some((*target_asset, *html));
+                    }
+                    if failed.load(Ordering::SeqCst) {
+                        cancelled.fetch_add(1, Ordering::SeqCst);
+                        return None;
+                    }
+
+                    // output is logged once per asset so it isn't interleaved with
+                    // other pipelines
+                    let mut output = String::new();
+                    let mut succeeded = true;
+                    for pipeline in pipelines {
+                        let _ = writeln!(output, "pipeline {}", pipeline.glob());
+
+                        let started = Instant::now();
+                        let result = pipeline.run(asset_uri);
+                        report.record_pipeline(pipeline.glob(), started.elapsed());
+                        match result {
+                            Ok(transcript) => output.push_str(&transcript),
+                            Err(e) => {
+                                failed.store(true, Ordering::SeqCst);
+                                for html in *html_files {
+                                    diagnostics.push(
+                                        Diagnostic::new(
+                                            DiagnosticKind::FailedPipeline,
+                                            output_uri(html.html_src_file().as_sys_path()),
+                                            asset_uri.as_str(),
+                                        )
+                                        .with_message(format!("{e:#}")),
+                                    );
+                                }
+                                succeeded = false;
+                                break;
+                            }
+                        }
+                    }
+                    debug!(target: USER_LOG, asset = %asset_uri.as_str(), "ran pipelines\n{}", output.trim_end());
+                    if succeeded {
+                        report.record_piped_asset();
+                    }
+                    None
+                })
+                .collect::<Vec<_>>()
+        });
 
-            for pipeline in engine.rules().pipelines() {
-                if pipeline.is_match(html.asset_target_uri().as_str()) {
-                    debug!(target: USER_LOG, pipeline=%pipeline.glob(), asset=%html.asset_target_path().uri().as_str(), "run pipeline");
-                    // asset has an associated pipeline, so we won't report an error
-                    asset_processed = true;
+        for (target_asset, html) in unprocessed {
+            missing_assets.entry(target_asset).or_default().push(html);
+        }
 
-                    pipeline.run(html.asset_target_uri()).wrap_err_with(|| {
-                        format!(
-                            "Failed to run pipeline on asset '{}'",
-                            html.asset_target_uri()
-                        )
-                    })?;
-                }
-            }
-            // all assets that weren't processed need to be reported later.
-            if !asset_processed {
-                let entry = missing_assets.entry(target_asset).or_default();
-                entry.push(html);
-            }
+        let cancelled = cancelled.into_inner();
+        if cancelled > 0 {
+            warn!(
+                target: USER_LOG,
+                "cancelled pipelines for {cancelled} assets because a pipeline failed"
+            );
         }
     }
 
@@ -238,9 +988,9 @@ pub fn run_pipelines<'a>(
         };
 
         for (target_asset, html_files) in html_assets {
-            // Continue to the next asset if it already exists. This happens when a pipeline
-            // has already processed the asset.
-            if target_asset.exists() {
+            // Only assets without a pipeline are copied. Continue to the next asset if it
+            // already exists.
+            if !missing_assets.contains_key(&target_asset) || target_asset.exists() {
                 continue;
             }
 
@@ -251,6 +1001,7 @@ pub fn run_pipelines<'a>(
                 // A successful pipeline means that we copied over the asset.
                 if copy_pipeline.run(html.asset_target_uri()).is_ok() {
                     missing_assets.remove(&target_asset);
+                    outputs.add_path(target_asset.as_path());
                     // all pages link to this same asset, so we can bail once the asset exists
                     break;
                 }
@@ -268,6 +1019,26 @@ pub fn run_pipelines<'a>(
         .collect())
 }
 
+/// Adds a diagnostic for each page which references one of the missing assets.
+pub fn record_missing_assets(
+    html_assets: &HtmlAssets,
+    missing: &HashSet<&HtmlAsset>,
+    diagnostics: &Diagnostics,
+) {
+    for asset in missing {
+        let references = html_assets
+            .get(asset.asset_target_path().target())
+            .unwrap_or(std::slice::from_ref(*asset));
+        for html in references {
+            diagnostics.push(Diagnostic::new(
+                DiagnosticKind::MissingAsset,
+                output_uri(html.html_src_file().as_sys_path()),
+                html.asset_target_uri().as_str(),
+            ));
+        }
+    }
+}
+
 #[allow(clippy::needless_pass_by_value)]
 pub fn build_library(engine_paths: GlobalEnginePaths, renderers: &Renderers) -> Result<Library> {
     debug!(target: USER_LOG, "discovering documents");
@@ -298,6 +1069,35 @@ pub fn build_library(engine_paths: GlobalEnginePaths, renderers: &Renderers) ->
     Ok(library)
 }
 
+/// Adds the pages generated by the rule script to the library, failing on conflicts.
+pub fn add_generated_pages(rules: &Rules, library: &mut Library) -> Result<()> {
+    for page in rules.pages() {
+        if library.get(&page.search_keys()[0]).is_some() {
+            eyre::bail!(
+                "generated page '{}' conflicts with an existing page",
+                page.uri()
+            );
+        }
+        library.insert(page.clone());
+    }
+    Ok(())
+}
+
+/// Applies template rules from the rule script to every page in the library.
+pub fn assign_templates(rules: &Rules, renderers: &Renderers, library: &mut Library) -> Result<()> {
+    let all_templates = renderers
+        .tera()
+        .get_template_names()
+        .into_iter()
+        .collect::<HashSet<_>>();
+
+    for (_, page) in library.iter_mut() {
+        crate::core::page::util::assign_template(page, rules, &all_templates)
+            .wrap_err_with(|| format!("Failed to assign template to page '{}'", page.path()))?;
+    }
+    Ok(())
+}
+
 pub fn build_required_asset_list<'a, F>(engine: &Engine, files: F) -> Result<HtmlAssets>
 where
     F: IntoIterator<Item = &'a ConfirmedPath<pathmarker::HtmlFile>>,
@@ -382,11 +1182,106 @@ where
     Ok(())
 }
 
-pub fn minify_html_files<'a, F>(engine: &Engine, html_files: F) -> Result<()>
+pub fn export_documents<'a, P>(
+    engine: &Engine,
+    pages: P,
+    target: &AbsPath,
+    options: &crate::core::export::ExportOptions,
+) -> Result<()>
+where
+    P: IntoIterator<Item = &'a Page>,
+{
+    info!(target: USER_LOG, "exporting documents");
+
+    let exported = crate::core::export::export(engine, pages, options)?;
+    crate::util::make_parent_dirs(&target.pop())?;
+    std::fs::write(target, exported)
+        .wrap_err_with(|| format!("Failed to write export to '{target}'"))?;
+
+    Ok(())
+}
+
+/// Uri of a file in the output directory.
+fn output_uri(path: &SysPath) -> String {
+    format!("/{}", path.target().to_string_lossy().replace('\\', "/"))
+}
+
+/// Checks that links with a fragment in the HTML files point to an element on the
+/// target page. Any HTML file in the output directory can be the target.
+pub fn check_anchors<'a, F>(engine: &Engine, html_files: F) -> Result<()>
+where
+    F: IntoIterator<Item = &'a ConfirmedPath<pathmarker::HtmlFile>>,
+{
+    use crate::discover::anchor::{self, PageAnchors};
+
+    let Some(level) = engine.rules().anchor_check() else {
+        return Ok(());
+    };
+
+    let read = |file: &ConfirmedPath<pathmarker::HtmlFile>| {
+        let path = file.as_sys_path().to_absolute_path();
+        std::fs::read_to_string(&path)
+            .wrap_err_with(|| format!("Failed to read HTML file at '{path}'"))
+    };
+
+    let mut anchors = PageAnchors::new();
+    for file in get_all_output_files(engine, pathmarker::HtmlFile)? {
+        let ids = anchor::find_ids(read(&file)?)
+            .wrap_err_with(|| format!("Failed to find anchors in '{}'", file.as_sys_path()))?;
+        anchors.insert(output_uri(file.as_sys_path()), ids);
+    }
+
+    let mut links = HashMap::new();
+    for file in html_files {
+        let found = anchor::find_fragment_links(read(file)?)
+            .wrap_err_with(|| format!("Failed to find links in '{}'", file.as_sys_path()))?;
+        links.insert(output_uri(file.as_sys_path()), found);
+    }
+
+    report::missing_anchors(&anchor::verify(&anchors, &links), level)
+}
+
+/// Checks the offsite links in the HTML files of the output directory.
+pub fn check_offsite_links(
+    engine: &Engine,
+    options: &crate::core::link_check::ExternalCheckOptions,
+) -> Result<()> {
+    use crate::core::link_check::{self, LinkCache, OffsiteLinks, CACHE_FILE};
+
+    let mut links = OffsiteLinks::new();
+    for file in get_all_output_files(engine, pathmarker::HtmlFile)? {
+        let path = file.as_sys_path().to_absolute_path();
+        let html = std::fs::read_to_string(&path)
+            .wrap_err_with(|| format!("Failed to read HTML file at '{path}'"))?;
+        let page = output_uri(file.as_sys_path());
+        let urls = crate::discover::html_asset::find_offsite(&html)
+            .wrap_err_with(|| format!("Failed to find offsite links in '{path}'"))?;
+        for url in urls {
+            links.entry(url).or_default().insert(page.clone());
+        }
+    }
+
+    info!(target: USER_LOG, "checking {} offsite links", links.len());
+
+    let cache_path = engine
+        .paths()
+        .project_root()
+        .join(&RelPath::new(CACHE_FILE)?);
+    let mut cache = LinkCache::load(&cache_path);
+    let report = link_check::check(&links, options, &mut cache)?;
+    cache
+        .save(&cache_path)
+        .wrap_err("Failed to save offsite link cache")?;
+
+    report::offsite_links(&report)
+}
+
+pub fn minify_html_files<'a, F>(engine: &Engine, html_files: F) -> Result<MinifyStats>
 where
     F: IntoIterator<Item = &'a ConfirmedPath<pathmarker::HtmlFile>>,
 {
     let processor = engine.rules.post_processors().html_minifier();
+    let mut stats = MinifyStats::default();
 
     for file in html_files {
         let path = &file.as_sys_path().to_absolute_path();
@@ -404,15 +1299,17 @@ where
             "Failed to write minified HTML during minification process at '{}'",
             &path.display()
         ))?;
+        stats.add(content.len(), minified.len());
     }
-    Ok(())
+    Ok(stats)
 }
 
-pub fn minify_css_files<'a, F>(engine: &Engine, css_files: F) -> Result<()>
+pub fn minify_css_files<'a, F>(engine: &Engine, css_files: F) -> Result<MinifyStats>
 where
     F: IntoIterator<Item = &'a ConfirmedPath<pathmarker::CssFile>>,
 {
     let processor = engine.rules.post_processors().css_minifier();
+    let mut stats = MinifyStats::default();
 
     for file in css_files {
         let path = &file.as_sys_path().to_absolute_path();
@@ -430,8 +1327,9 @@ where
             "Failed to write minified CSS during minification process at '{}'",
             &path.display()
         ))?;
+        stats.add(content.len(), minified.len());
     }
-    Ok(())
+    Ok(stats)
 }
 
 #[cfg(test)]
@@ -444,7 +1342,9 @@ mod test {
         static HOOKED: once_cell::sync::OnceCell<()> = once_cell::sync::OnceCell::new();
         HOOKED.get_or_init(|| {
             let (_, eyre_hook) = color_eyre::config::HookBuilder::default().into_hooks();
-            eyre_hook.install().unwrap();
+            // another test may have already created a report, which installs the
+            // default hook
+            let _ = eyre_hook.install();
         });
     }
 
@@ -0,0 +1,248 @@
+use std::str::FromStr;
+
+use eyre::{eyre, WrapErr};
+use serde::Serialize;
+
+use crate::core::diagnostics::Diagnostics;
+use crate::core::engine::Engine;
+use crate::core::page::FrontMatter;
+use crate::core::Page;
+use crate::Result;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// A single JSON array.
+    Json,
+    /// One JSON object per line.
+    Ndjson,
+    Csv,
+}
+
+impl FromStr for ExportFormat {
+    type Err = eyre::Report;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "ndjson" => Ok(Self::Ndjson),
+            "csv" => Ok(Self::Csv),
+            other => Err(eyre!("invalid export format {other}")),
+        }
+    }
+}
+
+/// Data to export in addition to the frontmatter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportField {
+    Uri,
+    /// Path to the Markdown file.
+    Path,
+    /// Rendered Markdown, without the template.
+    Html,
+    /// Plain text of the rendered Markdown.
+    Text,
+    /// Table of contents.
+    Toc,
+}
+
+impl FromStr for ExportField {
+    type Err = eyre::Report;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "uri" => Ok(Self::Uri),
+            "path" => Ok(Self::Path),
+            "html" => Ok(Self::Html),
+            "text" => Ok(Self::Text),
+            "toc" => Ok(Self::Toc),
+            other => Err(eyre!("invalid export field {other}")),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    pub fields: Vec<ExportField>,
+}
+
+impl ExportOptions {
+    pub fn new(format: ExportFormat, fields: Vec<ExportField>) -> Self {
+        Self { format, fields }
+    }
+
+    fn includes(&self, field: ExportField) -> bool {
+        self.fields.contains(&field)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Record<'p> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uri: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+    #[serde(flatten)]
+    frontmatter: &'p FrontMatter,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    html: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    toc: Option<String>,
+}
+
+/// Exports the frontmatter and the requested fields of the pages into a single
+/// document.
+pub fn export<'a, P>(engine: &Engine, pages: P, options: &ExportOptions) -> Result<String>
+where
+    P: IntoIterator<Item = &'a Page>,
+{
+    let mut pages = pages.into_iter().collect::<Vec<_>>();
+    pages.sort_by_key(|page| page.uri().to_string());
+
+    let mut records = vec![];
+    for page in pages {
+        records.push(record(engine, page, options)?);
+    }
+
+    match options.format {
+        ExportFormat::Json => serde_json::to_string(&records).wrap_err("Failed serializing export"),
+        ExportFormat::Ndjson => {
+            let mut lines = vec![];
+            for record in &records {
+                lines.push(serde_json::to_string(record).wrap_err("Failed serializing export")?);
+            }
+            lines.push(String::new());
+            Ok(lines.join("\n"))
+        }
+        ExportFormat::Csv => csv(&records),
+    }
+}
+
+fn record<'p>(engine: &Engine, page: &'p Page, options: &ExportOptions) -> Result<Record<'p>> {
+    let html = if options.includes(ExportField::Html) || options.includes(ExportField::Text) {
+        let diagnostics = Diagnostics::new();
+        let html = crate::core::page::render::render_content(engine, page, &diagnostics)
+            .wrap_err_with(|| format!("Failed rendering content of '{}' for export", page.uri()))?;
+        diagnostics
+            .check()
+            .wrap_err_with(|| format!("Broken links in '{}' found during export", page.uri()))?;
+        Some(html)
+    } else {
+        None
+    };
+
+    Ok(Record {
+        uri: options
+            .includes(ExportField::Uri)
+            .then(|| page.uri().to_string()),
+        path: (options.includes(ExportField::Path) && !page.is_virtual())
+            .then(|| page.path().as_sys_path().to_relative_path().to_string()),
+        frontmatter: page.frontmatter(),
+        text: options
+            .includes(ExportField::Text)
+            .then(|| crate::core::search::plain_text(html.as_deref().unwrap_or_default())),
+        html: html.filter(|_| options.includes(ExportField::Html)),
+        toc: options
+            .includes(ExportField::Toc)
+            .then(|| engine.renderers().markdown().render_toc(page)),
+    })
+}
+
+/// Writes one row per record. Values which aren't strings are written as JSON.
+fn csv(records: &[Record<'_>]) -> Result<String> {
+    const LEADING: &[&str] = &["uri", "path"];
+    const TRAILING: &[&str] = &["html", "text", "toc"];
+
+    let mut rows = vec![];
+    for record in records {
+        let value = serde_json::to_value(record).wrap_err("Failed serializing export")?;
+        let serde_json::Value::Object(row) = value else {
+            eyre::bail!("export record is not an object");
+        };
+        rows.push(row);
+    }
+    if rows.is_empty() {
+        return Ok(String::new());
+    }
+
+    // records can have different keys, such as `path` which virtual pages don't have
+    let mut keys = vec![];
+    for key in rows.iter().flat_map(serde_json::Map::keys) {
+        if !keys.contains(key) {
+            keys.push(key.clone());
+        }
+    }
+    let has_key = |column: &&&str| keys.iter().any(|key| key == **column);
+
+    let mut columns = LEADING
+        .iter()
+        .filter(has_key)
+        .map(|column| (*column).to_owned())
+        .collect::<Vec<_>>();
+    columns.extend(
+        keys.iter()
+            .filter(|key| !LEADING.contains(&key.as_str()) && !TRAILING.contains(&key.as_str()))
+            .cloned(),
+    );
+    columns.extend(
+        TRAILING
+            .iter()
+            .filter(has_key)
+            .map(|column| (*column).to_owned()),
+    );
+
+    let mut lines = vec![columns
+        .iter()
+        .map(|c| csv_field(c))
+        .collect::<Vec<_>>()
+        .join(",")];
+    for row in &rows {
+        let line = columns
+            .iter()
+            .map(|column| match row.get(column) {
+                None | Some(serde_json::Value::Null) => String::new(),
+                Some(serde_json::Value::String(s)) => csv_field(s),
+                Some(other) => csv_field(&other.to_string()),
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        lines.push(line);
+    }
+    lines.push(String::new());
+    Ok(lines.join("\r\n"))
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    #![allow(warnings, unused)]
+
+    use super::*;
+
+    #[test]
+    fn parses_format_and_fields() {
+        assert_eq!(
+            "ndjson".parse::<ExportFormat>().unwrap(),
+            ExportFormat::Ndjson
+        );
+        assert!("xml".parse::<ExportFormat>().is_err());
+        assert_eq!("toc".parse::<ExportField>().unwrap(), ExportField::Toc);
+        assert!("body".parse::<ExportField>().is_err());
+    }
+
+    #[test]
+    fn escapes_csv_fields() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+}
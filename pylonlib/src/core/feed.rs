@@ -0,0 +1,295 @@
+use chrono::{DateTime, FixedOffset, SecondsFormat, TimeZone};
+use eyre::WrapErr;
+use serde::Deserialize;
+use std::ffi::OsStr;
+
+use crate::core::build_outputs;
+use crate::core::diagnostics::Diagnostics;
+use crate::core::engine::Engine;
+use crate::core::page::date;
+use crate::core::rules::Matcher;
+use crate::core::Page;
+use crate::util::escape_xml;
+use crate::Result;
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedFormat {
+    #[default]
+    Atom,
+    Rss,
+}
+
+/// What to use for the body of each feed entry.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedContent {
+    /// The `summary` metadata of the page.
+    #[default]
+    Summary,
+    /// The rendered Markdown of the page.
+    Full,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeedOptions {
+    pub format: FeedFormat,
+    pub content: FeedContent,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    /// Only include pages having this keyword.
+    pub keyword: Option<String>,
+    /// Maximum number of entries, newest first.
+    pub limit: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Feed {
+    matcher: Matcher,
+    uri: String,
+    options: FeedOptions,
+}
+
+impl Feed {
+    pub fn new<S: AsRef<str>>(matcher: Matcher, uri: S, options: FeedOptions) -> Result<Self> {
+        let uri = uri.as_ref();
+        let uri = if uri.starts_with('/') {
+            uri.to_owned()
+        } else {
+            format!("/{uri}")
+        };
+        if uri.ends_with('/') || build_outputs::output_path(&uri).is_none() {
+            eyre::bail!("feed '{uri}' must be a file in the output directory");
+        }
+        Ok(Self {
+            matcher,
+            uri,
+            options,
+        })
+    }
+
+    pub fn matcher(&self) -> &Matcher {
+        &self.matcher
+    }
+
+    pub fn uri(&self) -> &str {
+        self.uri.as_str()
+    }
+
+    pub fn options(&self) -> &FeedOptions {
+        &self.options
+    }
+
+    /// Returns `true` if the page is an entry in this feed.
+    pub fn includes(&self, page: &Page) -> bool {
+        page.frontmatter().published
+            && !is_index(page)
+            && self.matcher.is_match(page.uri().as_str())
+            && self
+                .options
+                .keyword
+                .as_ref()
+                .is_none_or(|keyword| page.frontmatter().keywords.contains(keyword))
+    }
+}
+
+/// Section index pages are not feed entries.
+pub fn is_index(page: &Page) -> bool {
+    page.path().as_sys_path().file_name() == OsStr::new("index.md")
+}
+
+pub fn title(page: &Page) -> Option<&str> {
+    meta_str(page, "title")
+}
+
+fn meta_str<'p>(page: &'p Page, key: &str) -> Option<&'p str> {
+    page.frontmatter()
+        .meta
+        .get(key)
+        .and_then(serde_json::Value::as_str)
+}
+
+/// Lint check for entries of the feed missing a title.
+pub fn missing_title(feed: &Feed, page: &Page) -> bool {
+    feed.includes(page) && title(page).is_none()
+}
+
+/// Lint check for entries of the feed missing a date.
+pub fn missing_date(feed: &Feed, page: &Page) -> bool {
+    feed.includes(page) && date::find(page, &["date"]).is_none()
+}
+
+struct Entry<'p> {
+    page: &'p Page,
+    link: String,
+    published: DateTime<FixedOffset>,
+    updated: DateTime<FixedOffset>,
+    body: Option<String>,
+}
+
+/// Generates the feed using pages from the library of the engine.
+pub fn generate(engine: &Engine, feed: &Feed, base_url: &str) -> Result<String> {
+    let mut entries = vec![];
+    for (_, page) in engine.library() {
+        if !feed.includes(page) {
+            continue;
+        }
+        // pages without a date are reported by lints
+        let Some(published) = date::find(page, &["date"]) else {
+            continue;
+        };
+        let updated = date::find(page, &["updated"]).unwrap_or(published);
+        let body = match feed.options.content {
+            FeedContent::Summary => meta_str(page, "summary").map(ToOwned::to_owned),
+            // broken links are reported when the page itself is rendered
+            FeedContent::Full => Some(absolute_links(
+                &crate::core::page::render::render_content(engine, page, &Diagnostics::new())
+                    .wrap_err_with(|| {
+                        format!("Failed rendering content of '{}' for feed", page.uri())
+                    })?,
+                base_url,
+            )),
+        };
+        entries.push(Entry {
+            page,
+            link: format!("{base_url}{}", page.uri()),
+            published,
+            updated,
+            body,
+        });
+    }
+
+    entries.sort_by(|a, b| {
+        b.published
+            .cmp(&a.published)
+            .then_with(|| a.link.cmp(&b.link))
+    });
+    if let Some(limit) = feed.options.limit {
+        entries.truncate(limit);
+    }
+
+    Ok(match feed.options.format {
+        FeedFormat::Atom => atom(feed, base_url, &entries),
+        FeedFormat::Rss => rss(feed, base_url, &entries),
+    })
+}
+
+/// Links in feed content must be absolute, because feed readers don't know where
+/// the content came from.
+fn absolute_links(html: &str, base_url: &str) -> String {
+    html.replace("href=\"/", &format!("href=\"{base_url}/"))
+        .replace("src=\"/", &format!("src=\"{base_url}/"))
+}
+
+fn rfc3339(date: &DateTime<FixedOffset>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn atom(feed: &Feed, base_url: &str, entries: &[Entry<'_>]) -> String {
+    let feed_url = escape_xml(&format!("{base_url}{}", feed.uri));
+    let title = escape_xml(feed.options.title.as_deref().unwrap_or(base_url));
+    // a feed without entries uses a fixed date, so building it again gives the same
+    // file
+    let updated = entries
+        .iter()
+        .map(|entry| entry.updated)
+        .max()
+        .unwrap_or_else(|| FixedOffset::east(0).timestamp(0, 0));
+
+    let mut lines = vec![
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#.to_owned(),
+        r#"<feed xmlns="http://www.w3.org/2005/Atom">"#.to_owned(),
+        format!("  <title>{title}</title>"),
+        format!("  <id>{feed_url}</id>"),
+        format!(r#"  <link rel="self" href="{feed_url}"/>"#),
+        format!(r#"  <link href="{}/"/>"#, escape_xml(base_url)),
+        format!("  <updated>{}</updated>", rfc3339(&updated)),
+    ];
+    if let Some(author) = &feed.options.author {
+        lines.push(format!(
+            "  <author><name>{}</name></author>",
+            escape_xml(author)
+        ));
+    }
+
+    for entry in entries {
+        let link = escape_xml(&entry.link);
+        lines.push("  <entry>".to_owned());
+        lines.push(format!(
+            "    <title>{}</title>",
+            escape_xml(&title_or_uri(entry.page))
+        ));
+        lines.push(format!("    <id>{link}</id>"));
+        lines.push(format!(r#"    <link href="{link}"/>"#));
+        lines.push(format!(
+            "    <published>{}</published>",
+            rfc3339(&entry.published)
+        ));
+        lines.push(format!(
+            "    <updated>{}</updated>",
+            rfc3339(&entry.updated)
+        ));
+        if let Some(author) = meta_str(entry.page, "author") {
+            lines.push(format!(
+                "    <author><name>{}</name></author>",
+                escape_xml(author)
+            ));
+        }
+        if let Some(body) = &entry.body {
+            lines.push(match feed.options.content {
+                FeedContent::Summary => format!("    <summary>{}</summary>", escape_xml(body)),
+                FeedContent::Full => {
+                    format!(r#"    <content type="html">{}</content>"#, escape_xml(body))
+                }
+            });
+        }
+        lines.push("  </entry>".to_owned());
+    }
+
+    lines.push("</feed>\n".to_owned());
+    lines.join("\n")
+}
+
+fn rss(feed: &Feed, base_url: &str, entries: &[Entry<'_>]) -> String {
+    let title = escape_xml(feed.options.title.as_deref().unwrap_or(base_url));
+
+    let mut lines = vec![
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#.to_owned(),
+        r#"<rss version="2.0">"#.to_owned(),
+        "  <channel>".to_owned(),
+        format!("    <title>{title}</title>"),
+        format!("    <link>{}/</link>", escape_xml(base_url)),
+        format!("    <description>{title}</description>"),
+    ];
+
+    for entry in entries {
+        let link = escape_xml(&entry.link);
+        lines.push("    <item>".to_owned());
+        lines.push(format!(
+            "      <title>{}</title>",
+            escape_xml(&title_or_uri(entry.page))
+        ));
+        lines.push(format!("      <link>{link}</link>"));
+        lines.push(format!("      <guid>{link}</guid>"));
+        lines.push(format!(
+            "      <pubDate>{}</pubDate>",
+            entry.published.to_rfc2822()
+        ));
+        if let Some(body) = &entry.body {
+            lines.push(format!(
+                "      <description>{}</description>",
+                escape_xml(body)
+            ));
+        }
+        lines.push("    </item>".to_owned());
+    }
+
+    lines.push("  </channel>".to_owned());
+    lines.push("</rss>\n".to_owned());
+    lines.join("\n")
+}
+
+fn title_or_uri(page: &Page) -> String {
+    title(page).map_or_else(|| page.uri().to_string(), ToOwned::to_owned)
+}
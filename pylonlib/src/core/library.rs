@@ -117,9 +117,24 @@ impl Library {
         }
     }
 
+    /// Keeps only the pages for which `keep` returns `true`.
+    pub fn retain<F>(&mut self, mut keep: F)
+    where
+        F: FnMut(&Page) -> bool,
+    {
+        self.pages.retain(|_, page| keep(page));
+        let pages = &self.pages;
+        self.key_map
+            .retain(|_, page_key| pages.contains_key(*page_key));
+    }
+
     pub fn iter(&self) -> slotmap::basic::Iter<'_, PageKey, Page> {
         self.into_iter()
     }
+
+    pub fn iter_mut(&mut self) -> slotmap::basic::IterMut<'_, PageKey, Page> {
+        self.into_iter()
+    }
 }
 
 impl Default for Library {
@@ -151,6 +166,15 @@ impl<'a> IntoIterator for &'a Library {
     }
 }
 
+impl<'a> IntoIterator for &'a mut Library {
+    type Item = (PageKey, &'a mut Page);
+    type IntoIter = slotmap::basic::IterMut<'a, PageKey, Page>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.pages.iter_mut()
+    }
+}
+
 pub mod script {
 
     use super::{Library, SearchKey};
@@ -250,4 +274,39 @@ mod test {
         let page = store.get(&"/1/page.md".into()).unwrap();
         assert_eq!(page.page_key, key);
     }
+
+    #[test]
+    fn retains_pages() {
+        let tree = temptree! {
+            "rules.rhai": "",
+            templates: {
+                "default.tera": "",
+                "empty.tera": "",
+            },
+            target: {},
+            src: {
+                "1": {
+                    "page.md": "",
+                },
+                "2": {
+                    "page.md": "",
+                },
+            },
+            syntax_themes: {},
+        };
+
+        let page1 = new_page_with_tree(&tree, &tree.path().join("src/1/page.md"), MINIMAL).unwrap();
+        let page2 = new_page_with_tree(&tree, &tree.path().join("src/2/page.md"), MINIMAL).unwrap();
+
+        let mut store = Library::new();
+        store.insert(page1);
+        store.insert(page2);
+
+        store.retain(|page| page.uri().as_str() == "/1/page.html");
+
+        assert_eq!(store.iter().count(), 1);
+        assert!(store.get(&"/1/page.md".into()).is_some());
+        assert!(store.get(&"/2/page.md".into()).is_none());
+        assert!(store.get(&"/2/page.html".into()).is_none());
+    }
 }
@@ -0,0 +1,303 @@
+pub mod cache;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use eyre::WrapErr;
+use futures_util::StreamExt;
+
+use crate::Result;
+
+pub use cache::LinkCache;
+
+/// Location of the link cache, relative to the project root.
+pub const CACHE_FILE: &str = ".pylon/link_cache.json";
+
+#[derive(Clone, Debug)]
+pub struct ExternalCheckOptions {
+    /// Maximum number of requests in flight.
+    pub concurrency: usize,
+    /// Time limit for each request.
+    pub timeout: Duration,
+    /// How long reachable links are remembered.
+    pub cache_ttl: Duration,
+    /// Sends all requests to this URL instead, keeping the path and query of each link.
+    pub base_override: Option<String>,
+}
+
+impl Default for ExternalCheckOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 8,
+            timeout: Duration::from_secs(10),
+            cache_ttl: Duration::from_hours(24),
+            base_override: None,
+        }
+    }
+}
+
+/// Offsite links mapped to the pages containing them.
+pub type OffsiteLinks = BTreeMap<String, BTreeSet<String>>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LinkStatus {
+    Ok(u16),
+    Broken(u16),
+    /// The request didn't complete, such as when the host is unreachable or the
+    /// request timed out.
+    Failed(String),
+}
+
+impl std::fmt::Display for LinkStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ok(status) | Self::Broken(status) => write!(f, "HTTP {status}"),
+            Self::Failed(reason) => write!(f, "{reason}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LinkReport {
+    /// Number of links requested.
+    pub checked: usize,
+    /// Number of links skipped because they were cached.
+    pub cached: usize,
+    /// Pages mapped to their broken links, along with the reason each link is broken.
+    pub broken: BTreeMap<String, Vec<(String, String)>>,
+}
+
+impl LinkReport {
+    /// Number of broken links, counting each link once.
+    pub fn broken_links(&self) -> usize {
+        self.broken
+            .values()
+            .flatten()
+            .map(|(url, _)| url)
+            .collect::<BTreeSet<_>>()
+            .len()
+    }
+}
+
+/// Requests each link which isn't in the cache, and adds the reachable links to the
+/// cache.
+pub fn check(
+    links: &OffsiteLinks,
+    options: &ExternalCheckOptions,
+    cache: &mut LinkCache,
+) -> Result<LinkReport> {
+    let now = unix_now();
+    cache.prune(options.cache_ttl, now);
+
+    let mut report = LinkReport::default();
+    let mut pending = vec![];
+    for url in links.keys() {
+        if cache.get(url, options.cache_ttl, now).is_some() {
+            report.cached += 1;
+        } else {
+            pending.push(url.as_str());
+        }
+    }
+
+    let client = reqwest::Client::builder()
+        .timeout(options.timeout)
+        .user_agent(concat!("pylon/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .wrap_err("Failed to create HTTP client")?;
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .wrap_err("Failed to start link checker runtime")?;
+
+    let base_override = options.base_override.as_deref();
+    let results = runtime.block_on(
+        futures_util::stream::iter(pending)
+            .map(|url| {
+                let client = &client;
+                async move { (url, request(client, url, base_override).await) }
+            })
+            .buffer_unordered(options.concurrency.max(1))
+            .collect::<Vec<_>>(),
+    );
+
+    report.checked = results.len();
+    for (url, status) in results {
+        if let LinkStatus::Ok(code) = status {
+            cache.insert(url, code, now);
+            continue;
+        }
+        for page in &links[url] {
+            report
+                .broken
+                .entry(page.clone())
+                .or_default()
+                .push((url.to_owned(), status.to_string()));
+        }
+    }
+    for broken in report.broken.values_mut() {
+        broken.sort();
+    }
+
+    Ok(report)
+}
+
+async fn request(client: &reqwest::Client, url: &str, base_override: Option<&str>) -> LinkStatus {
+    let target = match base_override {
+        Some(base) => match rebase(url, base) {
+            Ok(target) => target,
+            Err(e) => return LinkStatus::Failed(e.to_string()),
+        },
+        None => url.to_owned(),
+    };
+
+    match client.head(&target).send().await {
+        Ok(response) if response.status().is_success() => {
+            return LinkStatus::Ok(response.status().as_u16())
+        }
+        Err(e) if e.is_timeout() => return LinkStatus::Failed(e.without_url().to_string()),
+        // some servers don't support HEAD requests, so try again with GET
+        _ => (),
+    }
+
+    match client.get(&target).send().await {
+        Ok(response) if response.status().is_success() => {
+            LinkStatus::Ok(response.status().as_u16())
+        }
+        Ok(response) => LinkStatus::Broken(response.status().as_u16()),
+        Err(e) => LinkStatus::Failed(e.without_url().to_string()),
+    }
+}
+
+/// Replaces the scheme and host of the url with `base`.
+fn rebase(url: &str, base: &str) -> Result<String> {
+    let parsed = reqwest::Url::parse(url).wrap_err_with(|| format!("invalid url '{url}'"))?;
+    let mut rebased = format!("{}{}", base.trim_end_matches('/'), parsed.path());
+    if let Some(query) = parsed.query() {
+        rebased.push('?');
+        rebased.push_str(query);
+    }
+    Ok(rebased)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+
+    #![allow(warnings, unused)]
+
+    use super::*;
+    use poem::{get, handler, http::StatusCode, Route};
+
+    #[handler]
+    fn ok() -> &'static str {
+        "ok"
+    }
+
+    #[handler]
+    fn missing() -> StatusCode {
+        StatusCode::NOT_FOUND
+    }
+
+    /// Serves `/ok` and `/missing`, returning the address of the server.
+    fn serve() -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            runtime.block_on(async move {
+                let app = Route::new()
+                    .at("/ok", get(ok).head(ok))
+                    .at("/missing", get(missing).head(missing));
+                poem::Server::new(poem::listener::TcpListener::bind(addr.to_string()))
+                    .run(app)
+                    .await
+            })
+        });
+        // wait for the server to accept connections
+        for _ in 0..50 {
+            if std::net::TcpStream::connect(addr).is_ok() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        format!("http://{addr}")
+    }
+
+    fn links(entries: &[(&str, &str)]) -> OffsiteLinks {
+        let mut links = OffsiteLinks::new();
+        for (url, page) in entries {
+            links
+                .entry((*url).to_owned())
+                .or_default()
+                .insert((*page).to_owned());
+        }
+        links
+    }
+
+    #[test]
+    fn rebases_urls() {
+        assert_eq!(
+            rebase("https://example.com/a/b.html?q=1", "http://127.0.0.1:8080/").unwrap(),
+            "http://127.0.0.1:8080/a/b.html?q=1"
+        );
+        assert_eq!(
+            rebase("https://example.com", "http://127.0.0.1:8080").unwrap(),
+            "http://127.0.0.1:8080/"
+        );
+    }
+
+    #[test]
+    fn reports_broken_links_per_page() {
+        let options = ExternalCheckOptions {
+            base_override: Some(serve()),
+            ..Default::default()
+        };
+        let links = links(&[
+            ("https://example.com/ok", "/a.html"),
+            ("https://example.com/missing", "/a.html"),
+            ("https://example.com/missing", "/b.html"),
+        ]);
+        let mut cache = LinkCache::default();
+
+        let report = check(&links, &options, &mut cache).unwrap();
+        assert_eq!(report.checked, 2);
+        assert_eq!(report.cached, 0);
+        assert_eq!(report.broken_links(), 1);
+        assert_eq!(
+            report.broken["/a.html"],
+            vec![(
+                "https://example.com/missing".to_owned(),
+                "HTTP 404".to_owned()
+            )]
+        );
+        assert!(report.broken.contains_key("/b.html"));
+
+        // reachable links are cached, broken links are checked again
+        let report = check(&links, &options, &mut cache).unwrap();
+        assert_eq!(report.checked, 1);
+        assert_eq!(report.cached, 1);
+    }
+
+    #[test]
+    fn reports_unreachable_hosts() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        let options = ExternalCheckOptions {
+            base_override: Some(format!("http://{addr}")),
+            timeout: Duration::from_secs(2),
+            ..Default::default()
+        };
+        let links = links(&[("https://example.com/", "/a.html")]);
+
+        let report = check(&links, &options, &mut LinkCache::default()).unwrap();
+        assert_eq!(report.broken_links(), 1);
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+use eyre::WrapErr;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{Result, USER_LOG};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct CacheEntry {
+    /// Seconds since the Unix epoch.
+    checked_at: u64,
+    status: u16,
+}
+
+/// Offsite links which were reachable, so they don't need to be requested on every
+/// check. Broken links are not cached, because they are often temporary.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkCache {
+    entries: BTreeMap<String, CacheEntry>,
+}
+
+impl LinkCache {
+    /// Loads the cache, starting over if the file is missing or unreadable.
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+        let Ok(json) = std::fs::read_to_string(path) else {
+            return Self::default();
+        };
+        serde_json::from_str(&json).unwrap_or_else(|e| {
+            warn!(
+                target: USER_LOG,
+                "ignoring invalid link cache at {}: {e}",
+                path.display()
+            );
+            Self::default()
+        })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).wrap_err_with(|| {
+                format!("Failed to create link cache directory {}", parent.display())
+            })?;
+        }
+        let json = serde_json::to_string_pretty(self).wrap_err("Failed serializing link cache")?;
+        std::fs::write(path, json)
+            .wrap_err_with(|| format!("Failed to write link cache to {}", path.display()))
+    }
+
+    /// Status of the url, if it was checked within `ttl` of `now`.
+    pub fn get(&self, url: &str, ttl: Duration, now: u64) -> Option<u16> {
+        self.entries
+            .get(url)
+            .filter(|entry| now.saturating_sub(entry.checked_at) < ttl.as_secs())
+            .map(|entry| entry.status)
+    }
+
+    pub fn insert<S: Into<String>>(&mut self, url: S, status: u16, now: u64) {
+        self.entries.insert(
+            url.into(),
+            CacheEntry {
+                checked_at: now,
+                status,
+            },
+        );
+    }
+
+    /// Removes entries older than `ttl`.
+    pub fn prune(&mut self, ttl: Duration, now: u64) {
+        self.entries
+            .retain(|_, entry| now.saturating_sub(entry.checked_at) < ttl.as_secs());
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    #![allow(warnings, unused)]
+
+    use super::*;
+    use temptree::temptree;
+
+    const TTL: Duration = Duration::from_secs(100);
+
+    #[test]
+    fn expires_entries() {
+        let mut cache = LinkCache::default();
+        cache.insert("https://example.com", 200, 1000);
+        assert_eq!(cache.get("https://example.com", TTL, 1050), Some(200));
+        assert_eq!(cache.get("https://example.com", TTL, 1100), None);
+        assert_eq!(cache.get("https://example.org", TTL, 1050), None);
+
+        cache.prune(TTL, 1100);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn saves_and_loads() {
+        let tree = temptree! {};
+        let path = tree.path().join(".pylon/link_cache.json");
+        assert!(LinkCache::load(&path).is_empty());
+
+        let mut cache = LinkCache::default();
+        cache.insert("https://example.com", 200, 1000);
+        cache.save(&path).unwrap();
+        assert_eq!(LinkCache::load(&path), cache);
+    }
+
+    #[test]
+    fn ignores_invalid_cache() {
+        let tree = temptree! {
+            "cache.json": "not json",
+        };
+        assert!(LinkCache::load(tree.path().join("cache.json")).is_empty());
+    }
+}
@@ -1,9 +1,10 @@
+pub mod date;
 pub mod frontmatter;
 pub mod lint;
 pub mod render;
 pub mod util;
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::ops::Deref;
 
 use crate::core::engine::GlobalEnginePaths;
@@ -11,7 +12,7 @@ use crate::core::library::SearchKey;
 use crate::render::template::TemplateName;
 use crate::Renderers;
 use crate::SysPath;
-use eyre::WrapErr;
+use eyre::{bail, WrapErr};
 pub use frontmatter::FrontMatter;
 pub use lint::{lint, LintLevel, LintResult};
 pub use render::{render, RenderedPage, RenderedPageCollection};
@@ -63,6 +64,27 @@ impl Deref for RawMarkdown {
     }
 }
 
+/// Where the template for a page was selected from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TemplateSource {
+    /// `template_name` was set in the frontmatter.
+    Frontmatter,
+    /// A template rule from the rule script matched the page.
+    Rules,
+    /// A `default.tera` was located in the template directory tree.
+    Default,
+}
+
+impl std::fmt::Display for TemplateSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Frontmatter => write!(f, "frontmatter"),
+            Self::Rules => write!(f, "template rule"),
+            Self::Default => write!(f, "default template"),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize)]
 pub struct Page {
     #[serde(skip)]
@@ -75,6 +97,12 @@ pub struct Page {
 
     pub frontmatter: FrontMatter,
     pub raw_markdown: RawMarkdown,
+
+    #[serde(skip)]
+    pub template_source: TemplateSource,
+
+    #[serde(skip)]
+    pub is_virtual: bool,
 }
 
 impl Page {
@@ -107,17 +135,19 @@ impl Page {
         let (mut frontmatter, raw_markdown) = util::split_raw_doc(&raw_doc)
             .wrap_err_with(|| format!("failed parsing raw document for {}", file_path))?;
 
-        if frontmatter.template_name.is_none() {
+        // Template rules from the rule script are applied once the rules are loaded,
+        // so a missing default template is not an error at this point.
+        let template_source = if frontmatter.template_name.is_some() {
+            TemplateSource::Frontmatter
+        } else {
             let all_templates = renderers
                 .tera()
                 .get_template_names()
                 .into_iter()
                 .collect::<HashSet<_>>();
-            let template = util::find_default_template(&all_templates, &file_path)
-                .wrap_err("Failed to locate default templates when creating new page")?;
-
-            frontmatter.template_name = Some(template);
-        }
+            frontmatter.template_name = util::get_default_template_name(&all_templates, &file_path);
+            TemplateSource::Default
+        };
 
         Ok(Self {
             engine_paths,
@@ -128,6 +158,64 @@ impl Page {
 
             frontmatter,
             raw_markdown,
+
+            template_source,
+            is_virtual: false,
+        })
+    }
+
+    /// Creates a page without a Markdown source. The page is located in the content
+    /// directory where a Markdown file for `uri` would have been.
+    pub fn new_virtual(
+        engine_paths: GlobalEnginePaths,
+        uri: &str,
+        template_name: TemplateName,
+        meta: HashMap<String, serde_json::Value>,
+    ) -> crate::Result<Self> {
+        let target = {
+            let uri = uri.strip_prefix('/').unwrap_or(uri);
+            if uri.is_empty() || uri.ends_with('/') {
+                format!("{uri}index.html")
+            } else {
+                uri.to_owned()
+            }
+        };
+        let target = RelPath::new(target)
+            .wrap_err_with(|| format!("invalid uri '{uri}' for generated page"))?;
+        match target.extension() {
+            None => (),
+            Some(ext) if ext == "html" => (),
+            Some(_) => bail!("generated page '{uri}' must be an html page"),
+        }
+
+        let path = SysPath::new(
+            engine_paths.project_root(),
+            engine_paths.content_dir(),
+            &target,
+        )
+        .with_extension("md")
+        .typed(pathmarker::MdFile)
+        .assume_confirmed();
+
+        let frontmatter = FrontMatter {
+            template_name: Some(template_name),
+            published: true,
+            meta,
+            ..FrontMatter::default()
+        };
+
+        Ok(Self {
+            engine_paths,
+            path,
+
+            raw_doc: String::new(),
+            page_key: PageKey::default(),
+
+            frontmatter,
+            raw_markdown: RawMarkdown::default(),
+
+            template_source: TemplateSource::Frontmatter,
+            is_virtual: true,
         })
     }
 
@@ -168,15 +256,17 @@ impl Page {
     }
 
     pub fn search_keys(&self) -> Vec<SearchKey> {
-        vec![
-            self.uri().as_str().into(),
-            self.path()
-                .as_sys_path()
-                .with_root(&AbsPath::from_absolute("/"))
-                .with_base(&RelPath::from_relative(""))
-                .to_string()
-                .into(),
-        ]
+        vec![self.uri().as_str().into(), self.path_search_key()]
+    }
+
+    /// Search key of the Markdown file, such as `/blog/post.md`.
+    pub fn path_search_key(&self) -> SearchKey {
+        self.path()
+            .as_sys_path()
+            .with_root(&AbsPath::from_absolute("/"))
+            .with_base(&RelPath::from_relative(""))
+            .to_string()
+            .into()
     }
 
     pub fn template_name(&self) -> TemplateName {
@@ -187,6 +277,15 @@ impl Page {
     pub fn frontmatter(&self) -> &FrontMatter {
         &self.frontmatter
     }
+
+    pub fn template_source(&self) -> TemplateSource {
+        self.template_source
+    }
+
+    /// Returns `true` if the page was generated by the rule script.
+    pub fn is_virtual(&self) -> bool {
+        self.is_virtual
+    }
 }
 
 pub mod script {
@@ -389,6 +488,29 @@ mod test {
         let markdown = RawMarkdown("test".into());
         assert_eq!(markdown.as_ref(), "test");
     }
+
+    #[test]
+    fn new_virtual_page_uris() {
+        let (paths, tree) = crate::test::simple_init();
+        let new_page = |uri| Page::new_virtual(paths.clone(), uri, "t.tera".into(), HashMap::new());
+
+        let page = new_page("/products/widget.html").unwrap();
+        assert_eq!(page.uri().as_str(), "/products/widget.html");
+        assert_eq!(page.path_search_key().as_str(), "/products/widget.md");
+        assert!(page.is_virtual());
+        assert!(page.frontmatter().published);
+
+        assert_eq!(
+            new_page("products/gadget").unwrap().uri().as_str(),
+            "/products/gadget.html"
+        );
+        assert_eq!(
+            new_page("/products/").unwrap().path_search_key().as_str(),
+            "/products/index.md"
+        );
+        assert_eq!(new_page("/").unwrap().uri().as_str(), "/");
+        assert!(new_page("/feed.xml").is_err());
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,97 @@
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use tracing::warn;
+
+use super::Page;
+use crate::USER_LOG;
+
+/// Returns the first valid date found in the page metadata at one of the `keys`.
+pub fn find(page: &Page, keys: &[&str]) -> Option<DateTime<FixedOffset>> {
+    find_with(page, keys, from_value)
+}
+
+/// Like [`find`], but parses the metadata values with `parse`.
+pub fn find_with<T>(
+    page: &Page,
+    keys: &[&str],
+    parse: impl Fn(&serde_json::Value) -> Option<T>,
+) -> Option<T> {
+    for key in keys {
+        if let Some(value) = page.frontmatter().meta.get(*key) {
+            if let Some(date) = parse(value) {
+                return Some(date);
+            }
+            warn!(
+                target: USER_LOG,
+                "ignoring invalid '{key}' date in {}",
+                page.path()
+            );
+        }
+    }
+    None
+}
+
+/// Parses RFC 3339 dates, dates without a time, and dates without a time zone.
+/// Dates lacking a time zone are assumed to be UTC.
+pub fn from_value(value: &serde_json::Value) -> Option<DateTime<FixedOffset>> {
+    let date = value_str(value)?;
+    if let Ok(date) = DateTime::parse_from_rfc3339(date) {
+        return Some(date);
+    }
+    let utc = FixedOffset::east(0);
+    if let Ok(date) = NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S") {
+        return Some(utc.from_utc_datetime(&date));
+    }
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .ok()
+        .map(|date| utc.from_utc_datetime(&date.and_hms(0, 0, 0)))
+}
+
+/// The text of a date in the page metadata.
+pub fn value_str(value: &serde_json::Value) -> Option<&str> {
+    match value {
+        serde_json::Value::String(date) => Some(date.as_str()),
+        // dates written as TOML datetimes in the frontmatter
+        serde_json::Value::Object(map) => map
+            .get("$__toml_private_datetime")
+            .and_then(serde_json::Value::as_str),
+        _ => None,
+    }
+}
+
+/// Modification time of the source file of the page.
+pub fn modified(page: &Page) -> Option<DateTime<FixedOffset>> {
+    if page.is_virtual() {
+        return None;
+    }
+    std::fs::metadata(page.path().as_sys_path().to_absolute_path())
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .map(|mtime| DateTime::<Utc>::from(mtime).into())
+}
+
+#[cfg(test)]
+mod test {
+
+    #![allow(warnings, unused)]
+
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_dates() {
+        let expected = DateTime::parse_from_rfc3339("2022-01-02T00:00:00Z").unwrap();
+        assert_eq!(from_value(&json!("2022-01-02")), Some(expected));
+        assert_eq!(from_value(&json!("2022-01-02T00:00:00")), Some(expected));
+        assert_eq!(from_value(&json!("2022-01-02T00:00:00Z")), Some(expected));
+        assert_eq!(
+            from_value(&json!({ "$__toml_private_datetime": "2022-01-02" })),
+            Some(expected)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_dates() {
+        assert!(from_value(&json!("yesterday")).is_none());
+        assert!(from_value(&json!(2022)).is_none());
+    }
+}
@@ -9,8 +9,9 @@ fn always_true() -> bool {
     true
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(default)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct FrontMatter {
     pub template_name: Option<TemplateName>,
     pub keywords: Vec<String>,
@@ -22,9 +23,31 @@ pub struct FrontMatter {
     #[serde(default = "always_true")]
     pub searchable: bool,
 
+    /// Whether the page is listed in the sitemap.
+    #[serde(default = "always_true")]
+    pub sitemap: bool,
+
+    /// Asks search engines not to index the page. The page is also left out of the sitemap.
+    pub noindex: bool,
+
     pub meta: HashMap<String, serde_json::Value>,
 }
 
+impl Default for FrontMatter {
+    fn default() -> Self {
+        Self {
+            template_name: None,
+            keywords: vec![],
+            use_breadcrumbs: false,
+            published: false,
+            searchable: always_true(),
+            sitemap: always_true(),
+            noindex: false,
+            meta: HashMap::new(),
+        }
+    }
+}
+
 pub mod script {
     #[allow(clippy::wildcard_imports)]
     use rhai::plugin::*;
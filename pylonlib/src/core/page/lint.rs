@@ -1,4 +1,5 @@
 use super::Page;
+use crate::core::feed::Feed;
 use crate::core::rules::{Matcher, RuleProcessor};
 use eyre::{eyre, WrapErr};
 
@@ -10,11 +11,20 @@ use typed_path::ConfirmedPath;
 pub const LINT_LEVEL_DENY: &str = "DENY";
 pub const LINT_LEVEL_WARN: &str = "WARN";
 
+/// The check performed by a lint. Returns `true` when the lint applies to the page.
+#[derive(Clone, Debug)]
+pub enum LintCheck {
+    /// A closure from the rule script.
+    Script(rhai::FnPtr),
+    /// A check of the metadata required by the entries of a feed.
+    Feed(Box<Feed>, fn(&Feed, &Page) -> bool),
+}
+
 #[derive(Clone, Debug)]
 pub struct Lint {
     level: LintLevel,
     msg: String,
-    lint_fn: rhai::FnPtr,
+    check: LintCheck,
 }
 
 impl Lint {
@@ -22,7 +32,20 @@ impl Lint {
         Self {
             level,
             msg: msg.into(),
-            lint_fn,
+            check: LintCheck::Script(lint_fn),
+        }
+    }
+
+    pub fn feed<S: Into<String>>(
+        level: LintLevel,
+        msg: S,
+        feed: Feed,
+        check: fn(&Feed, &Page) -> bool,
+    ) -> Self {
+        Self {
+            level,
+            msg: msg.into(),
+            check: LintCheck::Feed(Box::new(feed), check),
         }
     }
 }
@@ -212,9 +235,12 @@ pub fn lint(
         .collect();
     let mut lint_results = vec![];
     for lint in lints {
-        let check: bool = rule_processor
-            .run(&lint.lint_fn, (page.clone(),))
-            .wrap_err("Failed to run lint")?;
+        let check: bool = match &lint.check {
+            LintCheck::Script(lint_fn) => rule_processor
+                .run(lint_fn, (page.clone(),))
+                .wrap_err("Failed to run lint")?,
+            LintCheck::Feed(feed, check) => check(feed, page),
+        };
         if check {
             let lint_result = LintResult::new(lint.level, lint.msg, page.path());
             lint_results.push(lint_result);
@@ -2,15 +2,17 @@ mod breadcrumbs;
 
 use eyre::{eyre, WrapErr};
 use itertools::Itertools;
+use parking_lot::Mutex;
 use std::collections::HashSet;
 
 use tracing::{debug, error, trace};
 
 use crate::{
     core::{
+        diagnostics::Diagnostics,
         engine::Engine,
         page::{ContextItem, PageKey, RawMarkdown},
-        rules::{ContextKey, GlobStore, RuleProcessor},
+        rules::{ContextKey, GlobStore, RuleProcessor, TemplateOutput},
         Page,
     },
     site_context::SiteContext,
@@ -26,62 +28,64 @@ const RESERVED_CONTEXT_KEYWORDS: &[&str] = &[
     "breadcrumbs",
 ];
 
+/// Context added to errors from rendering a page, so the Markdown file can be
+/// reported.
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to render page '{uri}'")]
+pub struct RenderError {
+    pub uri: String,
+    /// Path to the Markdown file, relative to the project root.
+    pub file: String,
+}
+
+impl RenderError {
+    pub fn new(page: &Page) -> Self {
+        Self {
+            uri: page.uri().to_string(),
+            file: page.path().as_sys_path().to_relative_path().to_string(),
+        }
+    }
+}
+
+/// Renders the page. Broken internal links are added to the diagnostics instead of
+/// failing the render.
 #[allow(clippy::too_many_lines)]
-pub fn render(engine: &Engine, page: &Page) -> Result<RenderedPage> {
+pub fn render(engine: &Engine, page: &Page, diagnostics: &Diagnostics) -> Result<RenderedPage> {
     debug!(
         target: USER_LOG,
         "rendering doc {}",
         page.path().as_sys_path()
     );
 
-    let site_ctx = SiteContext::new("sample");
-
     match page.frontmatter.template_name.as_ref() {
         Some(template) => {
-            let mut tera_ctx = tera::Context::new();
-
-            // site context (from global site.toml file)
-            tera_ctx.insert("site", &site_ctx);
-
-            // library
-            let library = {
-                let mut library = ctx::Library::new();
-                for page in engine.library().iter().map(|(_, page)| page) {
-                    library.insert(page);
-                }
-
-                tera_ctx.insert("library", &library);
-                library
-            };
+            let site_wide = engine.site_wide_context()?;
+            let toc = engine.renderers().markdown().render_toc(page);
 
             // current page info
-            {
+            let page_ctx = {
                 let mut inner = tera::Context::new();
                 inner.insert("path", &page.path().to_string());
                 inner.insert("uri", &page.uri().to_string());
                 inner.insert("template_name", page.template_name().as_str());
                 inner.insert("meta", &page.frontmatter.meta);
                 inner.insert("use_breadcrumbs", &page.frontmatter.use_breadcrumbs);
+                inner.insert("noindex", &page.frontmatter.noindex);
 
-                let toc = engine.renderers().markdown().render_toc(page);
                 inner.insert("toc", &toc);
 
-                tera_ctx.insert("page", &inner.into_json());
-            }
-
-            // global context provided by user script
-            if let Some(global) = engine.rules().global_context() {
-                tera_ctx.insert("global", global);
-            }
+                inner.into_json()
+            };
 
             // breadcrumbs
-            if page.frontmatter().use_breadcrumbs {
-                let crumbs = breadcrumbs::generate(engine.library(), page);
-                tera_ctx.insert("breadcrumbs", &crumbs);
-            }
+            let crumbs = if page.frontmatter().use_breadcrumbs {
+                Some(breadcrumbs::generate(engine.library(), page))
+            } else {
+                None
+            };
 
             // page-specific context items provided by user script
-            {
+            let user_ctx = {
                 // the context items
                 let user_ctx = {
                     let page_ctxs = engine.rules().page_contexts();
@@ -101,66 +105,39 @@ pub fn render(engine: &Engine, page: &Page) -> Result<RenderedPage> {
                     }
                 }
 
-                // add the context items
-                for ctx in user_ctx {
-                    let mut user_ctx = tera::Context::new();
-                    user_ctx.insert(ctx.identifier, &ctx.data);
-                    tera_ctx.extend(user_ctx);
-                }
-            }
-
-            // shortcodes
-            let raw_markdown = {
-                let mut raw_markdown = page.raw_markdown().as_ref().to_string();
-
-                while let Some(code) = crate::discover::shortcode::find_next(&raw_markdown)
-                    .wrap_err_with(|| {
-                        format!(
-                            "Failed locating shortcodes when rendering page {}",
-                            page.path()
-                        )
-                    })?
-                {
-                    let template_name = format!("shortcodes/{}.tera", code.name());
-                    let mut context = tera::Context::new();
-                    for (k, v) in code.context() {
-                        context.insert(*k, v);
-                    }
-                    context.insert("library", &library);
-                    let rendered_shortcode = engine
-                        .renderers()
-                        .tera()
-                        .render(&template_name.into(), &context)?;
-
-                    // required for https://github.com/rust-lang/rust/issues/59159
-                    let range = code.range().clone();
-
-                    raw_markdown.replace_range(range, &rendered_shortcode);
-                }
-
-                RawMarkdown::from_raw(raw_markdown)
+                user_ctx
             };
 
             // the actual markdown content (rendered)
-            {
-                let rendered_markdown = engine
-                    .renderers()
-                    .markdown()
-                    .render(
-                        page,
-                        engine.library(),
-                        engine.renderers().highlight(),
-                        &raw_markdown,
-                    )
-                    .wrap_err("Failed rendering Markdown")?;
-                tera_ctx.insert("content", &rendered_markdown);
-            }
+            let rendered_markdown = render_markdown(engine, page, site_wide, diagnostics)?;
 
             // render the template with the context
             let renderer = &engine.renderers().tera();
-            renderer
-                .render(template, &tera_ctx)
-                .map(|html| RenderedPage::new(page.page_key, html, &page.target()))
+            site_wide
+                .scoped(|tera_ctx| {
+                    tera_ctx.insert("page", &page_ctx);
+                    if let Some(crumbs) = &crumbs {
+                        tera_ctx.insert("breadcrumbs", crumbs);
+                    }
+                    for ctx in &user_ctx {
+                        tera_ctx.insert(ctx.identifier.as_str(), &ctx.data);
+                    }
+                    tera_ctx.insert("content", &rendered_markdown);
+                    renderer.render(template, tera_ctx.tera())
+                })
+                .map(|html| {
+                    let html = if page.frontmatter.noindex {
+                        add_noindex_meta(html)
+                    } else {
+                        html
+                    };
+                    RenderedPage::new(page.page_key, html, &page.target()).with_content(
+                        RenderedContent {
+                            content: rendered_markdown,
+                            toc,
+                        },
+                    )
+                })
                 .wrap_err_with(|| {
                     format!(
                         "Failed to render template '{}' for document '{}'",
@@ -173,6 +150,207 @@ pub fn render(engine: &Engine, page: &Page) -> Result<RenderedPage> {
     }
 }
 
+/// Adds a robots `noindex` meta tag to the head of the document, unless the template
+/// already provides a robots meta tag.
+fn add_noindex_meta(html: String) -> String {
+    const META: &str = r#"<meta name="robots" content="noindex">"#;
+    if html.contains(r#"name="robots""#) {
+        return html;
+    }
+    // skip other tags starting with "head", such as <header>
+    let Some(head) = html
+        .match_indices("<head")
+        .map(|(i, _)| i)
+        .find(|i| html[i + "<head".len()..].starts_with(|c: char| c == '>' || c.is_whitespace()))
+    else {
+        return html;
+    };
+    match html[head..].find('>') {
+        Some(end) => {
+            let at = head + end + 1;
+            format!("{}{META}{}", &html[..at], &html[at..])
+        }
+        None => html,
+    }
+}
+
+/// The context items which are available in every template: the site, library, and
+/// global contexts. These are the same for every page, so they are built once and
+/// shared by all renders.
+#[derive(Debug)]
+pub struct SiteWideContext {
+    base: tera::Context,
+    // copying the library is as slow as building it, so contexts are reused once the
+    // page-specific items are removed
+    pool: Mutex<Vec<tera::Context>>,
+}
+
+impl SiteWideContext {
+    pub fn new(engine: &Engine) -> Result<Self> {
+        trace!("building site-wide context");
+
+        let mut tera_ctx = tera::Context::new();
+
+        // site context (from global site.toml file)
+        let site_ctx = SiteContext::new("sample");
+        tera_ctx.insert("site", &site_ctx);
+
+        // library
+        {
+            let mut library = ctx::Library::new();
+            for page in engine.library().iter().map(|(_, page)| page) {
+                library.insert(page);
+            }
+            tera_ctx
+                .try_insert("library", &library)
+                .wrap_err("Failed serializing library context")?;
+        }
+
+        // global context provided by user script
+        if let Some(global) = engine.rules().global_context() {
+            tera_ctx.insert("global", global);
+        }
+
+        Ok(Self {
+            base: tera_ctx,
+            pool: Mutex::new(vec![]),
+        })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&serde_json::Value> {
+        self.base.get(key)
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        self.base.clone().into_json()
+    }
+
+    /// Runs `f` with a context containing the site-wide items. Items added by `f` only
+    /// last until it returns.
+    pub fn scoped<T, F>(&self, f: F) -> T
+    where
+        F: FnOnce(&mut ScopedContext<'_>) -> T,
+    {
+        let context = self.pool.lock().pop();
+        let mut scoped = ScopedContext {
+            context: context.unwrap_or_else(|| self.base.clone()),
+            base: &self.base,
+            added: vec![],
+        };
+        let result = f(&mut scoped);
+        self.pool.lock().push(scoped.reset());
+        result
+    }
+}
+
+/// A site-wide context with items added for a single render.
+pub struct ScopedContext<'a> {
+    context: tera::Context,
+    base: &'a tera::Context,
+    added: Vec<String>,
+}
+
+impl ScopedContext<'_> {
+    pub fn insert<T: serde::Serialize + ?Sized>(&mut self, key: &str, val: &T) {
+        self.added.push(key.to_owned());
+        self.context.insert(key, val);
+    }
+
+    pub fn tera(&self) -> &tera::Context {
+        &self.context
+    }
+
+    /// Removes the added items, restoring any site-wide items they replaced.
+    fn reset(mut self) -> tera::Context {
+        for key in self.added {
+            match self.base.get(&key) {
+                Some(original) => self.context.insert(key, original),
+                None => {
+                    self.context.remove(&key);
+                }
+            }
+        }
+        self.context
+    }
+}
+
+/// Renders the Markdown content of the page, including shortcodes.
+pub fn render_content(engine: &Engine, page: &Page, diagnostics: &Diagnostics) -> Result<String> {
+    render_markdown(engine, page, engine.site_wide_context()?, diagnostics)
+}
+
+fn render_markdown(
+    engine: &Engine,
+    page: &Page,
+    site_wide: &SiteWideContext,
+    diagnostics: &Diagnostics,
+) -> Result<String> {
+    // shortcodes
+    let raw_markdown = {
+        let mut raw_markdown = page.raw_markdown().as_ref().to_string();
+
+        while let Some(code) =
+            crate::discover::shortcode::find_next(&raw_markdown).wrap_err_with(|| {
+                format!(
+                    "Failed locating shortcodes when rendering page {}",
+                    page.path()
+                )
+            })?
+        {
+            let template_name = format!("shortcodes/{}.tera", code.name()).into();
+            let rendered_shortcode = site_wide.scoped(|context| {
+                for (k, v) in code.context() {
+                    context.insert(k, v);
+                }
+                engine
+                    .renderers()
+                    .tera()
+                    .render(&template_name, context.tera())
+            })?;
+
+            // required for https://github.com/rust-lang/rust/issues/59159
+            let range = code.range().clone();
+
+            raw_markdown.replace_range(range, &rendered_shortcode);
+        }
+
+        RawMarkdown::from_raw(raw_markdown)
+    };
+
+    engine
+        .renderers()
+        .markdown()
+        .render(
+            page,
+            engine.library(),
+            engine.renderers().highlight(),
+            &raw_markdown,
+            diagnostics,
+        )
+        .wrap_err("Failed rendering Markdown")
+}
+
+pub fn render_template_output(engine: &Engine, output: &TemplateOutput) -> Result<RenderedPage> {
+    debug!(target: USER_LOG, "rendering template output {}", output.uri());
+
+    engine
+        .site_wide_context()?
+        .scoped(|tera_ctx| {
+            engine
+                .renderers()
+                .tera()
+                .render(output.template(), tera_ctx.tera())
+        })
+        .map(|rendered| RenderedPage::new(PageKey::default(), rendered, output.target()))
+        .wrap_err_with(|| {
+            format!(
+                "Failed to render template '{}' for output '{}'",
+                output.template(),
+                output.uri()
+            )
+        })
+}
+
 pub fn build_context(
     script_fn_runner: &RuleProcessor,
     page_ctxs: &GlobStore<ContextKey, rhai::FnPtr>,
@@ -202,12 +380,19 @@ pub fn build_context(
     Ok(contexts)
 }
 
+/// The rendered Markdown of a page before it was inserted into the template.
+#[derive(Clone, Debug)]
+pub struct RenderedContent {
+    pub content: String,
+    pub toc: String,
+}
+
 #[derive(Debug)]
 pub struct RenderedPage {
-    #[allow(dead_code)]
     page_key: PageKey,
     html: String,
     target: SysPath,
+    content: Option<RenderedContent>,
 }
 
 impl RenderedPage {
@@ -220,9 +405,25 @@ impl RenderedPage {
             page_key,
             html: html.into(),
             target: target.clone(),
+            content: None,
         }
     }
 
+    #[must_use]
+    pub fn with_content(mut self, content: RenderedContent) -> Self {
+        self.content = Some(content);
+        self
+    }
+
+    pub fn page_key(&self) -> PageKey {
+        self.page_key
+    }
+
+    /// Only present for pages rendered from Markdown.
+    pub fn content(&self) -> Option<&RenderedContent> {
+        self.content.as_ref()
+    }
+
     pub fn target(&self) -> &SysPath {
         &self.target
     }
@@ -272,6 +473,14 @@ impl RenderedPageCollection {
     pub fn iter(&self) -> std::slice::Iter<'_, RenderedPage> {
         self.pages.iter()
     }
+
+    pub fn len(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
 }
 
 impl Default for RenderedPageCollection {
@@ -469,6 +678,29 @@ mod test {
         pub struct TestKey;
     }
 
+    #[test]
+    fn scoped_context_items_are_removed_after_use() {
+        let mut base = tera::Context::new();
+        base.insert("site", "site");
+        let site_wide = SiteWideContext {
+            base,
+            pool: Mutex::new(vec![]),
+        };
+
+        site_wide.scoped(|ctx| {
+            ctx.insert("page", "page");
+            ctx.insert("site", "replaced");
+            assert_eq!(ctx.tera().get("site").unwrap(), "replaced");
+        });
+
+        // the context is reused by the next render
+        site_wide.scoped(|ctx| {
+            assert!(ctx.tera().get("page").is_none());
+            assert_eq!(ctx.tera().get("site").unwrap(), "site");
+        });
+        assert_eq!(site_wide.pool.lock().len(), 1);
+    }
+
     pub fn new_rendered_page(content: &str) -> RenderedPage {
         let tree = temptree! {
             "rules.rhai": "",
@@ -558,4 +790,20 @@ mod test {
         let ids = get_overwritten_identifiers(&contexts);
         assert_eq!(ids.len(), 6);
     }
+
+    #[test]
+    fn adds_noindex_meta_to_head() {
+        let html =
+            r#"<html><head lang="en"><title>t</title></head><body><header></header></body></html>"#;
+        assert_eq!(
+            add_noindex_meta(html.to_owned()),
+            r#"<html><head lang="en"><meta name="robots" content="noindex"><title>t</title></head><body><header></header></body></html>"#
+        );
+
+        let html = "<header>no head</header>";
+        assert_eq!(add_noindex_meta(html.to_owned()), html);
+
+        let html = r#"<head><meta name="robots" content="none"></head>"#;
+        assert_eq!(add_noindex_meta(html.to_owned()), html);
+    }
 }
@@ -1,16 +1,18 @@
+use crate::core::rules::Rules;
 use crate::render::template::TemplateName;
 
-use crate::Result;
+use crate::{Result, USER_LOG};
 
 use eyre::{eyre, WrapErr};
 
 use typed_path::ConfirmedPath;
 
 use std::{collections::HashSet, path::PathBuf};
-use tracing::trace_span;
+use tracing::{debug, trace_span};
 
 use super::FrontMatter;
 use super::RawMarkdown;
+use super::{Page, TemplateSource};
 
 const DEFAULT_TEMPLATE_NAME: &str = "default.tera";
 
@@ -42,7 +44,38 @@ pub fn find_default_template(
     }
 }
 
-fn get_default_template_name(
+/// Selects the template for a page. Templates set in the frontmatter always win,
+/// followed by the first matching template rule, and finally `default.tera`.
+pub fn assign_template(
+    page: &mut Page,
+    rules: &Rules,
+    all_templates: &HashSet<String>,
+) -> Result<()> {
+    if page.template_source != TemplateSource::Frontmatter {
+        let search_key = page.path_search_key();
+        let (template, source) = match rules.find_template(&search_key) {
+            Some(template) => (template, TemplateSource::Rules),
+            None => (
+                find_default_template(all_templates, page.path())?,
+                TemplateSource::Default,
+            ),
+        };
+        page.frontmatter.template_name = Some(template);
+        page.template_source = source;
+    }
+
+    debug!(
+        target: USER_LOG,
+        template = %page.template_name(),
+        reason = %page.template_source,
+        "selected template for {}",
+        page.path()
+    );
+
+    Ok(())
+}
+
+pub fn get_default_template_name(
     default_template_names: &HashSet<String>,
     path: &ConfirmedPath<pathmarker::MdFile>,
 ) -> Option<TemplateName> {
@@ -0,0 +1,109 @@
+use serde::Deserialize;
+
+/// Rules for a single group of crawlers.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RobotsGroup {
+    pub user_agent: String,
+    #[serde(default)]
+    pub allow: Vec<String>,
+    #[serde(default)]
+    pub disallow: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RobotsOptions {
+    /// Without any groups, all crawlers are allowed everywhere.
+    pub groups: Vec<RobotsGroup>,
+    /// Link to the sitemap. Requires the base url.
+    pub sitemap: bool,
+}
+
+impl Default for RobotsOptions {
+    fn default() -> Self {
+        Self {
+            groups: vec![],
+            sitemap: true,
+        }
+    }
+}
+
+/// Generates `robots.txt`. `sitemap_url` is linked when the options ask for it.
+pub fn generate(options: &RobotsOptions, sitemap_url: Option<&str>) -> String {
+    let mut lines = vec![];
+    if options.groups.is_empty() {
+        lines.push("User-agent: *".to_owned());
+        lines.push("Disallow:".to_owned());
+    }
+    for (i, group) in options.groups.iter().enumerate() {
+        if i > 0 {
+            lines.push(String::new());
+        }
+        lines.push(format!("User-agent: {}", group.user_agent));
+        for path in &group.allow {
+            lines.push(format!("Allow: {path}"));
+        }
+        for path in &group.disallow {
+            lines.push(format!("Disallow: {path}"));
+        }
+        // an empty group would otherwise be ignored by crawlers
+        if group.allow.is_empty() && group.disallow.is_empty() {
+            lines.push("Disallow:".to_owned());
+        }
+    }
+    if let Some(url) = sitemap_url.filter(|_| options.sitemap) {
+        lines.push(String::new());
+        lines.push(format!("Sitemap: {url}"));
+    }
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod test {
+
+    #![allow(warnings, unused)]
+
+    use super::*;
+
+    #[test]
+    fn allows_everything_by_default() {
+        let robots = generate(&RobotsOptions::default(), None);
+        assert_eq!(robots, "User-agent: *\nDisallow:\n");
+    }
+
+    #[test]
+    fn generates_groups_and_sitemap() {
+        let options = RobotsOptions {
+            groups: vec![
+                RobotsGroup {
+                    user_agent: "*".to_owned(),
+                    allow: vec!["/drafts/public.html".to_owned()],
+                    disallow: vec!["/drafts/".to_owned()],
+                },
+                RobotsGroup {
+                    user_agent: "BadBot".to_owned(),
+                    allow: vec![],
+                    disallow: vec!["/".to_owned()],
+                },
+            ],
+            sitemap: true,
+        };
+        let robots = generate(&options, Some("https://example.com/sitemap.xml"));
+        assert_eq!(
+            robots,
+            "User-agent: *\nAllow: /drafts/public.html\nDisallow: /drafts/\n\nUser-agent: BadBot\nDisallow: /\n\nSitemap: https://example.com/sitemap.xml\n"
+        );
+    }
+
+    #[test]
+    fn omits_disabled_sitemap() {
+        let options = RobotsOptions {
+            sitemap: false,
+            ..Default::default()
+        };
+        let robots = generate(&options, Some("https://example.com/sitemap.xml"));
+        assert!(!robots.contains("Sitemap"));
+    }
+}
@@ -9,18 +9,35 @@ pub use fn_pointers::GlobStore;
 pub use matcher::Matcher;
 use typed_uri::AssetUri;
 
-use crate::{postprocess::PostProcessors, AbsPath, RelPath};
+use crate::{
+    postprocess::PostProcessors, render::template::TemplateName, AbsPath, RelPath, SysPath,
+};
 use serde::Serialize;
 
 use super::{
-    engine::GlobalEnginePaths,
-    page::lint::{Lint, LintCollection},
+    engine::{BuildMode, GlobalEnginePaths},
+    feed::Feed,
+    page::lint::{Lint, LintCollection, LintLevel},
+    robots::RobotsOptions,
+    search::SearchOptions,
+    security_txt::SecurityTxt,
+    Page,
 };
 
+/// Where the 404 page is written, which is where hosts expect it.
+pub const NOT_FOUND_PAGE: &str = "/404.html";
+
+/// Files in the output directory which are never removed when cleaning or pruning.
+pub const PRESERVED_OUTPUTS: [&str; 2] = [".git", "CNAME"];
+
 slotmap::new_key_type! {
     pub struct ContextKey;
 }
 
+slotmap::new_key_type! {
+    pub struct TemplateKey;
+}
+
 #[derive(Debug, Clone)]
 pub struct Mount {
     src: AbsPath,
@@ -48,6 +65,50 @@ impl Mount {
     }
 }
 
+/// A file rendered from a template which isn't associated with a document.
+#[derive(Debug, Clone)]
+pub struct TemplateOutput {
+    template: TemplateName,
+    uri: String,
+    target: SysPath,
+}
+
+impl TemplateOutput {
+    pub fn new(
+        engine_paths: &GlobalEnginePaths,
+        template: TemplateName,
+        uri: &str,
+    ) -> crate::Result<Self> {
+        let relative = uri.strip_prefix('/').unwrap_or(uri);
+        if relative.is_empty() || relative.ends_with('/') {
+            eyre::bail!("template output '{uri}' must be a file");
+        }
+        let relative = RelPath::new(relative)
+            .wrap_err_with(|| format!("invalid uri '{uri}' for template output"))?;
+        Ok(Self {
+            template,
+            uri: format!("/{relative}"),
+            target: SysPath::new(
+                engine_paths.project_root(),
+                engine_paths.output_dir(),
+                &relative,
+            ),
+        })
+    }
+
+    pub fn template(&self) -> &TemplateName {
+        &self.template
+    }
+
+    pub fn uri(&self) -> &str {
+        self.uri.as_str()
+    }
+
+    pub fn target(&self) -> &SysPath {
+        &self.target
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ExternalWatch<T>
 where
@@ -106,6 +167,8 @@ impl ExternalWatch<()> {
 pub struct PylonPipeline {
     pipeline: pipeworks::Pipeline,
     target_glob: crate::util::PylonGlob,
+    /// Files used by the pipeline, relative to its working directory.
+    inputs: Vec<crate::util::PylonGlob>,
 }
 
 impl PylonPipeline {
@@ -113,14 +176,76 @@ impl PylonPipeline {
         Self {
             pipeline,
             target_glob,
+            inputs: vec![],
         }
     }
 
+    #[must_use]
+    pub fn with_inputs(mut self, inputs: Vec<crate::util::PylonGlob>) -> Self {
+        self.inputs = inputs;
+        self
+    }
+
+    /// The files used to generate the asset: the declared inputs, or the source file
+    /// when the pipeline uses one. Empty when the inputs are unknown.
+    pub fn input_files(&self, asset_uri: &AssetUri) -> crate::Result<Vec<AbsPath>> {
+        if self.inputs.is_empty() {
+            let source = self.pipeline.source_path(asset_uri)?;
+            return Ok(if self.pipeline.uses_source() && source.exists() {
+                vec![source]
+            } else {
+                vec![]
+            });
+        }
+
+        let working_dir = self.pipeline.working_dir(asset_uri)?;
+        let mut files = crate::discover::get_all_paths(working_dir.as_path(), &|path| {
+            let relative = path.strip_prefix(working_dir.as_path()).unwrap_or(path);
+            self.inputs.iter().any(|glob| glob.is_match(relative))
+        })?;
+        files.sort_by(|a, b| a.as_path().cmp(b.as_path()));
+        Ok(files)
+    }
+
+    /// The extra files written when generating the asset.
+    pub fn output_paths(&self, asset_uri: &AssetUri) -> crate::Result<Vec<AbsPath>> {
+        let target = self.pipeline.target_path(asset_uri)?;
+        Ok(self.pipeline.output_paths(&target))
+    }
+
+    /// Identifies the definition of the pipeline, so changes to the rule script cause
+    /// the pipeline to run again.
+    pub fn definition(&self) -> String {
+        let ops = self
+            .pipeline
+            .ops()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        let inputs = self
+            .inputs
+            .iter()
+            .map(crate::util::PylonGlob::glob)
+            .collect::<Vec<_>>();
+        let outputs = self
+            .pipeline
+            .outputs()
+            .iter()
+            .map(|output| format!("{}={}", output.name(), output.file()))
+            .collect::<Vec<_>>();
+        format!(
+            "{:?} {} {ops:?} {inputs:?} {outputs:?}",
+            self.pipeline.base_dir(),
+            self.glob()
+        )
+    }
+
     pub fn is_match<P: AsRef<Path>>(&self, asset: P) -> bool {
         self.target_glob.is_match(asset)
     }
 
-    pub fn run(&self, asset_uri: &AssetUri) -> crate::Result<()> {
+    /// Generates the asset, returning the output of the pipeline.
+    pub fn run(&self, asset_uri: &AssetUri) -> crate::Result<String> {
         self.pipeline.run(asset_uri)
     }
 
@@ -133,33 +258,176 @@ impl PylonPipeline {
     }
 }
 
+/// The files generated by a build step.
+#[derive(Debug, Clone)]
+pub enum BuildStepTargets {
+    /// Uris of the generated files. `$SOURCE` is the file with the same name in the
+    /// working directory.
+    Uris(Vec<String>),
+    /// One file for each source matching the glob, relative to the working directory.
+    /// `*` in the target uri is replaced by the path of the source, without its
+    /// extension.
+    Sources {
+        glob: crate::util::PylonGlob,
+        target: String,
+    },
+}
+
+/// A file generated by a build step.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildStepJob {
+    pub uri: String,
+    pub source: AbsPath,
+    pub target: AbsPath,
+}
+
+/// A pipeline which runs on every build, for files which aren't linked from a
+/// document.
+#[derive(Debug, Clone)]
+pub struct BuildStep {
+    pipeline: pipeworks::Pipeline,
+    targets: BuildStepTargets,
+}
+
+impl BuildStep {
+    pub fn new(pipeline: pipeworks::Pipeline, targets: BuildStepTargets) -> Self {
+        Self { pipeline, targets }
+    }
+
+    /// Identifies the build step in logs and timings.
+    pub fn name(&self) -> String {
+        match &self.targets {
+            BuildStepTargets::Uris(uris) => uris.join(", "),
+            BuildStepTargets::Sources { glob, target } => format!("{} -> {target}", glob.glob()),
+        }
+    }
+
+    /// The files to generate, written to `output_dir`.
+    pub fn jobs(&self, output_dir: &AbsPath) -> crate::Result<Vec<BuildStepJob>> {
+        let working_dir = self.pipeline.root_working_dir()?;
+        let job = |uri: String, source: AbsPath| {
+            let relative = crate::core::build_outputs::output_path(&uri).ok_or_else(|| {
+                eyre::eyre!("build step target '{uri}' must be a file in the output directory")
+            })?;
+            Ok(BuildStepJob {
+                target: output_dir.join(&relative),
+                uri,
+                source,
+            })
+        };
+
+        match &self.targets {
+            BuildStepTargets::Uris(uris) => uris
+                .iter()
+                .map(|uri| {
+                    let name = uri.rsplit_once('/').map_or(uri.as_str(), |(_, name)| name);
+                    job(uri.clone(), working_dir.join(&RelPath::from_relative(name)))
+                })
+                .collect(),
+            BuildStepTargets::Sources { glob, target } => {
+                let mut sources = crate::discover::get_all_paths(working_dir.as_path(), &|path| {
+                    let relative = path.strip_prefix(working_dir.as_path()).unwrap_or(path);
+                    glob.is_match(relative)
+                })?;
+                sources.sort_by(|a, b| a.as_path().cmp(b.as_path()));
+                sources
+                    .into_iter()
+                    .map(|source| {
+                        let stem = source
+                            .as_path()
+                            .strip_prefix(working_dir.as_path())
+                            .unwrap_or_else(|_| source.as_path())
+                            .with_extension("");
+                        job(target.replace('*', &stem.to_string_lossy()), source)
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// The extra files written when generating the file.
+    pub fn output_paths(&self, job: &BuildStepJob) -> Vec<AbsPath> {
+        self.pipeline.output_paths(&job.target)
+    }
+
+    /// Generates the file, returning the output of the pipeline.
+    pub fn run(&self, job: &BuildStepJob) -> crate::Result<String> {
+        self.pipeline.run_at(&job.source, &job.target)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Rules {
     pipelines: Vec<PylonPipeline>,
+    build_steps: Vec<BuildStep>,
     global_context: Option<serde_json::Value>,
     page_contexts: GlobStore<ContextKey, rhai::FnPtr>,
+    templates: GlobStore<TemplateKey, TemplateName>,
     lints: LintCollection,
     mounts: Vec<Mount>,
     watches: Vec<AbsPath>,
     external_watches: Vec<ExternalWatch<()>>,
     engine_paths: GlobalEnginePaths,
     post_processors: PostProcessors,
+    pages: Vec<Page>,
+    template_outputs: Vec<TemplateOutput>,
+    base_url: Option<String>,
+    sitemap: bool,
+    feeds: Vec<Feed>,
+    search: Option<SearchOptions>,
+    content_api: bool,
+    not_found_page: Option<String>,
+    robots: Option<RobotsOptions>,
+    security_txt: Option<SecurityTxt>,
+    anchor_check: Option<LintLevel>,
+    preserved_outputs: Vec<crate::util::PylonGlob>,
+    build_mode: BuildMode,
 }
 
 impl Rules {
     pub fn new(engine_paths: GlobalEnginePaths) -> Self {
         Self {
             pipelines: vec![],
+            build_steps: vec![],
             global_context: None,
             page_contexts: GlobStore::new(),
+            templates: GlobStore::new(),
             lints: LintCollection::new(),
             mounts: vec![],
             watches: vec![],
             external_watches: vec![],
             engine_paths,
             post_processors: PostProcessors::new(),
+            pages: vec![],
+            template_outputs: vec![],
+            base_url: None,
+            sitemap: true,
+            feeds: vec![],
+            search: None,
+            content_api: false,
+            not_found_page: None,
+            robots: None,
+            security_txt: None,
+            anchor_check: Some(LintLevel::Warn),
+            preserved_outputs: PRESERVED_OUTPUTS
+                .iter()
+                .map(|glob| crate::util::PylonGlob::try_from(*glob).expect("invalid default glob"))
+                .collect(),
+            build_mode: BuildMode::default(),
         }
     }
+
+    /// Operations limited to a different build mode are left out of pipelines.
+    #[must_use]
+    pub fn with_build_mode(mut self, build_mode: BuildMode) -> Self {
+        self.build_mode = build_mode;
+        self
+    }
+
+    pub fn build_mode(&self) -> BuildMode {
+        self.build_mode
+    }
+
     pub fn set_global_context<S: Serialize>(&mut self, ctx: S) -> crate::Result<()> {
         let ctx = serde_json::to_value(ctx)
             .wrap_err("Failed converting global context to serde value")?;
@@ -187,6 +455,190 @@ impl Rules {
         &self.page_contexts
     }
 
+    pub fn set_template(&mut self, matcher: Matcher, template: TemplateName) {
+        self.templates.add(matcher, template);
+    }
+
+    pub fn templates(&self) -> &GlobStore<TemplateKey, TemplateName> {
+        &self.templates
+    }
+
+    /// Returns the template from the first template rule matching `search`.
+    pub fn find_template<S: AsRef<str>>(&self, search: S) -> Option<TemplateName> {
+        self.templates
+            .find_keys(search)
+            .first()
+            .and_then(|key| self.templates.get(*key))
+    }
+
+    pub fn add_page(&mut self, page: Page) {
+        self.pages.push(page);
+    }
+
+    /// Pages generated by the rule script.
+    pub fn pages(&self) -> impl Iterator<Item = &Page> {
+        self.pages.iter()
+    }
+
+    pub fn add_template_output(&mut self, output: TemplateOutput) {
+        self.template_outputs.push(output);
+    }
+
+    pub fn template_outputs(&self) -> impl Iterator<Item = &TemplateOutput> {
+        self.template_outputs.iter()
+    }
+
+    /// Sets the URL where the site is hosted. Trailing slashes are removed.
+    pub fn set_base_url<S: AsRef<str>>(&mut self, url: S) -> crate::Result<()> {
+        let url = url.as_ref();
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+            eyre::bail!("base url '{url}' must start with http:// or https://");
+        }
+        self.base_url = Some(url.trim_end_matches('/').to_owned());
+        Ok(())
+    }
+
+    pub fn base_url(&self) -> Option<&str> {
+        self.base_url.as_deref()
+    }
+
+    pub fn set_sitemap(&mut self, enabled: bool) {
+        self.sitemap = enabled;
+    }
+
+    pub fn sitemap_enabled(&self) -> bool {
+        self.sitemap
+    }
+
+    /// Adds the feed along with lints for the metadata required by feed entries.
+    pub fn add_feed(&mut self, feed: Feed) {
+        use crate::core::feed::{missing_date, missing_title};
+
+        let uri = feed.uri();
+        self.add_lint(
+            feed.matcher().clone(),
+            Lint::feed(
+                LintLevel::Deny,
+                format!("missing title required by feed {uri}"),
+                feed.clone(),
+                missing_title,
+            ),
+        );
+        self.add_lint(
+            feed.matcher().clone(),
+            Lint::feed(
+                LintLevel::Deny,
+                format!("missing date required by feed {uri}"),
+                feed.clone(),
+                missing_date,
+            ),
+        );
+        self.feeds.push(feed);
+    }
+
+    pub fn feeds(&self) -> impl Iterator<Item = &Feed> {
+        self.feeds.iter()
+    }
+
+    /// Enables generation of the search index.
+    pub fn set_search(&mut self, options: SearchOptions) -> crate::Result<()> {
+        self.search = Some(options.validate()?);
+        Ok(())
+    }
+
+    pub fn search(&self) -> Option<&SearchOptions> {
+        self.search.as_ref()
+    }
+
+    pub fn set_content_api(&mut self, enabled: bool) {
+        self.content_api = enabled;
+    }
+
+    pub fn content_api_enabled(&self) -> bool {
+        self.content_api
+    }
+
+    /// Uses the page at `uri` as the 404 page, instead of the document rendered
+    /// to `/404.html`.
+    pub fn set_not_found_page<S: AsRef<str>>(&mut self, uri: S) -> crate::Result<()> {
+        let uri = uri.as_ref();
+        let uri = if uri.starts_with('/') {
+            uri.to_owned()
+        } else {
+            format!("/{uri}")
+        };
+        let extension = Path::new(&uri).extension();
+        if !extension.is_some_and(|ext| ext.eq_ignore_ascii_case("html")) {
+            eyre::bail!("404 page '{uri}' must be an HTML file");
+        }
+        if crate::core::build_outputs::output_path(&uri).is_none() {
+            eyre::bail!("404 page '{uri}' must be in the output directory");
+        }
+        self.not_found_page = Some(uri);
+        Ok(())
+    }
+
+    /// The uri of the page served for missing files.
+    pub fn not_found_page(&self) -> &str {
+        self.not_found_page.as_deref().unwrap_or(NOT_FOUND_PAGE)
+    }
+
+    /// Returns `true` if the uri is the 404 page. Index pages may be given either
+    /// with or without the `index.html`.
+    pub fn is_not_found_page(&self, uri: &str) -> bool {
+        let normalize = |uri: &str| uri.strip_suffix("index.html").unwrap_or(uri).to_owned();
+        let uri = normalize(uri);
+        uri == NOT_FOUND_PAGE || uri == normalize(self.not_found_page())
+    }
+
+    /// Enables generation of `robots.txt`.
+    pub fn set_robots(&mut self, options: RobotsOptions) {
+        self.robots = Some(options);
+    }
+
+    pub fn robots(&self) -> Option<&RobotsOptions> {
+        self.robots.as_ref()
+    }
+
+    /// Enables generation of `.well-known/security.txt`.
+    pub fn set_security_txt(&mut self, security_txt: SecurityTxt) -> crate::Result<()> {
+        self.security_txt = Some(security_txt.validate()?);
+        Ok(())
+    }
+
+    pub fn security_txt(&self) -> Option<&SecurityTxt> {
+        self.security_txt.as_ref()
+    }
+
+    /// Sets how links to missing anchors are reported, or disables the check with `None`.
+    pub fn set_anchor_check(&mut self, level: Option<LintLevel>) {
+        self.anchor_check = level;
+    }
+
+    pub fn anchor_check(&self) -> Option<LintLevel> {
+        self.anchor_check
+    }
+
+    /// Keeps files matching the glob, relative to the output directory, when cleaning
+    /// or pruning the output directory.
+    pub fn add_preserved_output(&mut self, glob: crate::util::PylonGlob) {
+        self.preserved_outputs.push(glob);
+    }
+
+    pub fn preserved_outputs(&self) -> impl Iterator<Item = &crate::util::PylonGlob> {
+        self.preserved_outputs.iter()
+    }
+
+    /// Whether the path (relative to the output directory) or any of its parent
+    /// directories is preserved.
+    pub fn is_preserved_output<P: AsRef<Path>>(&self, path: P) -> bool {
+        path.as_ref().ancestors().any(|path| {
+            self.preserved_outputs
+                .iter()
+                .any(|glob| glob.is_match(path))
+        })
+    }
+
     pub fn add_pipeline(&mut self, pipeline: PylonPipeline) {
         self.pipelines.push(pipeline);
     }
@@ -195,6 +647,14 @@ impl Rules {
         self.pipelines.iter()
     }
 
+    pub fn add_build_step(&mut self, build_step: BuildStep) {
+        self.build_steps.push(build_step);
+    }
+
+    pub fn build_steps(&self) -> impl Iterator<Item = &BuildStep> {
+        self.build_steps.iter()
+    }
+
     pub fn add_mount(&mut self, src: &RelPath, target: &RelPath) {
         self.mounts.push(Mount::new(
             self.engine_paths.project_root(),
@@ -236,6 +696,8 @@ impl Rules {
     }
 }
 
+/// Runs functions from the rule script. Pages are linted and rendered in parallel, so
+/// this is shared between threads, which the `sync` feature of rhai allows.
 #[derive(Debug)]
 pub struct RuleProcessor {
     engine: rhai::Engine,
@@ -265,79 +727,744 @@ pub mod script {
     #[allow(clippy::wildcard_imports)]
     use rhai::plugin::*;
 
-    #[rhai::export_module]
-    pub mod rhai_module {
-        use crate::core::rules::{Matcher, Rules};
-        use pipeworks::BaseDir;
-        use rhai::FnPtr;
-        use tracing::trace;
-        use typed_path::{AbsPath, RelPath};
+    fn script_err<M, E>(msg: M, error: E) -> Box<EvalAltResult>
+    where
+        M: Into<String>,
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        Box::new(EvalAltResult::ErrorSystem(msg.into(), error.into()))
+    }
+
+    fn new_build_step_pipeline(
+        rules: &super::Rules,
+        base_dir: &str,
+        ops: rhai::Array,
+    ) -> Result<pipeworks::Pipeline, Box<EvalAltResult>> {
+        // build steps aren't linked from a document
+        if !base_dir.starts_with('/') {
+            return Err(
+                format!("build step working directory '{base_dir}' must start with `/`").into(),
+            );
+        }
+        new_pipeline(rules, base_dir, ops)
+    }
+
+    fn new_pipeline(
+        rules: &super::Rules,
+        base_dir: &str,
+        ops: rhai::Array,
+    ) -> Result<pipeworks::Pipeline, Box<EvalAltResult>> {
+        use pipeworks::BaseDir;
+
+        let mut parsed_ops = vec![];
+        for op in ops {
+            if let Some(op) = parse_op(rules, op)? {
+                parsed_ops.push(op);
+            }
+        }
+
+        let base_dir = if base_dir.starts_with('/') {
+            BaseDir::RelativeToRoot(typed_path::AbsPath::from_absolute(base_dir))
+        } else {
+            BaseDir::RelativeToDoc(typed_path::RelPath::from_relative(base_dir))
+        };
+
+        let paths = rules.engine_paths();
+        let paths = pipeworks::Paths::new(
+            paths.project_root(),
+            paths.output_dir(),
+            paths.content_dir(),
+        );
+        pipeworks::Pipeline::with_ops(paths, &base_dir, &parsed_ops).map_err(|e| {
+            EvalAltResult::ErrorSystem("failed creating pipeline".into(), e.into()).into()
+        })
+    }
+
+    /// Parses an operation, which is either a command or a map of options. Returns
+    /// `None` when the operation is limited to a different build mode.
+    fn parse_op(
+        rules: &super::Rules,
+        op: Dynamic,
+    ) -> Result<Option<pipeworks::Operation>, Box<EvalAltResult>> {
+        use pipeworks::{Operation, ShellCommand, OP_COPY};
+        use std::str::FromStr;
+
+        let Some(mut definition) = op.clone().try_cast::<rhai::Map>() else {
+            let op: String = op.into_string()?;
+            return Ok(Some(Operation::from_str(&op)?));
+        };
+
+        let run: String = required_key(&mut definition, "operation", "run")?.into_string()?;
+        let only = definition
+            .remove("only")
+            .map(|only| -> Result<_, Box<EvalAltResult>> {
+                Ok(only
+                    .into_string()?
+                    .parse::<crate::core::engine::BuildMode>()?)
+            })
+            .transpose()?;
+        let name = definition
+            .remove("name")
+            .map(Dynamic::into_string)
+            .transpose()?;
+        let env = definition
+            .remove("env")
+            .map(|env| {
+                env.try_cast::<rhai::Map>()
+                    .ok_or_else(|| Box::<EvalAltResult>::from("operation `env` must be a map"))
+            })
+            .transpose()?;
+        let dir = definition
+            .remove("dir")
+            .map(Dynamic::into_string)
+            .transpose()?;
+        check_unused_keys(&definition, "operation")?;
+
+        if only.is_some_and(|only| only != rules.build_mode()) {
+            return Ok(None);
+        }
+
+        if run == OP_COPY {
+            if name.is_some() || env.is_some() || dir.is_some() {
+                return Err("`name`, `env`, and `dir` only apply to commands".into());
+            }
+            return Ok(Some(Operation::Copy));
+        }
+
+        let mut command = ShellCommand::new(run);
+        if let Some(name) = name {
+            command = command.with_name(name);
+        }
+        for (key, value) in env.unwrap_or_default() {
+            command = command.with_env(key.as_str(), value.into_string()?);
+        }
+        if let Some(dir) = dir {
+            let dir = typed_path::RelPath::new(&dir).map_err(|e| {
+                EvalAltResult::ErrorSystem(
+                    format!("operation `dir` '{dir}' must be relative to the working directory"),
+                    e.into(),
+                )
+            })?;
+            command = command.with_working_dir(dir);
+        }
+        Ok(Some(Operation::Shell(command)))
+    }
+
+    /// Removes a key which must be present in a map definition.
+    fn required_key(
+        definition: &mut rhai::Map,
+        kind: &str,
+        key: &str,
+    ) -> Result<Dynamic, Box<EvalAltResult>> {
+        definition
+            .remove(key)
+            .ok_or_else(|| format!("{kind} definition is missing `{key}`").into())
+    }
+
+    /// Keys which remain after parsing a map definition are likely typos.
+    fn check_unused_keys(definition: &rhai::Map, kind: &str) -> Result<(), Box<EvalAltResult>> {
+        match definition.keys().next() {
+            Some(key) => Err(format!("unknown key `{key}` in {kind} definition").into()),
+            None => Ok(()),
+        }
+    }
+
+    fn into_array(
+        value: Dynamic,
+        kind: &str,
+        key: &str,
+    ) -> Result<rhai::Array, Box<EvalAltResult>> {
+        value
+            .into_array()
+            .map_err(|_| format!("{kind} `{key}` must be an array").into())
+    }
+
+    fn into_map(value: Dynamic, kind: &str, key: &str) -> Result<rhai::Map, Box<EvalAltResult>> {
+        value
+            .try_cast::<rhai::Map>()
+            .ok_or_else(|| format!("{kind} `{key}` must be a map").into())
+    }
+
+    /// Build step targets must be files in the output directory.
+    fn check_build_step_target(target: &str) -> Result<(), Box<EvalAltResult>> {
+        if !target.starts_with('/') {
+            return Err(format!("build step target '{target}' must start with `/`").into());
+        }
+        if crate::core::build_outputs::output_path(target).is_none() {
+            return Err(format!(
+                "build step target '{target}' must be a file in the output directory"
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    fn build_step_uris(
+        targets: rhai::Array,
+    ) -> Result<super::BuildStepTargets, Box<EvalAltResult>> {
+        let mut uris = vec![];
+        for target in targets {
+            let target: String = target.into_string()?;
+            check_build_step_target(&target)?;
+            uris.push(target);
+        }
+        Ok(super::BuildStepTargets::Uris(uris))
+    }
+
+    fn build_step_sources(
+        source_glob: &str,
+        target: &str,
+    ) -> Result<super::BuildStepTargets, Box<EvalAltResult>> {
+        let glob = crate::util::PylonGlob::try_from(source_glob).map_err(|e| {
+            EvalAltResult::ErrorSystem("failed to parse build step source glob".into(), e.into())
+        })?;
+        check_build_step_target(target)?;
+        // otherwise every source would write to the same target
+        if !target.contains('*') {
+            return Err(format!(
+                "build step target '{target}' must contain `*` to be used with sources"
+            )
+            .into());
+        }
+        Ok(super::BuildStepTargets::Sources {
+            glob,
+            target: target.to_owned(),
+        })
+    }
+
+    fn add_build_step(
+        rules: &mut super::Rules,
+        base_dir: &str,
+        targets: super::BuildStepTargets,
+        ops: rhai::Array,
+        outputs: rhai::Map,
+    ) -> Result<(), Box<EvalAltResult>> {
+        let mut pipeline = new_build_step_pipeline(rules, base_dir, ops)?;
+        add_outputs(&mut pipeline, outputs)?;
+        tracing::trace!("add build step");
+        rules.add_build_step(super::BuildStep::new(pipeline, targets));
+        Ok(())
+    }
+
+    /// Declares the extra files written by the pipeline, keyed by name.
+    fn add_outputs(
+        pipeline: &mut pipeworks::Pipeline,
+        outputs: rhai::Map,
+    ) -> Result<(), Box<EvalAltResult>> {
+        for (name, file) in outputs {
+            let file: String = file.into_string()?;
+            pipeworks::Output::new(name.as_str(), file)
+                .and_then(|output| pipeline.add_output(output))
+                .map_err(|e| {
+                    EvalAltResult::ErrorSystem("failed to add pipeline output".into(), e.into())
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Path of a data file given to the rule script, relative to the project root.
+    fn data_file_path(rules: &super::Rules, path: &str) -> crate::AbsPath {
+        let path = if path.starts_with('/') {
+            path.strip_prefix('/').unwrap()
+        } else {
+            path
+        };
+        let data_path = std::path::PathBuf::from(path);
+        rules
+            .engine_paths()
+            .project_root()
+            .join(&typed_path::RelPath::from_relative(data_path))
+    }
+
+    fn read_data_file(
+        rules: &super::Rules,
+        path: &str,
+    ) -> Result<serde_json::Value, Box<EvalAltResult>> {
+        let path = data_file_path(rules, path);
+
+        let raw_data = std::fs::read_to_string(&path).map_err(|e| {
+            script_err(
+                format!("error reading data file at {}:{}", e, path.display()),
+                e,
+            )
+        })?;
+
+        match path.extension() {
+            Some(ext) if ext == "toml" => match raw_data.parse::<toml::Value>() {
+                Ok(v) => serde_json::to_value(v)
+                    .map_err(|e| script_err("failed converting TOML content", e)),
+                Err(e) => Err(format!("failed parsing TOML content: {e}").into()),
+            },
+            Some(ext) if ext == "json" => serde_json::from_str(&raw_data)
+                .map_err(|e| format!("failed parsing JSON content: {e}").into()),
+            Some(ext) => Err(format!("unsupported file type: {}", ext.to_string_lossy()).into()),
+            None => Err(format!("file extension required for data at {}", path.display()).into()),
+        }
+    }
+
+    #[rhai::export_module]
+    pub mod rhai_module {
+        use crate::core::rules::{Matcher, Rules};
+        use rhai::FnPtr;
+        use tracing::trace;
+        use typed_path::RelPath;
+
+        #[rhai_fn(name = "add_pipeline", return_raw)]
+        pub fn add_pipeline(
+            rules: &mut Rules,
+            base_dir: &str,
+            target_glob: &str,
+            ops: rhai::Array,
+        ) -> Result<(), Box<EvalAltResult>> {
+            add_pipeline_with_inputs(rules, base_dir, target_glob, ops, rhai::Array::new())
+        }
+
+        /// Adds a pipeline which runs again when any file matching the input globs
+        /// changes. The globs are relative to the working directory.
+        #[rhai_fn(name = "add_pipeline", return_raw)]
+        pub fn add_pipeline_with_inputs(
+            rules: &mut Rules,
+            base_dir: &str,
+            target_glob: &str,
+            ops: rhai::Array,
+            inputs: rhai::Array,
+        ) -> Result<(), Box<EvalAltResult>> {
+            add_pipeline_with_inputs_and_outputs(
+                rules,
+                base_dir,
+                target_glob,
+                ops,
+                inputs,
+                rhai::Map::new(),
+            )
+        }
+
+        /// Adds a pipeline which writes extra files next to the target. Each output maps
+        /// a name, used as `$OUTPUT(name)` in commands, to a file relative to the
+        /// directory of the target.
+        #[rhai_fn(name = "add_pipeline", return_raw)]
+        pub fn add_pipeline_with_outputs(
+            rules: &mut Rules,
+            base_dir: &str,
+            target_glob: &str,
+            ops: rhai::Array,
+            outputs: rhai::Map,
+        ) -> Result<(), Box<EvalAltResult>> {
+            add_pipeline_with_inputs_and_outputs(
+                rules,
+                base_dir,
+                target_glob,
+                ops,
+                rhai::Array::new(),
+                outputs,
+            )
+        }
+
+        /// Adds a pipeline from a map with the keys `dir`, `glob`, and `ops`, and
+        /// optionally `inputs` and `outputs`.
+        #[rhai_fn(name = "add_pipeline", return_raw)]
+        pub fn add_pipeline_from_map(
+            rules: &mut Rules,
+            mut definition: rhai::Map,
+        ) -> Result<(), Box<EvalAltResult>> {
+            const KIND: &str = "pipeline";
+
+            let base_dir: String =
+                super::required_key(&mut definition, KIND, "dir")?.into_string()?;
+            let target_glob: String =
+                super::required_key(&mut definition, KIND, "glob")?.into_string()?;
+            let ops = super::into_array(
+                super::required_key(&mut definition, KIND, "ops")?,
+                KIND,
+                "ops",
+            )?;
+            let inputs = definition
+                .remove("inputs")
+                .map(|inputs| super::into_array(inputs, KIND, "inputs"))
+                .transpose()?
+                .unwrap_or_default();
+            let outputs = definition
+                .remove("outputs")
+                .map(|outputs| super::into_map(outputs, KIND, "outputs"))
+                .transpose()?
+                .unwrap_or_default();
+            super::check_unused_keys(&definition, KIND)?;
+
+            add_pipeline_with_inputs_and_outputs(
+                rules,
+                &base_dir,
+                &target_glob,
+                ops,
+                inputs,
+                outputs,
+            )
+        }
+
+        #[rhai_fn(name = "add_pipeline", return_raw)]
+        pub fn add_pipeline_with_inputs_and_outputs(
+            rules: &mut Rules,
+            base_dir: &str,
+            target_glob: &str,
+            ops: rhai::Array,
+            inputs: rhai::Array,
+            outputs: rhai::Map,
+        ) -> Result<(), Box<EvalAltResult>> {
+            use crate::core::rules::PylonPipeline;
+            use crate::util::PylonGlob;
+
+            let mut parsed_inputs = vec![];
+            for input in inputs {
+                let input: String = input.into_string()?;
+                let glob = PylonGlob::try_from(input.as_str()).map_err(|e| {
+                    EvalAltResult::ErrorSystem(
+                        "failed to parse pipeline input glob".into(),
+                        e.into(),
+                    )
+                })?;
+                parsed_inputs.push(glob);
+            }
+
+            let pylon_pipeline = {
+                let glob: PylonGlob = {
+                    let glob: Result<PylonGlob, _> = target_glob.try_into();
+                    glob.map_err(|e| {
+                        EvalAltResult::ErrorSystem("failed to parse pipeline glob".into(), e.into())
+                    })?
+                };
+
+                let mut pipeline = super::new_pipeline(rules, base_dir, ops)?;
+                super::add_outputs(&mut pipeline, outputs)?;
+                PylonPipeline::new(pipeline, glob).with_inputs(parsed_inputs)
+            };
+
+            rules.add_pipeline(pylon_pipeline);
+
+            Ok(())
+        }
+
+        /// Runs the operations on every build to generate the target, even when it isn't
+        /// linked from a document. The working directory must start with `/`.
+        #[rhai_fn(name = "add_build_step", return_raw)]
+        pub fn add_build_step(
+            rules: &mut Rules,
+            base_dir: &str,
+            target: &str,
+            ops: rhai::Array,
+        ) -> Result<(), Box<EvalAltResult>> {
+            add_build_step_with_targets(rules, base_dir, vec![target.into()], ops)
+        }
+
+        /// Runs the operations on every build to generate each target.
+        #[rhai_fn(name = "add_build_step", return_raw)]
+        pub fn add_build_step_with_targets(
+            rules: &mut Rules,
+            base_dir: &str,
+            targets: rhai::Array,
+            ops: rhai::Array,
+        ) -> Result<(), Box<EvalAltResult>> {
+            let targets = super::build_step_uris(targets)?;
+            super::add_build_step(rules, base_dir, targets, ops, rhai::Map::new())
+        }
+
+        /// Runs the operations on every build for each file matching the source glob,
+        /// relative to the working directory. `*` in the target is replaced by the path
+        /// of the source without its extension.
+        #[rhai_fn(name = "add_build_step", return_raw)]
+        pub fn add_build_step_for_sources(
+            rules: &mut Rules,
+            base_dir: &str,
+            source_glob: &str,
+            target: &str,
+            ops: rhai::Array,
+        ) -> Result<(), Box<EvalAltResult>> {
+            let targets = super::build_step_sources(source_glob, target)?;
+            super::add_build_step(rules, base_dir, targets, ops, rhai::Map::new())
+        }
+
+        /// Adds a build step from a map with the keys `dir`, `ops`, and either `target`,
+        /// `targets`, or `sources` and `target`. `outputs` is optional.
+        #[rhai_fn(name = "add_build_step", return_raw)]
+        pub fn add_build_step_from_map(
+            rules: &mut Rules,
+            mut definition: rhai::Map,
+        ) -> Result<(), Box<EvalAltResult>> {
+            const KIND: &str = "build step";
+
+            let base_dir: String =
+                super::required_key(&mut definition, KIND, "dir")?.into_string()?;
+            let ops = super::into_array(
+                super::required_key(&mut definition, KIND, "ops")?,
+                KIND,
+                "ops",
+            )?;
+            let outputs = definition
+                .remove("outputs")
+                .map(|outputs| super::into_map(outputs, KIND, "outputs"))
+                .transpose()?
+                .unwrap_or_default();
+            let sources = definition.remove("sources");
+            let target = definition.remove("target");
+            let targets = definition.remove("targets");
+            super::check_unused_keys(&definition, KIND)?;
+
+            let targets = match (sources, target, targets) {
+                (None, Some(target), None) => super::build_step_uris(vec![target])?,
+                (None, None, Some(targets)) => {
+                    super::build_step_uris(super::into_array(targets, KIND, "targets")?)?
+                }
+                (Some(sources), Some(target), None) => {
+                    super::build_step_sources(&sources.into_string()?, &target.into_string()?)?
+                }
+                _ => {
+                    return Err(
+                        "build step definition needs either `target`, `targets`, or \
+                        `sources` and `target`"
+                            .into(),
+                    )
+                }
+            };
+            super::add_build_step(rules, &base_dir, targets, ops, outputs)
+        }
+
+        /// Associates the closure with the given matcher. This closure will be called
+        /// and the returned context from the closure will be available in the page template.
+        #[rhai_fn(return_raw)]
+        pub fn add_doc_context(
+            rules: &mut Rules,
+            matcher: &str,
+            ctx_fn: FnPtr,
+        ) -> Result<(), Box<EvalAltResult>> {
+            let matcher = crate::util::PylonGlob::try_from(matcher).map_err(|e| {
+                EvalAltResult::ErrorSystem("failed processing glob".into(), e.into())
+            })?;
+            let matcher = Matcher::Glob(vec![matcher]);
+            trace!("add page ctx_fn");
+            rules.add_doc_context(matcher, ctx_fn);
+            Ok(())
+        }
+
+        /// Uses the template for all documents matching the glob, unless the document
+        /// sets `template_name` in the frontmatter. The first matching rule is used.
+        #[rhai_fn(return_raw)]
+        pub fn set_template(
+            rules: &mut Rules,
+            matcher: &str,
+            template: &str,
+        ) -> Result<(), Box<EvalAltResult>> {
+            // documents are matched using their path from the content root, which always
+            // has a leading slash
+            let matcher = if matcher.starts_with('/') {
+                matcher.to_owned()
+            } else {
+                format!("/{matcher}")
+            };
+            let matcher = crate::util::PylonGlob::try_from(matcher).map_err(|e| {
+                EvalAltResult::ErrorSystem("failed processing glob".into(), e.into())
+            })?;
+            let matcher = Matcher::Glob(vec![matcher]);
+            trace!("add template rule");
+            rules.set_template(matcher, template.into());
+            Ok(())
+        }
+
+        /// Creates a page without a Markdown source. Entries in `context` are used as the
+        /// page metadata.
+        #[rhai_fn(return_raw)]
+        pub fn add_page(
+            rules: &mut Rules,
+            uri: &str,
+            template: &str,
+            context: rhai::Map,
+        ) -> Result<(), Box<EvalAltResult>> {
+            use crate::core::Page;
+
+            let meta = rhai::serde::from_dynamic(&context.into())?;
+            let page = Page::new_virtual(rules.engine_paths(), uri, template.into(), meta)
+                .map_err(|e| EvalAltResult::ErrorSystem("failed creating page".into(), e.into()))?;
+            trace!("add page");
+            rules.add_page(page);
+            Ok(())
+        }
+
+        /// Renders the template to the given location in the output directory. The
+        /// template has access to the site, library, and global contexts.
+        #[rhai_fn(return_raw)]
+        pub fn render_template(
+            rules: &mut Rules,
+            template: &str,
+            uri: &str,
+        ) -> Result<(), Box<EvalAltResult>> {
+            use crate::core::rules::TemplateOutput;
+
+            let output =
+                TemplateOutput::new(&rules.engine_paths(), template.into(), uri).map_err(|e| {
+                    EvalAltResult::ErrorSystem("failed adding template output".into(), e.into())
+                })?;
+            trace!("add template output");
+            rules.add_template_output(output);
+            Ok(())
+        }
+
+        /// Sets the URL where the site is hosted, for use in generated files which
+        /// require absolute links.
+        #[rhai_fn(return_raw)]
+        pub fn set_base_url(rules: &mut Rules, url: &str) -> Result<(), Box<EvalAltResult>> {
+            trace!("set base url");
+            rules.set_base_url(url).map_err(|e| {
+                EvalAltResult::ErrorSystem("failed setting base url".into(), e.into())
+            })?;
+            Ok(())
+        }
+
+        /// Enables or disables generation of `sitemap.xml`.
+        #[rhai_fn()]
+        pub fn set_sitemap(rules: &mut Rules, enabled: bool) {
+            trace!("set sitemap");
+            rules.set_sitemap(enabled);
+        }
 
-        #[rhai_fn(name = "add_pipeline", return_raw)]
-        pub fn add_pipeline(
+        /// Generates a feed at `uri` containing the documents matching the glob.
+        #[rhai_fn(name = "add_feed", return_raw)]
+        pub fn add_feed(
             rules: &mut Rules,
-            base_dir: &str,
-            target_glob: &str,
-            ops: rhai::Array,
+            glob: &str,
+            uri: &str,
         ) -> Result<(), Box<EvalAltResult>> {
-            use crate::core::rules::PylonPipeline;
-            use crate::util::PylonGlob;
-            use std::str::FromStr;
+            add_feed_with_options(rules, glob, uri, rhai::Map::new())
+        }
 
-            let mut parsed_ops = vec![];
-            for op in ops {
-                let op: String = op.into_string()?;
-                let op = pipeworks::Operation::from_str(&op)?;
-                parsed_ops.push(op);
-            }
+        /// Generates a feed at `uri` containing the documents matching the glob, using
+        /// `format`, `content`, `title`, `author`, `keyword` and `limit` from `options`.
+        #[rhai_fn(name = "add_feed", return_raw)]
+        pub fn add_feed_with_options(
+            rules: &mut Rules,
+            glob: &str,
+            uri: &str,
+            options: rhai::Map,
+        ) -> Result<(), Box<EvalAltResult>> {
+            use crate::core::feed::{Feed, FeedOptions};
 
-            let base_dir = if base_dir.starts_with('/') {
-                BaseDir::RelativeToRoot(AbsPath::from_absolute(base_dir))
-            } else {
-                BaseDir::RelativeToDoc(RelPath::from_relative(base_dir))
-            };
+            let options: FeedOptions = rhai::serde::from_dynamic(&options.into())?;
+            let matcher = crate::util::PylonGlob::try_from(glob).map_err(|e| {
+                EvalAltResult::ErrorSystem("failed processing glob".into(), e.into())
+            })?;
+            let feed = Feed::new(Matcher::Glob(vec![matcher]), uri, options)
+                .map_err(|e| EvalAltResult::ErrorSystem("failed adding feed".into(), e.into()))?;
+            trace!("add feed");
+            rules.add_feed(feed);
+            Ok(())
+        }
 
-            let pylon_pipeline = {
-                let glob: PylonGlob = {
-                    let glob: Result<PylonGlob, _> = target_glob.try_into();
-                    glob.map_err(|e| {
-                        EvalAltResult::ErrorSystem("failed to parse pipeline glob".into(), e.into())
-                    })?
-                };
+        /// Serves the page at `uri` for missing files. The page is copied to `/404.html`
+        /// when building the site.
+        #[rhai_fn(return_raw)]
+        pub fn set_not_found_page(rules: &mut Rules, uri: &str) -> Result<(), Box<EvalAltResult>> {
+            trace!("set 404 page");
+            rules.set_not_found_page(uri).map_err(|e| {
+                EvalAltResult::ErrorSystem("failed setting 404 page".into(), e.into())
+            })?;
+            Ok(())
+        }
 
-                let pipeline = {
-                    let paths = rules.engine_paths();
-                    let paths = pipeworks::Paths::new(
-                        paths.project_root(),
-                        paths.output_dir(),
-                        paths.content_dir(),
-                    );
-                    pipeworks::Pipeline::with_ops(paths, &base_dir, &parsed_ops).map_err(|e| {
-                        EvalAltResult::ErrorSystem("failed creating pipeline".into(), e.into())
-                    })?
-                };
-                PylonPipeline::new(pipeline, glob)
-            };
+        /// Generates `robots.txt` allowing all crawlers and linking to the sitemap.
+        #[rhai_fn(name = "set_robots", return_raw)]
+        pub fn set_robots(rules: &mut Rules) -> Result<(), Box<EvalAltResult>> {
+            set_robots_with_options(rules, rhai::Map::new())
+        }
 
-            rules.add_pipeline(pylon_pipeline);
+        /// Generates `robots.txt` using `groups` and `sitemap` from `options`. Each
+        /// group has a `user_agent` along with `allow` and `disallow` paths.
+        #[rhai_fn(name = "set_robots", return_raw)]
+        pub fn set_robots_with_options(
+            rules: &mut Rules,
+            options: rhai::Map,
+        ) -> Result<(), Box<EvalAltResult>> {
+            use crate::core::robots::RobotsOptions;
 
+            let options: RobotsOptions = rhai::serde::from_dynamic(&options.into())?;
+            trace!("set robots");
+            rules.set_robots(options);
             Ok(())
         }
 
-        /// Associates the closure with the given matcher. This closure will be called
-        /// and the returned context from the closure will be available in the page template.
+        /// Generates `.well-known/security.txt` from the fields of the map. `contact`
+        /// and `expires` are required.
         #[rhai_fn(return_raw)]
-        pub fn add_doc_context(
+        pub fn set_security_txt(
             rules: &mut Rules,
-            matcher: &str,
-            ctx_fn: FnPtr,
+            fields: rhai::Map,
         ) -> Result<(), Box<EvalAltResult>> {
-            let matcher = crate::util::PylonGlob::try_from(matcher).map_err(|e| {
+            use crate::core::security_txt::SecurityTxt;
+
+            let security_txt: SecurityTxt = rhai::serde::from_dynamic(&fields.into())?;
+            trace!("set security.txt");
+            rules.set_security_txt(security_txt).map_err(|e| {
+                EvalAltResult::ErrorSystem("failed setting security.txt".into(), e.into())
+            })?;
+            Ok(())
+        }
+
+        /// Reports links to missing anchors as a warning ("WARN") or an error ("DENY"),
+        /// or disables the check ("OFF").
+        #[rhai_fn(return_raw)]
+        pub fn set_anchor_check(rules: &mut Rules, level: &str) -> Result<(), Box<EvalAltResult>> {
+            use crate::core::page::lint::LintLevel;
+            use std::str::FromStr;
+
+            let level = match level {
+                "OFF" => None,
+                level => Some(LintLevel::from_str(level).map_err(|e| {
+                    EvalAltResult::ErrorSystem("invalid anchor check level".into(), e.into())
+                })?),
+            };
+            trace!("set anchor check");
+            rules.set_anchor_check(level);
+            Ok(())
+        }
+
+        /// Keeps files matching the glob (relative to the output directory) when
+        /// cleaning or pruning the output directory.
+        #[rhai_fn(return_raw)]
+        pub fn preserve_output(rules: &mut Rules, glob: &str) -> Result<(), Box<EvalAltResult>> {
+            let glob = crate::util::PylonGlob::try_from(glob).map_err(|e| {
                 EvalAltResult::ErrorSystem("failed processing glob".into(), e.into())
             })?;
-            let matcher = Matcher::Glob(vec![matcher]);
-            trace!("add page ctx_fn");
-            rules.add_doc_context(matcher, ctx_fn);
+            trace!("preserve output");
+            rules.add_preserved_output(glob);
+            Ok(())
+        }
+
+        /// Enables or disables the JSON representation of each document.
+        #[rhai_fn()]
+        pub fn set_content_api(rules: &mut Rules, enabled: bool) {
+            trace!("set content api");
+            rules.set_content_api(enabled);
+        }
+
+        /// Generates a search index of published documents using the default options.
+        #[rhai_fn(name = "enable_search", return_raw)]
+        pub fn enable_search(rules: &mut Rules) -> Result<(), Box<EvalAltResult>> {
+            enable_search_with_options(rules, rhai::Map::new())
+        }
+
+        /// Generates a search index of published documents, using `uri`, `fields` and
+        /// `split_sections` from `options`.
+        #[rhai_fn(name = "enable_search", return_raw)]
+        pub fn enable_search_with_options(
+            rules: &mut Rules,
+            options: rhai::Map,
+        ) -> Result<(), Box<EvalAltResult>> {
+            use crate::core::search::SearchOptions;
+
+            // rhai can't deserialize enum map keys, so the options go through serde_json
+            let options: serde_json::Value = rhai::serde::from_dynamic(&options.into())?;
+            let options: SearchOptions = serde_json::from_value(options).map_err(|e| {
+                EvalAltResult::ErrorSystem("invalid search options".into(), e.into())
+            })?;
+            trace!("enable search");
+            rules.set_search(options).map_err(|e| {
+                EvalAltResult::ErrorSystem("failed enabling search".into(), e.into())
+            })?;
             Ok(())
         }
 
@@ -431,14 +1558,6 @@ pub mod script {
             Ok(())
         }
 
-        fn script_err<M, E>(msg: M, error: E) -> Box<EvalAltResult>
-        where
-            M: Into<String>,
-            E: std::error::Error + Send + Sync + 'static,
-        {
-            Box::new(EvalAltResult::ErrorSystem(msg.into(), error.into()))
-        }
-
         #[rhai_fn(return_raw)]
         pub fn load_context(rules: &mut Rules, path: &str) -> Result<(), Box<EvalAltResult>> {
             trace!("load context");
@@ -447,55 +1566,25 @@ pub mod script {
                 return Err("Error: attempt to set the global context more than once".into());
             }
 
-            let path = {
-                let path = if path.starts_with('/') {
-                    path.strip_prefix('/').unwrap()
-                } else {
-                    path
-                };
-                let context_path = std::path::PathBuf::from(path);
-                rules
-                    .engine_paths()
-                    .project_root()
-                    .join(&RelPath::from_relative(context_path))
-            };
+            let context = super::read_data_file(rules, path)?;
+            if let Err(e) = rules.set_global_context(context) {
+                let path = super::data_file_path(rules, path);
+                return Err(format!("failed setting context from {}:{e}", path.display()).into());
+            }
 
-            let raw_context = std::fs::read_to_string(&path).map_err(|e| {
-                script_err(
-                    format!("error reading context file at {}:{}", e, path.display()),
-                    e,
-                )
-            })?;
+            Ok(())
+        }
 
-            if let Some(ext) = path.extension() {
-                let status = if ext == "toml" {
-                    match raw_context.parse::<toml::Value>() {
-                        Ok(v) => rules.set_global_context(v),
-                        Err(e) => return Err(format!("failed parsing TOML content: {}", e).into()),
-                    }
-                } else if ext == "json" {
-                    let v: Result<serde_json::Value, _> = serde_json::from_str(&raw_context);
-                    match v {
-                        Ok(v) => rules.set_global_context(v),
-                        Err(e) => return Err(format!("failed parsing JSON content: {}", e).into()),
-                    }
-                } else {
-                    return Err(format!("unsupported file type: {}", ext.to_string_lossy()).into());
-                };
-                if let Err(e) = status {
-                    return Err(
-                        format!("failed setting context from {}:{}", path.display(), e).into(),
-                    );
-                }
-            } else {
-                return Err(format!(
-                    "file extension required for context data at {}",
-                    path.display()
-                )
-                .into());
-            };
+        /// Returns the data from a TOML or JSON file, for use within the rule script.
+        #[rhai_fn(return_raw)]
+        pub fn load_data(
+            rules: &mut Rules,
+            path: &str,
+        ) -> Result<rhai::Dynamic, Box<EvalAltResult>> {
+            trace!("load data");
 
-            Ok(())
+            let data = super::read_data_file(rules, path)?;
+            rhai::serde::to_dynamic(data)
         }
 
         #[rhai_fn(return_raw)]
@@ -545,6 +1634,250 @@ pub mod script {
             assert_eq!(rules.pipelines().count(), 1);
         }
 
+        #[test]
+        fn adds_pipeline_with_inputs() {
+            let (paths, tree) = crate::test::simple_init();
+            let mut rules = Rules::new(paths);
+            let ops = vec!["sass main.scss > $TARGET".into()];
+            let inputs = vec!["**/*.scss".into()];
+            add_pipeline_with_inputs(&mut rules, "/web", "/style.css", ops, inputs)
+                .expect("failed to add pipeline");
+            let pipeline = rules.pipelines().next().unwrap();
+            assert!(pipeline.definition().contains("**/*.scss"));
+        }
+
+        #[test]
+        fn rejects_bad_pipeline_input_glob() {
+            let (paths, tree) = crate::test::simple_init();
+            let mut rules = Rules::new(paths);
+            let ops = vec!["_COPY_".into()];
+            let inputs = vec!["[".into()];
+            assert!(add_pipeline_with_inputs(&mut rules, "/web", "*", ops, inputs).is_err());
+        }
+
+        #[test]
+        fn adds_pipeline_with_outputs() {
+            let (paths, tree) = crate::test::simple_init();
+            let mut rules = Rules::new(paths);
+            let ops = || vec!["sass main.scss $TARGET --source-map $OUTPUT(map)".into()];
+            let mut outputs = rhai::Map::new();
+            outputs.insert("map".into(), "$TARGET_NAME.map".into());
+
+            add_pipeline_with_outputs(&mut rules, "/web", "/style.css", ops(), outputs)
+                .expect("failed to add pipeline");
+            let pipeline = rules.pipelines().next().unwrap();
+            assert!(pipeline.definition().contains("map=$TARGET_NAME.map"));
+
+            let mut outputs = rhai::Map::new();
+            outputs.insert("map".into(), "/style.css.map".into());
+            assert!(
+                add_pipeline_with_outputs(&mut rules, "/web", "/style.css", ops(), outputs)
+                    .is_err()
+            );
+        }
+
+        #[test]
+        fn adds_build_steps() {
+            let (paths, tree) = crate::test::simple_init();
+            let mut rules = Rules::new(paths.clone());
+            let ops = || vec!["sass $SOURCE > $TARGET".into()];
+
+            add_build_step(&mut rules, "/web", "/js/app.js", ops())
+                .expect("failed to add build step");
+            add_build_step_for_sources(&mut rules, "/web", "**/*.scss", "/css/*.css", ops())
+                .expect("failed to add build step");
+            assert_eq!(rules.build_steps().count(), 2);
+
+            // build steps aren't linked from a document
+            assert!(add_build_step(&mut rules, "web", "/js/app.js", ops()).is_err());
+            assert!(add_build_step(&mut rules, "/web", "js/app.js", ops()).is_err());
+            assert!(add_build_step(&mut rules, "/web", "/../../etc/x", ops()).is_err());
+            assert!(add_build_step(&mut rules, "/web", "/a/../../x", ops()).is_err());
+            assert!(
+                add_build_step_for_sources(&mut rules, "/web", "*.scss", "/../*.css", ops())
+                    .is_err()
+            );
+            assert!(
+                add_build_step_for_sources(&mut rules, "/web", "[", "/css/*.css", ops()).is_err()
+            );
+            assert!(add_build_step_for_sources(
+                &mut rules,
+                "/web",
+                "**/*.scss",
+                "/css/style.css",
+                ops()
+            )
+            .is_err());
+        }
+
+        fn map(script: &str) -> rhai::Map {
+            rhai::Engine::new()
+                .eval::<rhai::Map>(script)
+                .expect("invalid map")
+        }
+
+        #[test]
+        fn adds_pipeline_from_map() {
+            let (paths, tree) = crate::test::simple_init();
+            let mut rules = Rules::new(paths);
+            let definition = map(r#"#{
+                dir: "/web",
+                glob: "/style.css",
+                ops: [
+                    #{ run: "sass main.scss > $TARGET", name: "css", env: #{ A: "1" }, dir: "styles" },
+                    "echo done",
+                ],
+                inputs: ["**/*.scss"],
+                outputs: #{ map: "$TARGET_NAME.map" },
+            }"#);
+            add_pipeline_from_map(&mut rules, definition).expect("failed to add pipeline");
+
+            let definition = rules.pipelines().next().unwrap().definition();
+            assert!(definition.contains("[css] cd styles && A=1 sass main.scss > $TARGET"));
+            assert!(definition.contains("echo done"));
+            assert!(definition.contains("**/*.scss"));
+            assert!(definition.contains("map=$TARGET_NAME.map"));
+        }
+
+        #[test]
+        fn rejects_invalid_pipeline_maps() {
+            let (paths, tree) = crate::test::simple_init();
+            let mut rules = Rules::new(paths);
+            for definition in [
+                // missing glob
+                r#"#{ dir: "/web", ops: [] }"#,
+                // unknown key
+                r#"#{ dir: "/web", glob: "*", ops: [], input: [] }"#,
+                r#"#{ dir: "/web", glob: "*", ops: [#{ run: "true", evn: #{} }] }"#,
+                // options for a builtin
+                r#"#{ dir: "/web", glob: "*", ops: [#{ run: OP_COPY, name: "copy" }] }"#,
+                r#"#{ dir: "/web", glob: "*", ops: [#{ run: "true", only: "staging" }] }"#,
+                r#"#{ dir: "/web", glob: "*", ops: [#{ run: "true", dir: "/abs" }] }"#,
+                r#"#{ dir: "/web", glob: "*", ops: "true" }"#,
+            ] {
+                let definition =
+                    definition.replace("OP_COPY", &format!("\"{}\"", pipeworks::OP_COPY));
+                assert!(
+                    add_pipeline_from_map(&mut rules, map(&definition)).is_err(),
+                    "{definition}"
+                );
+            }
+            assert_eq!(rules.pipelines().count(), 0);
+        }
+
+        #[test]
+        fn skips_ops_for_other_build_modes() {
+            use crate::core::engine::BuildMode;
+
+            let (paths, tree) = crate::test::simple_init();
+            let definition = || {
+                map(r#"#{
+                    dir: "/web",
+                    glob: "*",
+                    ops: [
+                        "build",
+                        #{ run: "minify", only: "prod" },
+                        #{ run: "sourcemap", only: "dev" },
+                    ],
+                }"#)
+            };
+
+            let mut rules = Rules::new(paths.clone());
+            add_pipeline_from_map(&mut rules, definition()).unwrap();
+            let prod = rules.pipelines().next().unwrap().definition();
+            assert!(prod.contains("minify") && !prod.contains("sourcemap"));
+
+            let mut rules = Rules::new(paths).with_build_mode(BuildMode::Development);
+            add_pipeline_from_map(&mut rules, definition()).unwrap();
+            let dev = rules.pipelines().next().unwrap().definition();
+            assert!(!dev.contains("minify") && dev.contains("sourcemap"));
+        }
+
+        #[test]
+        fn adds_build_steps_from_map() {
+            let (paths, tree) = crate::test::simple_init();
+            std::fs::write(tree.path().join("main.scss"), "").unwrap();
+            let mut rules = Rules::new(paths.clone());
+
+            for definition in [
+                r#"#{ dir: "/", target: "/a.js", ops: ["true"] }"#,
+                r#"#{ dir: "/", targets: ["/b.js", "/c.js"], ops: ["true"] }"#,
+                r#"#{ dir: "/", sources: "*.scss", target: "/*.css", ops: ["true"],
+                      outputs: #{ map: "$TARGET_NAME.map" } }"#,
+            ] {
+                add_build_step_from_map(&mut rules, map(definition))
+                    .expect("failed to add build step");
+            }
+            for definition in [
+                r#"#{ dir: "/", ops: ["true"] }"#,
+                r#"#{ dir: "/", target: "/a.js", targets: ["/b.js"], ops: ["true"] }"#,
+                r#"#{ dir: "/", target: "a.js", ops: ["true"] }"#,
+                r#"#{ dir: "/", sources: "*.scss", target: "/style.css", ops: ["true"] }"#,
+            ] {
+                assert!(add_build_step_from_map(&mut rules, map(definition)).is_err());
+            }
+
+            let output_dir = paths.abs_output_dir();
+            let steps = rules.build_steps().collect::<Vec<_>>();
+            assert_eq!(steps.len(), 3);
+            assert_eq!(steps[1].jobs(&output_dir).unwrap().len(), 2);
+
+            let job = &steps[2].jobs(&output_dir).unwrap()[0];
+            assert_eq!(job.uri, "/main.css");
+            assert_eq!(
+                steps[2].output_paths(job),
+                vec![output_dir.join(&crate::RelPath::from_relative("main.css.map"))]
+            );
+        }
+
+        #[test]
+        fn finds_build_step_jobs() {
+            let (paths, tree) = crate::test::simple_init();
+            std::fs::create_dir_all(tree.path().join("web/themes")).unwrap();
+            std::fs::write(tree.path().join("web/main.scss"), "").unwrap();
+            std::fs::write(tree.path().join("web/themes/dark.scss"), "").unwrap();
+            std::fs::write(tree.path().join("web/app.js"), "").unwrap();
+
+            let mut rules = Rules::new(paths.clone());
+            let ops = || vec!["sass $SOURCE > $TARGET".into()];
+            add_build_step_for_sources(&mut rules, "/web", "**/*.scss", "/css/*.css", ops())
+                .expect("failed to add build step");
+            add_build_step(&mut rules, "/web", "/js/app.js", ops())
+                .expect("failed to add build step");
+
+            let output_dir = paths.abs_output_dir();
+            let jobs = rules
+                .build_steps()
+                .map(|step| step.jobs(&output_dir).unwrap())
+                .collect::<Vec<_>>();
+            let uris = |jobs: &[crate::core::rules::BuildStepJob]| {
+                jobs.iter()
+                    .map(|job| (job.uri.clone(), job.source.as_path().to_path_buf()))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(
+                uris(&jobs[0]),
+                vec![
+                    (
+                        "/css/main.css".to_owned(),
+                        tree.path().join("web/main.scss")
+                    ),
+                    (
+                        "/css/themes/dark.css".to_owned(),
+                        tree.path().join("web/themes/dark.scss")
+                    ),
+                ]
+            );
+            assert_eq!(
+                uris(&jobs[1]),
+                vec![("/js/app.js".to_owned(), tree.path().join("web/app.js"))]
+            );
+            assert_eq!(
+                jobs[1][0].target,
+                output_dir.join(&crate::RelPath::from_relative("js/app.js"))
+            );
+        }
+
         #[test]
         fn adds_mount_at_target() {
             let (paths, tree) = crate::test::simple_init();
@@ -577,6 +1910,234 @@ pub mod script {
             assert_eq!(rules.external_watches().count(), 1);
         }
 
+        #[test]
+        fn adds_template_rule() {
+            let (paths, tree) = crate::test::simple_init();
+            let mut rules = Rules::new(paths);
+            set_template(&mut rules, "blog/**/*.md", "blog/post.tera")
+                .expect("failed to add template rule");
+            assert_eq!(
+                rules.find_template("/blog/2022/post.md"),
+                Some("blog/post.tera".into())
+            );
+            assert!(rules.find_template("/about.md").is_none());
+        }
+
+        #[test]
+        fn adds_page() {
+            let (paths, tree) = crate::test::simple_init();
+            let mut rules = Rules::new(paths);
+            let mut context = rhai::Map::new();
+            context.insert("name".into(), "Widget".into());
+            add_page(&mut rules, "/products/widget.html", "product.tera", context)
+                .expect("failed to add page");
+
+            let page = rules.pages().next().unwrap();
+            assert_eq!(page.uri().as_str(), "/products/widget.html");
+            assert_eq!(page.frontmatter().meta["name"], "Widget");
+        }
+
+        #[test]
+        fn rejects_bad_page_uri() {
+            let (paths, tree) = crate::test::simple_init();
+            let mut rules = Rules::new(paths);
+            assert!(add_page(&mut rules, "/feed.xml", "feed.tera", rhai::Map::new()).is_err());
+        }
+
+        #[test]
+        fn adds_template_output() {
+            let (paths, tree) = crate::test::simple_init();
+            let mut rules = Rules::new(paths);
+            render_template(&mut rules, "feeds/atom.tera", "feed.xml")
+                .expect("failed to add template output");
+
+            let output = rules.template_outputs().next().unwrap();
+            assert_eq!(output.uri(), "/feed.xml");
+            assert_eq!(
+                output.target().to_relative_path().to_path_buf(),
+                std::path::PathBuf::from("target/feed.xml")
+            );
+        }
+
+        #[test]
+        fn rejects_directory_template_output() {
+            let (paths, tree) = crate::test::simple_init();
+            let mut rules = Rules::new(paths);
+            assert!(render_template(&mut rules, "feeds/atom.tera", "/feeds/").is_err());
+        }
+
+        #[test]
+        fn sets_base_url() {
+            let (paths, tree) = crate::test::simple_init();
+            let mut rules = Rules::new(paths);
+            set_base_url(&mut rules, "https://example.com/").expect("failed to set base url");
+            assert_eq!(rules.base_url(), Some("https://example.com"));
+            assert!(set_base_url(&mut rules, "example.com").is_err());
+        }
+
+        #[test]
+        fn disables_sitemap() {
+            let (paths, tree) = crate::test::simple_init();
+            let mut rules = Rules::new(paths);
+            assert!(rules.sitemap_enabled());
+            set_sitemap(&mut rules, false);
+            assert!(!rules.sitemap_enabled());
+        }
+
+        #[test]
+        fn adds_feed() {
+            use crate::core::feed::FeedFormat;
+
+            let (paths, tree) = crate::test::simple_init();
+            let mut rules = Rules::new(paths);
+            let mut options = rhai::Map::new();
+            options.insert("format".into(), "rss".into());
+            add_feed_with_options(&mut rules, "/blog/**", "blog/rss.xml", options)
+                .expect("failed to add feed");
+
+            let feed = rules.feeds().next().unwrap();
+            assert_eq!(feed.uri(), "/blog/rss.xml");
+            assert_eq!(feed.options().format, FeedFormat::Rss);
+            assert_eq!(rules.lints().len(), 2);
+        }
+
+        #[test]
+        fn sets_robots() {
+            let (paths, tree) = crate::test::simple_init();
+            let mut rules = Rules::new(paths);
+            assert!(rules.robots().is_none());
+
+            let mut group = rhai::Map::new();
+            group.insert("user_agent".into(), "*".into());
+            group.insert(
+                "disallow".into(),
+                vec![rhai::Dynamic::from("/drafts/".to_owned())].into(),
+            );
+            let mut options = rhai::Map::new();
+            options.insert("groups".into(), vec![rhai::Dynamic::from(group)].into());
+            set_robots_with_options(&mut rules, options).expect("failed to set robots");
+
+            let robots = rules.robots().unwrap();
+            assert_eq!(robots.groups[0].disallow, vec!["/drafts/"]);
+            assert!(robots.sitemap);
+        }
+
+        #[test]
+        fn sets_security_txt() {
+            let (paths, tree) = crate::test::simple_init();
+            let mut rules = Rules::new(paths);
+
+            let mut fields = rhai::Map::new();
+            fields.insert("contact".into(), "mailto:security@example.com".into());
+            assert!(set_security_txt(&mut rules, fields.clone()).is_err());
+
+            fields.insert("expires".into(), "2030-01-02".into());
+            set_security_txt(&mut rules, fields).expect("failed to set security.txt");
+            let security_txt = rules.security_txt().unwrap();
+            assert_eq!(security_txt.contact, vec!["mailto:security@example.com"]);
+            assert_eq!(security_txt.expires, "2030-01-02T00:00:00Z");
+        }
+
+        #[test]
+        fn sets_anchor_check() {
+            use crate::core::page::lint::LintLevel;
+
+            let (paths, tree) = crate::test::simple_init();
+            let mut rules = Rules::new(paths);
+            assert_eq!(rules.anchor_check(), Some(LintLevel::Warn));
+
+            set_anchor_check(&mut rules, "DENY").expect("failed to set anchor check");
+            assert_eq!(rules.anchor_check(), Some(LintLevel::Deny));
+            set_anchor_check(&mut rules, "OFF").expect("failed to set anchor check");
+            assert_eq!(rules.anchor_check(), None);
+            assert!(set_anchor_check(&mut rules, "LOUD").is_err());
+        }
+
+        #[test]
+        fn rejects_bad_feed_options() {
+            let (paths, tree) = crate::test::simple_init();
+            let mut rules = Rules::new(paths);
+            let mut options = rhai::Map::new();
+            options.insert("format".into(), "json".into());
+            assert!(add_feed_with_options(&mut rules, "**", "/feed.xml", options).is_err());
+            assert!(add_feed(&mut rules, "**", "/feeds/").is_err());
+            assert!(add_feed(&mut rules, "**", "/../feed.xml").is_err());
+            assert!(add_feed(&mut rules, "**", "/a/../../feed.xml").is_err());
+        }
+
+        #[test]
+        fn enables_search() {
+            use crate::core::search::SearchField;
+
+            let (paths, tree) = crate::test::simple_init();
+            let mut rules = Rules::new(paths);
+            assert!(rules.search().is_none());
+
+            let mut fields = rhai::Map::new();
+            fields.insert("title".into(), 10.into());
+            fields.insert("content".into(), (0.5 as rhai::FLOAT).into());
+            let mut options = rhai::Map::new();
+            options.insert("fields".into(), fields.into());
+            options.insert("split_sections".into(), true.into());
+            enable_search_with_options(&mut rules, options).expect("failed to enable search");
+
+            let search = rules.search().unwrap();
+            assert_eq!(search.uri, "/search_index.json");
+            assert_eq!(search.fields.get(&SearchField::Title), Some(&10.0));
+            assert_eq!(search.fields.get(&SearchField::Content), Some(&0.5));
+            assert!(!search.fields.contains_key(&SearchField::Keywords));
+            assert!(search.split_sections);
+        }
+
+        #[test]
+        fn rejects_bad_search_options() {
+            let (paths, tree) = crate::test::simple_init();
+            let mut rules = Rules::new(paths);
+            let mut options = rhai::Map::new();
+            options.insert("fields".into(), "title".into());
+            assert!(enable_search_with_options(&mut rules, options).is_err());
+        }
+
+        #[test]
+        fn enables_content_api() {
+            let (paths, tree) = crate::test::simple_init();
+            let mut rules = Rules::new(paths);
+            assert!(!rules.content_api_enabled());
+            set_content_api(&mut rules, true);
+            assert!(rules.content_api_enabled());
+        }
+
+        #[test]
+        fn preserves_outputs() {
+            let (paths, tree) = crate::test::simple_init();
+            let mut rules = Rules::new(paths);
+            assert!(rules.is_preserved_output("CNAME"));
+            assert!(rules.is_preserved_output(".git/objects/pack"));
+            assert!(!rules.is_preserved_output("index.html"));
+            assert!(!rules.is_preserved_output("docs/CNAME"));
+
+            preserve_output(&mut rules, "downloads/*.zip").expect("failed to preserve output");
+            assert!(rules.is_preserved_output("downloads/release.zip"));
+            assert!(!rules.is_preserved_output("downloads/release.tar"));
+            assert!(preserve_output(&mut rules, "[").is_err());
+        }
+
+        #[test]
+        fn sets_not_found_page() {
+            let (paths, tree) = crate::test::simple_init();
+            let mut rules = Rules::new(paths);
+            assert_eq!(rules.not_found_page(), "/404.html");
+
+            set_not_found_page(&mut rules, "errors/index.html").expect("failed to set 404 page");
+            assert_eq!(rules.not_found_page(), "/errors/index.html");
+            assert!(rules.is_not_found_page("/errors/"));
+            assert!(rules.is_not_found_page("/404.html"));
+            assert!(!rules.is_not_found_page("/errors/other.html"));
+
+            assert!(set_not_found_page(&mut rules, "/errors/").is_err());
+            assert!(set_not_found_page(&mut rules, "/../secret.html").is_err());
+        }
+
         #[test]
         fn rejects_bad_pipeline_op() {
             let (paths, tree) = crate::test::simple_init();
@@ -740,6 +2301,21 @@ key="value""#;
             assert!(engine.is_err());
         }
 
+        #[test]
+        fn loads_data() {
+            let (paths, tree) = crate::test::simple_init();
+            std::fs::write(
+                tree.path().join("products.json"),
+                r#"[{"name": "Widget"}, {"name": "Gadget"}]"#,
+            )
+            .unwrap();
+
+            let mut rules = Rules::new(paths);
+            let data = load_data(&mut rules, "products.json").unwrap();
+            let data = data.cast::<rhai::Array>();
+            assert_eq!(data.len(), 2);
+        }
+
         #[test]
         fn loading_global_context_aborts_if_already_set() {
             use temptree::temptree;
@@ -8,7 +8,7 @@ use crate::core::rules::{RuleProcessor, Rules};
 use crate::core::Library;
 use crate::Result;
 
-use super::engine::GlobalEnginePaths;
+use super::engine::{BuildMode, GlobalEnginePaths};
 
 // Define the custom package 'MyCustomPackage'.
 def_package! {
@@ -99,22 +99,31 @@ impl ScriptEngine {
         RuleProcessor::new(engine, script.as_ref())
     }
 
-    fn new_scope(engine_paths: GlobalEnginePaths, library: &Library) -> Scope<'static> {
+    fn new_scope(
+        engine_paths: GlobalEnginePaths,
+        build_mode: BuildMode,
+        library: &Library,
+    ) -> Scope<'static> {
         use crate::core::page::lint::{LINT_LEVEL_DENY, LINT_LEVEL_WARN};
         use pipeworks::OP_COPY;
 
         let mut scope = Scope::new();
-        scope.push("rules", Rules::new(engine_paths));
+        scope.push(
+            "rules",
+            Rules::new(engine_paths).with_build_mode(build_mode),
+        );
         scope.push("PAGES", library.clone());
         scope.push("DENY", LINT_LEVEL_DENY);
         scope.push("WARN", LINT_LEVEL_WARN);
         scope.push("OP_COPY", OP_COPY);
+        scope.push("BUILD_MODE", build_mode.as_str());
         scope
     }
 
     pub fn build_rules<S: AsRef<str>>(
         &self,
         engine_paths: GlobalEnginePaths,
+        build_mode: BuildMode,
         library: &Library,
         script: S,
     ) -> Result<(RuleProcessor, Rules)> {
@@ -124,7 +133,7 @@ impl ScriptEngine {
             .compile(script)
             .wrap_err("Failed to compile AST while building rules")?;
 
-        let mut scope = Self::new_scope(engine_paths, library);
+        let mut scope = Self::new_scope(engine_paths, build_mode, library);
 
         self.engine
             .run_ast_with_scope(&mut scope, &ast)
@@ -157,8 +166,8 @@ mod test {
     fn scope_contains_proper_items() {
         let store = Library::default();
         let (paths, tree) = crate::test::simple_init();
-        let scope = ScriptEngine::new_scope(paths, &store);
-        let required_items = &["rules", "PAGES", "DENY", "WARN"];
+        let scope = ScriptEngine::new_scope(paths, BuildMode::Development, &store);
+        let required_items = &["rules", "PAGES", "DENY", "WARN", "OP_COPY", "BUILD_MODE"];
         for item in required_items {
             assert!(scope.contains(item));
         }
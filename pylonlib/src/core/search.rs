@@ -0,0 +1,313 @@
+pub mod stem;
+
+use std::collections::{BTreeMap, HashMap};
+
+use eyre::WrapErr;
+use serde::{Deserialize, Serialize};
+
+use crate::core::diagnostics::Diagnostics;
+use crate::core::engine::Engine;
+use crate::Result;
+
+const STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "if", "in", "into", "is", "it",
+    "no", "not", "of", "on", "or", "such", "that", "the", "their", "then", "there", "these",
+    "they", "this", "to", "was", "will", "with",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchField {
+    Title,
+    Keywords,
+    /// Plain text of the rendered Markdown.
+    Content,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchOptions {
+    /// Location of the search index in the output directory.
+    pub uri: String,
+    /// Fields to index along with the boost applied to matching terms.
+    pub fields: BTreeMap<SearchField, f64>,
+    /// Write a separate index for each top-level directory.
+    pub split_sections: bool,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            uri: "/search_index.json".to_owned(),
+            fields: BTreeMap::from([
+                (SearchField::Title, 3.0),
+                (SearchField::Keywords, 2.0),
+                (SearchField::Content, 1.0),
+            ]),
+            split_sections: false,
+        }
+    }
+}
+
+impl SearchOptions {
+    /// Checks the options and adds a leading slash to the uri.
+    pub fn validate(mut self) -> Result<Self> {
+        if !self.uri.starts_with('/') {
+            self.uri = format!("/{}", self.uri);
+        }
+        let extension = std::path::Path::new(&self.uri).extension();
+        if !extension.is_some_and(|ext| ext.eq_ignore_ascii_case("json")) {
+            eyre::bail!("search index '{}' must be a .json file", self.uri);
+        }
+        if crate::core::build_outputs::output_path(&self.uri).is_none() {
+            eyre::bail!(
+                "search index '{}' must be in the output directory",
+                self.uri
+            );
+        }
+        if let Some((field, boost)) = self.fields.iter().find(|(_, boost)| **boost <= 0.0) {
+            eyre::bail!("boost for search field '{field:?}' must be positive, found {boost}");
+        }
+        Ok(self)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchFile {
+    pub uri: String,
+    pub json: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Document<'p> {
+    uri: String,
+    title: Option<&'p str>,
+    keywords: &'p [String],
+    text: String,
+}
+
+#[derive(Debug, Serialize)]
+struct SearchIndex<'a> {
+    fields: &'a BTreeMap<SearchField, f64>,
+    documents: Vec<&'a Document<'a>>,
+    /// Stemmed terms mapped to `[document, score]` pairs, where `document` is an
+    /// index into `documents`.
+    index: BTreeMap<String, Vec<(usize, f64)>>,
+}
+
+/// Generates the search index for published and searchable pages in the library, other
+/// than the 404 page. When splitting by section, the file at `options.uri` maps each
+/// section to its index.
+pub fn generate(engine: &Engine, options: &SearchOptions) -> Result<Vec<SearchFile>> {
+    let mut pages = engine
+        .library()
+        .iter()
+        .map(|(_, page)| page)
+        .filter(|page| page.frontmatter().published && page.frontmatter().searchable)
+        .filter(|page| !engine.rules().is_not_found_page(page.uri().as_str()))
+        .collect::<Vec<_>>();
+    pages.sort_by_key(|page| page.uri().to_string());
+
+    let mut documents = vec![];
+    for page in pages {
+        // broken links are reported when the page itself is rendered
+        let html = crate::core::page::render::render_content(engine, page, &Diagnostics::new())
+            .wrap_err_with(|| {
+                format!(
+                    "Failed rendering content of '{}' for search index",
+                    page.uri()
+                )
+            })?;
+        documents.push(Document {
+            uri: page.uri().to_string(),
+            title: crate::core::feed::title(page),
+            keywords: &page.frontmatter().keywords,
+            text: plain_text(&html),
+        });
+    }
+
+    if !options.split_sections {
+        let json = index_json(options, documents.iter())?;
+        return Ok(vec![SearchFile {
+            uri: options.uri.clone(),
+            json,
+        }]);
+    }
+
+    let mut sections: BTreeMap<&str, Vec<&Document<'_>>> = BTreeMap::new();
+    for doc in &documents {
+        sections.entry(section(&doc.uri)).or_default().push(doc);
+    }
+
+    let base = &options.uri[..options.uri.len() - ".json".len()];
+    let mut manifest = BTreeMap::new();
+    let mut files = vec![];
+    for (section, docs) in sections {
+        // pages at the root are kept outside of the directory of sections, so they
+        // can't collide with a section
+        let uri = if section.is_empty() {
+            format!("{base}.root.json")
+        } else {
+            format!("{base}/{section}.json")
+        };
+        manifest.insert(format!("/{section}/").replace("//", "/"), uri.clone());
+        files.push(SearchFile {
+            uri,
+            json: index_json(options, docs.into_iter())?,
+        });
+    }
+    files.push(SearchFile {
+        uri: options.uri.clone(),
+        json: serde_json::to_string(&serde_json::json!({ "sections": manifest }))
+            .wrap_err("Failed serializing search index manifest")?,
+    });
+
+    Ok(files)
+}
+
+/// The top-level directory of the uri, or an empty string for pages at the root.
+fn section(uri: &str) -> &str {
+    let uri = uri.trim_start_matches('/');
+    match uri.split_once('/') {
+        Some((section, _)) => section,
+        None => "",
+    }
+}
+
+fn index_json<'a, D>(options: &'a SearchOptions, documents: D) -> Result<String>
+where
+    D: Iterator<Item = &'a Document<'a>>,
+{
+    let documents = documents.collect::<Vec<_>>();
+    let mut index: BTreeMap<String, Vec<(usize, f64)>> = BTreeMap::new();
+    for (id, doc) in documents.iter().enumerate() {
+        let mut scores: HashMap<String, f64> = HashMap::new();
+        for (field, boost) in &options.fields {
+            let text = match field {
+                SearchField::Title => doc.title.unwrap_or_default().to_owned(),
+                SearchField::Keywords => doc.keywords.join(" "),
+                SearchField::Content => doc.text.clone(),
+            };
+            for term in terms(&text) {
+                *scores.entry(term).or_default() += boost;
+            }
+        }
+        for (term, score) in scores {
+            index.entry(term).or_default().push((id, score));
+        }
+    }
+
+    serde_json::to_string(&SearchIndex {
+        fields: &options.fields,
+        documents,
+        index,
+    })
+    .wrap_err("Failed serializing search index")
+}
+
+/// Splits the text into lowercase and stemmed terms, skipping stop words.
+pub fn terms(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .filter(|word| !STOP_WORDS.contains(&word.as_str()))
+        .map(|word| stem::stem(&word))
+}
+
+/// Text content of the HTML with whitespace collapsed.
+pub fn plain_text(html: &str) -> String {
+    const BLOCKS: &[&str] = &[
+        "address",
+        "article",
+        "aside",
+        "blockquote",
+        "br",
+        "dd",
+        "div",
+        "dl",
+        "dt",
+        "figcaption",
+        "figure",
+        "footer",
+        "h1",
+        "h2",
+        "h3",
+        "h4",
+        "h5",
+        "h6",
+        "header",
+        "hr",
+        "li",
+        "main",
+        "nav",
+        "ol",
+        "p",
+        "pre",
+        "section",
+        "table",
+        "td",
+        "th",
+        "tr",
+        "ul",
+    ];
+
+    let fragment = scraper::Html::parse_fragment(html);
+    let mut text = String::new();
+    for node in fragment.root_element().descendants() {
+        match node.value() {
+            scraper::Node::Text(t) => text.push_str(t),
+            // separate the text of block elements
+            scraper::Node::Element(e) if BLOCKS.contains(&e.name()) => text.push(' '),
+            _ => (),
+        }
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod test {
+
+    #![allow(warnings, unused)]
+
+    use super::*;
+
+    #[test]
+    fn extracts_terms() {
+        let terms = terms("The Running-Cats, and 2 ponies!").collect::<Vec<_>>();
+        assert_eq!(terms, vec!["run", "cat", "2", "poni"]);
+    }
+
+    #[test]
+    fn extracts_plain_text() {
+        let html = "<h1>Title</h1><p>Some <em>emph</em>asized,\n text &amp; more</p>";
+        assert_eq!(plain_text(html), "Title Some emphasized, text & more");
+    }
+
+    #[test]
+    fn finds_section() {
+        assert_eq!(section("/blog/post.html"), "blog");
+        assert_eq!(section("/blog/2022/post.html"), "blog");
+        assert_eq!(section("/about.html"), "");
+    }
+
+    #[test]
+    fn validates_options() {
+        let options = SearchOptions {
+            uri: "search.json".to_owned(),
+            ..Default::default()
+        };
+        assert_eq!(options.validate().unwrap().uri, "/search.json");
+
+        for uri in ["/search/", "/../search.json", "/a/../../search.json"] {
+            let options = SearchOptions {
+                uri: uri.to_owned(),
+                ..Default::default()
+            };
+            assert!(options.validate().is_err(), "{uri}");
+        }
+
+        let mut options = SearchOptions::default();
+        options.fields.insert(SearchField::Title, 0.0);
+        assert!(options.validate().is_err());
+    }
+}
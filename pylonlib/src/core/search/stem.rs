@@ -0,0 +1,309 @@
+//! The Porter stemming algorithm for English words.
+//!
+//! See <https://tartarus.org/martin/PorterStemmer/def.txt>. This follows the reference
+//! C implementation, including its departures from the published algorithm.
+
+// indices are signed because the stem can be empty, which the reference
+// implementation represents with -1
+#![allow(clippy::cast_possible_wrap, clippy::cast_sign_loss)]
+
+/// Returns the stem of a lowercase word. Words containing anything other than ASCII
+/// letters are returned unchanged.
+pub fn stem(word: &str) -> String {
+    if word.len() <= 2 || !word.bytes().all(|b| b.is_ascii_lowercase()) {
+        return word.to_owned();
+    }
+    let mut stemmer = Stemmer {
+        b: word.as_bytes().to_vec(),
+        k: word.len() as isize - 1,
+        j: 0,
+    };
+    stemmer.step1ab();
+    if stemmer.k > 0 {
+        stemmer.step1c();
+        stemmer.step2();
+        stemmer.step3();
+        stemmer.step4();
+        stemmer.step5();
+    }
+    stemmer.b.truncate((stemmer.k + 1) as usize);
+    String::from_utf8(stemmer.b).expect("stems are always ASCII")
+}
+
+struct Stemmer {
+    b: Vec<u8>,
+    /// Index of the last letter of the current stem.
+    k: isize,
+    /// Index of the last letter before the most recently matched suffix.
+    j: isize,
+}
+
+impl Stemmer {
+    fn at(&self, i: isize) -> u8 {
+        self.b[i as usize]
+    }
+
+    fn cons(&self, i: isize) -> bool {
+        match self.at(i) {
+            b'a' | b'e' | b'i' | b'o' | b'u' => false,
+            b'y' => i == 0 || !self.cons(i - 1),
+            _ => true,
+        }
+    }
+
+    /// Number of consonant-vowel sequences between the start of the word and `j`.
+    fn m(&self) -> usize {
+        let mut n = 0;
+        let mut i = 0;
+        loop {
+            if i > self.j {
+                return n;
+            }
+            if !self.cons(i) {
+                break;
+            }
+            i += 1;
+        }
+        i += 1;
+        loop {
+            loop {
+                if i > self.j {
+                    return n;
+                }
+                if self.cons(i) {
+                    break;
+                }
+                i += 1;
+            }
+            i += 1;
+            n += 1;
+            loop {
+                if i > self.j {
+                    return n;
+                }
+                if !self.cons(i) {
+                    break;
+                }
+                i += 1;
+            }
+            i += 1;
+        }
+    }
+
+    fn vowel_in_stem(&self) -> bool {
+        (0..=self.j).any(|i| !self.cons(i))
+    }
+
+    fn double_cons(&self, i: isize) -> bool {
+        i >= 1 && self.at(i) == self.at(i - 1) && self.cons(i)
+    }
+
+    /// Consonant-vowel-consonant ending at `i`, where the last consonant is not w, x or y.
+    fn cvc(&self, i: isize) -> bool {
+        i >= 2
+            && self.cons(i)
+            && !self.cons(i - 1)
+            && self.cons(i - 2)
+            && !matches!(self.at(i), b'w' | b'x' | b'y')
+    }
+
+    fn ends(&mut self, suffix: &str) -> bool {
+        let len = suffix.len() as isize;
+        if len > self.k + 1 {
+            return false;
+        }
+        let start = (self.k + 1 - len) as usize;
+        if &self.b[start..=self.k as usize] != suffix.as_bytes() {
+            return false;
+        }
+        self.j = self.k - len;
+        true
+    }
+
+    fn set_to(&mut self, s: &str) {
+        self.b.truncate((self.j + 1) as usize);
+        self.b.extend_from_slice(s.as_bytes());
+        self.k = self.j + s.len() as isize;
+    }
+
+    fn replace(&mut self, s: &str) {
+        if self.m() > 0 {
+            self.set_to(s);
+        }
+    }
+
+    /// Removes plurals and -ed or -ing.
+    fn step1ab(&mut self) {
+        if self.at(self.k) == b's' {
+            if self.ends("sses") {
+                self.k -= 2;
+            } else if self.ends("ies") {
+                self.set_to("i");
+            } else if self.at(self.k - 1) != b's' {
+                self.k -= 1;
+            }
+        }
+        if self.ends("eed") {
+            if self.m() > 0 {
+                self.k -= 1;
+            }
+        } else if (self.ends("ed") || self.ends("ing")) && self.vowel_in_stem() {
+            self.k = self.j;
+            if self.ends("at") {
+                self.set_to("ate");
+            } else if self.ends("bl") {
+                self.set_to("ble");
+            } else if self.ends("iz") {
+                self.set_to("ize");
+            } else if self.double_cons(self.k) {
+                self.k -= 1;
+                if matches!(self.at(self.k), b'l' | b's' | b'z') {
+                    self.k += 1;
+                }
+            } else if self.m() == 1 && self.cvc(self.k) {
+                self.set_to("e");
+            }
+        }
+    }
+
+    /// Turns a terminal y into i when there is another vowel in the stem.
+    fn step1c(&mut self) {
+        if self.ends("y") && self.vowel_in_stem() {
+            let k = self.k as usize;
+            self.b[k] = b'i';
+        }
+    }
+
+    fn replace_first(&mut self, rules: &[(&str, &str)]) {
+        for (suffix, replacement) in rules {
+            if self.ends(suffix) {
+                self.replace(replacement);
+                return;
+            }
+        }
+    }
+
+    /// Maps double suffixes to single ones.
+    fn step2(&mut self) {
+        let rules: &[(&str, &str)] = match self.at(self.k - 1) {
+            b'a' => &[("ational", "ate"), ("tional", "tion")],
+            b'c' => &[("enci", "ence"), ("anci", "ance")],
+            b'e' => &[("izer", "ize")],
+            b'l' => &[
+                ("bli", "ble"),
+                ("alli", "al"),
+                ("entli", "ent"),
+                ("eli", "e"),
+                ("ousli", "ous"),
+            ],
+            b'o' => &[("ization", "ize"), ("ation", "ate"), ("ator", "ate")],
+            b's' => &[
+                ("alism", "al"),
+                ("iveness", "ive"),
+                ("fulness", "ful"),
+                ("ousness", "ous"),
+            ],
+            b't' => &[("aliti", "al"), ("iviti", "ive"), ("biliti", "ble")],
+            b'g' => &[("logi", "log")],
+            _ => &[],
+        };
+        self.replace_first(rules);
+    }
+
+    /// Handles -ic-, -full, -ness etc.
+    fn step3(&mut self) {
+        let rules: &[(&str, &str)] = match self.at(self.k) {
+            b'e' => &[("icate", "ic"), ("ative", ""), ("alize", "al")],
+            b'i' => &[("iciti", "ic")],
+            b'l' => &[("ical", "ic"), ("ful", "")],
+            b's' => &[("ness", "")],
+            _ => &[],
+        };
+        self.replace_first(rules);
+    }
+
+    /// Removes -ant, -ence etc. in a context of <c>vcvc<v>.
+    fn step4(&mut self) {
+        let matched = match self.at(self.k - 1) {
+            b'a' => self.ends("al"),
+            b'c' => self.ends("ance") || self.ends("ence"),
+            b'e' => self.ends("er"),
+            b'i' => self.ends("ic"),
+            b'l' => self.ends("able") || self.ends("ible"),
+            b'n' => self.ends("ant") || self.ends("ement") || self.ends("ment") || self.ends("ent"),
+            b'o' => {
+                (self.ends("ion") && self.j >= 0 && matches!(self.at(self.j), b's' | b't'))
+                    || self.ends("ou")
+            }
+            b's' => self.ends("ism"),
+            b't' => self.ends("ate") || self.ends("iti"),
+            b'u' => self.ends("ous"),
+            b'v' => self.ends("ive"),
+            b'z' => self.ends("ize"),
+            _ => false,
+        };
+        if matched && self.m() > 1 {
+            self.k = self.j;
+        }
+    }
+
+    /// Removes a final -e and changes -ll to -l when the stem is long enough.
+    fn step5(&mut self) {
+        self.j = self.k;
+        if self.at(self.k) == b'e' {
+            let m = self.m();
+            if m > 1 || m == 1 && !self.cvc(self.k - 1) {
+                self.k -= 1;
+            }
+        }
+        if self.at(self.k) == b'l' && self.double_cons(self.k) && self.m() > 1 {
+            self.k -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    #![allow(warnings, unused)]
+
+    use super::*;
+
+    #[test]
+    fn stems_words() {
+        let cases = [
+            ("caresses", "caress"),
+            ("ponies", "poni"),
+            ("ties", "ti"),
+            ("cats", "cat"),
+            ("feed", "feed"),
+            ("agreed", "agre"),
+            ("plastered", "plaster"),
+            ("motoring", "motor"),
+            ("sing", "sing"),
+            ("conflated", "conflat"),
+            ("troubled", "troubl"),
+            ("sized", "size"),
+            ("hopping", "hop"),
+            ("falling", "fall"),
+            ("filing", "file"),
+            ("happy", "happi"),
+            ("relational", "relat"),
+            ("generalizations", "gener"),
+            ("connection", "connect"),
+            ("adoption", "adopt"),
+            ("controlling", "control"),
+            ("running", "run"),
+        ];
+        for (word, expected) in cases {
+            assert_eq!(stem(word), expected, "stem of '{word}'");
+        }
+    }
+
+    #[test]
+    fn skips_non_ascii_words() {
+        assert_eq!(stem("über"), "über");
+        assert_eq!(stem("v2"), "v2");
+        assert_eq!(stem("is"), "is");
+    }
+}
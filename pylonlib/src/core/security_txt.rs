@@ -0,0 +1,170 @@
+use chrono::{DateTime, FixedOffset, SecondsFormat, Utc};
+use serde::{Deserialize, Deserializer};
+
+use crate::core::page::date;
+use crate::Result;
+
+/// Where `security.txt` is written, as required by RFC 9116.
+pub const SECURITY_TXT: &str = "/.well-known/security.txt";
+
+/// Fields of `security.txt`. Fields which may appear more than once accept either a
+/// single value or a list.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SecurityTxt {
+    #[serde(deserialize_with = "one_or_many")]
+    pub contact: Vec<String>,
+    /// Date after which the file should no longer be used.
+    pub expires: String,
+    #[serde(deserialize_with = "one_or_many")]
+    pub encryption: Vec<String>,
+    #[serde(deserialize_with = "one_or_many")]
+    pub acknowledgments: Vec<String>,
+    pub preferred_languages: Option<String>,
+    /// Defaults to the location of the file when the base url is set.
+    #[serde(deserialize_with = "one_or_many")]
+    pub canonical: Vec<String>,
+    #[serde(deserialize_with = "one_or_many")]
+    pub policy: Vec<String>,
+    #[serde(deserialize_with = "one_or_many")]
+    pub hiring: Vec<String>,
+}
+
+fn one_or_many<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    })
+}
+
+impl SecurityTxt {
+    /// Checks the required fields and normalizes the expiry date to RFC 3339.
+    pub fn validate(mut self) -> Result<Self> {
+        if self.contact.is_empty() {
+            eyre::bail!("security.txt requires at least one contact");
+        }
+        let Some(expires) = date::from_value(&serde_json::Value::String(self.expires.clone()))
+        else {
+            eyre::bail!("security.txt expiry '{}' is not a valid date", self.expires);
+        };
+        self.expires = expires.to_rfc3339_opts(SecondsFormat::Secs, true);
+        Ok(self)
+    }
+
+    pub fn expires(&self) -> Option<DateTime<FixedOffset>> {
+        DateTime::parse_from_rfc3339(&self.expires).ok()
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires()
+            .is_some_and(|expires| expires < DateTime::<Utc>::from(std::time::SystemTime::now()))
+    }
+
+    /// Generates `security.txt`, linking to itself when `base_url` is known and no
+    /// canonical location was given.
+    pub fn generate(&self, base_url: Option<&str>) -> String {
+        let mut lines = vec![];
+        let mut push = |field: &str, values: &[String]| {
+            for value in values {
+                lines.push(format!("{field}: {value}"));
+            }
+        };
+        push("Contact", &self.contact);
+        push("Expires", std::slice::from_ref(&self.expires));
+        push("Encryption", &self.encryption);
+        push("Acknowledgments", &self.acknowledgments);
+        if let Some(languages) = &self.preferred_languages {
+            push("Preferred-Languages", std::slice::from_ref(languages));
+        }
+        match base_url {
+            Some(base_url) if self.canonical.is_empty() => {
+                push("Canonical", &[format!("{base_url}{SECURITY_TXT}")]);
+            }
+            _ => push("Canonical", &self.canonical),
+        }
+        push("Policy", &self.policy);
+        push("Hiring", &self.hiring);
+        lines.push(String::new());
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    #![allow(warnings, unused)]
+
+    use super::*;
+
+    fn security_txt() -> SecurityTxt {
+        SecurityTxt {
+            contact: vec!["mailto:security@example.com".to_owned()],
+            expires: "2030-01-02".to_owned(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn validates_fields() {
+        let txt = security_txt().validate().unwrap();
+        assert_eq!(txt.expires, "2030-01-02T00:00:00Z");
+        assert!(!txt.is_expired());
+
+        let txt = SecurityTxt {
+            contact: vec![],
+            ..security_txt()
+        };
+        assert!(txt.validate().is_err());
+
+        let txt = SecurityTxt {
+            expires: "soon".to_owned(),
+            ..security_txt()
+        };
+        assert!(txt.validate().is_err());
+    }
+
+    #[test]
+    fn detects_expired_file() {
+        let txt = SecurityTxt {
+            expires: "2000-01-01".to_owned(),
+            ..security_txt()
+        };
+        assert!(txt.validate().unwrap().is_expired());
+    }
+
+    #[test]
+    fn generates_fields_in_order() {
+        let txt = SecurityTxt {
+            preferred_languages: Some("en, de".to_owned()),
+            policy: vec!["https://example.com/policy.html".to_owned()],
+            ..security_txt()
+        }
+        .validate()
+        .unwrap();
+        assert_eq!(
+            txt.generate(Some("https://example.com")),
+            "Contact: mailto:security@example.com\nExpires: 2030-01-02T00:00:00Z\nPreferred-Languages: en, de\nCanonical: https://example.com/.well-known/security.txt\nPolicy: https://example.com/policy.html\n"
+        );
+        assert!(!txt.generate(None).contains("Canonical"));
+    }
+
+    #[test]
+    fn accepts_single_values() {
+        let txt: SecurityTxt = serde_json::from_value(serde_json::json!({
+            "contact": "mailto:security@example.com",
+            "expires": "2030-01-02",
+            "encryption": ["https://example.com/a.asc", "https://example.com/b.asc"],
+        }))
+        .unwrap();
+        assert_eq!(txt.contact, vec!["mailto:security@example.com"]);
+        assert_eq!(txt.encryption.len(), 2);
+    }
+}
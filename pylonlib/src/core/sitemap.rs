@@ -0,0 +1,240 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, SecondsFormat};
+
+use crate::core::page::date;
+use crate::core::Page;
+use crate::util::escape_xml;
+
+/// Maximum number of URLs allowed in a single sitemap file.
+pub const MAX_URLS: usize = 50_000;
+
+/// Maximum size in bytes of a single uncompressed sitemap file.
+pub const MAX_BYTES: usize = 50 * 1024 * 1024;
+
+const URLSET_OPEN: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+"#;
+const URLSET_CLOSE: &str = "</urlset>\n";
+
+const INDEX_OPEN: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+"#;
+const INDEX_CLOSE: &str = "</sitemapindex>\n";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SitemapFile {
+    pub uri: String,
+    pub xml: String,
+}
+
+/// Generates `/sitemap.xml` for the pages. When the pages don't fit into a single
+/// sitemap, they are split into `/sitemap1.xml`, `/sitemap2.xml`, etc. and
+/// `/sitemap.xml` becomes a sitemap index.
+pub fn generate<'a, P>(base_url: &str, pages: P) -> Vec<SitemapFile>
+where
+    P: IntoIterator<Item = &'a Page>,
+{
+    let mut pages = pages
+        .into_iter()
+        .filter(|page| include_page(page))
+        .collect::<Vec<_>>();
+    pages.sort_by_key(|page| page.uri().to_string());
+
+    let entries = pages.into_iter().map(|page| url_entry(base_url, page));
+
+    let mut urlsets = vec![];
+    let mut current = vec![];
+    let mut current_size = URLSET_OPEN.len() + URLSET_CLOSE.len();
+    for entry in entries {
+        if current.len() == MAX_URLS || current_size + entry.len() > MAX_BYTES {
+            urlsets.push(std::mem::take(&mut current));
+            current_size = URLSET_OPEN.len() + URLSET_CLOSE.len();
+        }
+        current_size += entry.len();
+        current.push(entry);
+    }
+    urlsets.push(current);
+
+    if urlsets.len() == 1 {
+        return vec![SitemapFile {
+            uri: "/sitemap.xml".to_owned(),
+            xml: urlset(&urlsets[0]),
+        }];
+    }
+
+    let mut files = urlsets
+        .iter()
+        .enumerate()
+        .map(|(i, entries)| SitemapFile {
+            uri: format!("/sitemap{}.xml", i + 1),
+            xml: urlset(entries),
+        })
+        .collect::<Vec<_>>();
+
+    let index = {
+        let mut xml = INDEX_OPEN.to_owned();
+        for file in &files {
+            let loc = escape_xml(&format!("{base_url}{}", file.uri));
+            xml.push_str("  <sitemap><loc>");
+            xml.push_str(&loc);
+            xml.push_str("</loc></sitemap>\n");
+        }
+        xml.push_str(INDEX_CLOSE);
+        xml
+    };
+    files.push(SitemapFile {
+        uri: "/sitemap.xml".to_owned(),
+        xml: index,
+    });
+
+    files
+}
+
+fn include_page(page: &Page) -> bool {
+    let frontmatter = page.frontmatter();
+    frontmatter.published && frontmatter.searchable && frontmatter.sitemap && !frontmatter.noindex
+}
+
+fn url_entry(base_url: &str, page: &Page) -> String {
+    let loc = escape_xml(&format!("{base_url}{}", page.uri()));
+    if let Some(lastmod) = lastmod(page) {
+        format!("  <url><loc>{loc}</loc><lastmod>{lastmod}</lastmod></url>\n")
+    } else {
+        format!("  <url><loc>{loc}</loc></url>\n")
+    }
+}
+
+fn urlset(entries: &[String]) -> String {
+    let mut xml = URLSET_OPEN.to_owned();
+    for entry in entries {
+        xml.push_str(entry);
+    }
+    xml.push_str(URLSET_CLOSE);
+    xml
+}
+
+/// Uses the `updated` or `date` metadata of the page, falling back to the
+/// modification time of the Markdown file.
+fn lastmod(page: &Page) -> Option<String> {
+    date::find_with(page, &["updated", "date"], w3c_date).or_else(|| {
+        date::modified(page).map(|date| date.to_rfc3339_opts(SecondsFormat::Secs, true))
+    })
+}
+
+/// Formats a metadata date in W3C Datetime format. Dates without a time zone
+/// are written as date only, so no time zone is made up.
+fn w3c_date(value: &serde_json::Value) -> Option<String> {
+    let date = date::value_str(value)?;
+    if let Ok(date) = DateTime::parse_from_rfc3339(date) {
+        return Some(date.to_rfc3339_opts(SecondsFormat::Secs, true));
+    }
+    if let Ok(date) = NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S") {
+        return Some(date.date().to_string());
+    }
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .ok()
+        .map(|date| date.to_string())
+}
+
+#[cfg(test)]
+mod test {
+
+    #![allow(warnings, unused)]
+
+    use super::*;
+    use crate::core::engine::GlobalEnginePaths;
+    use std::collections::HashMap;
+
+    fn page(uri: &str, meta: &[(&str, serde_json::Value)]) -> Page {
+        let (paths, _tree) = crate::test::simple_init();
+        page_with_paths(paths, uri, meta)
+    }
+
+    fn page_with_paths(
+        paths: GlobalEnginePaths,
+        uri: &str,
+        meta: &[(&str, serde_json::Value)],
+    ) -> Page {
+        let meta = meta
+            .iter()
+            .map(|(k, v)| ((*k).to_owned(), v.clone()))
+            .collect::<HashMap<_, _>>();
+        Page::new_virtual(paths, uri, "default.tera".into(), meta).unwrap()
+    }
+
+    #[test]
+    fn generates_single_sitemap() {
+        let pages = vec![
+            page("/b.html", &[("date", "2022-01-02".into())]),
+            page("/a.html", &[]),
+        ];
+        let files = generate("https://example.com", &pages);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].uri, "/sitemap.xml");
+        assert_eq!(
+            files[0].xml,
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <url><loc>https://example.com/a.html</loc></url>
+  <url><loc>https://example.com/b.html</loc><lastmod>2022-01-02</lastmod></url>
+</urlset>
+"#
+        );
+    }
+
+    #[test]
+    fn prefers_updated_over_date() {
+        let page = page(
+            "/a.html",
+            &[
+                ("date", "2022-01-02".into()),
+                ("updated", "2022-03-04T05:06:07Z".into()),
+            ],
+        );
+        assert_eq!(lastmod(&page), Some("2022-03-04T05:06:07Z".to_owned()));
+    }
+
+    #[test]
+    fn reads_toml_dates() {
+        let date = serde_json::json!({ "$__toml_private_datetime": "2022-01-02" });
+        let page = page("/a.html", &[("date", date)]);
+        assert_eq!(lastmod(&page), Some("2022-01-02".to_owned()));
+    }
+
+    #[test]
+    fn escapes_urls() {
+        assert_eq!(escape_xml("/a&b.html"), "/a&amp;b.html");
+    }
+
+    #[test]
+    fn excludes_pages() {
+        let mut unsearchable = page("/a.html", &[]);
+        unsearchable.frontmatter.searchable = false;
+        let mut no_sitemap = page("/b.html", &[]);
+        no_sitemap.frontmatter.sitemap = false;
+        let mut unpublished = page("/c.html", &[]);
+        unpublished.frontmatter.published = false;
+        let mut noindex = page("/d.html", &[]);
+        noindex.frontmatter.noindex = true;
+
+        let pages = vec![unsearchable, no_sitemap, unpublished, noindex];
+        let files = generate("https://example.com", &pages);
+        assert!(!files[0].xml.contains("<url>"));
+    }
+
+    #[test]
+    fn splits_large_sitemaps() {
+        let (paths, _tree) = crate::test::simple_init();
+        let pages = (0..MAX_URLS + 1)
+            .map(|i| page_with_paths(paths.clone(), &format!("/{i}.html"), &[]))
+            .collect::<Vec<_>>();
+        let files = generate("https://example.com", &pages);
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[0].uri, "/sitemap1.xml");
+        assert_eq!(files[1].uri, "/sitemap2.xml");
+        assert_eq!(files[1].xml.matches("<url>").count(), 1);
+        assert_eq!(files[2].uri, "/sitemap.xml");
+        assert!(files[2]
+            .xml
+            .contains("<sitemap><loc>https://example.com/sitemap2.xml</loc></sitemap>"));
+    }
+}
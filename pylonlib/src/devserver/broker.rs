@@ -2,7 +2,7 @@ use std::net::SocketAddr;
 use std::sync::Arc;
 use std::thread::{self, JoinHandle};
 
-use crate::core::engine::{Engine, GlobalEnginePaths};
+use crate::core::engine::{BuildMode, Engine, GlobalEnginePaths};
 use crate::core::library::SearchKey;
 use crate::core::page::RenderedPage;
 use crate::devserver::{DevServerMsg, DevServerReceiver, DevServerSender};
@@ -59,6 +59,8 @@ pub enum EngineMsg {
     /// Renders a page and then returns it on the channel supplied in
     /// the request.
     RenderPage(EngineRequest<SearchKey, Result<Option<RenderedPage>>>),
+    /// Renders the 404 page of the site, if there is one.
+    RenderNotFound(EngineRequest<(), Result<Option<RenderedPage>>>),
     ProcessPipelines(EngineRequest<Uri, Result<()>>),
     ProcessMounts(EngineRequest<(), Result<()>>),
     /// Quits the application
@@ -176,7 +178,8 @@ impl EngineBroker {
         let bind = bind.into();
         let broker = self.clone();
         let engine_handle = thread::spawn(move || {
-            let mut engine = Engine::new(paths)?;
+            // pipeline operations limited to `dev` only run in the development server
+            let mut engine = Engine::with_build_mode(paths, BuildMode::Development)?;
 
             // engine.process_mounts(engine.rules().mounts())?;
 
@@ -199,6 +202,11 @@ impl EngineBroker {
                                 )
                             });
                         }
+                        EngineMsg::RenderNotFound(chan) => {
+                            respond_sync!(chan, &broker.handle(), {
+                                handle_msg::render_not_found(&engine, broker.render_behavior)
+                            });
+                        }
                         EngineMsg::ProcessPipelines(chan) => {
                             respond_sync!(chan, &broker.handle(), {
                                 handle_msg::process_pipelines(&engine, &chan.inner)
@@ -244,8 +252,11 @@ mod handle_msg {
 
     use crate::{
         core::{
+            build_outputs::BuildOutputs,
+            build_report::BuildReport,
+            diagnostics::Diagnostics,
             engine::{step, Engine},
-            page::RenderedPage,
+            page::{RenderedPage, RenderedPageCollection},
             Page,
         },
         devserver::broker::RenderBehavior,
@@ -278,7 +289,16 @@ mod handle_msg {
             .map(|(_, asset)| asset)
             .collect::<HtmlAssets>();
 
-        step::run_pipelines(engine, &required_assets)?;
+        let diagnostics = Diagnostics::new();
+        step::run_pipelines(
+            engine,
+            &required_assets,
+            None,
+            &BuildOutputs::new(engine.paths().abs_output_dir()),
+            &diagnostics,
+            &BuildReport::new(),
+        )?;
+        diagnostics.check()?;
 
         // let missing_assets = step::run_pipelines(engine, &required_assets)
         //     .wrap_err("Failed to run pipelines in dev server")?
@@ -310,7 +330,16 @@ mod handle_msg {
             if lints.has_deny() {
                 Err(eyre!(lints.to_string()))
             } else {
-                let rendered_collection = step::render(engine, std::iter::once(page))
+                let diagnostics = Diagnostics::new();
+                let rendered_collection = step::render(
+                    engine,
+                    std::iter::once(page),
+                    &diagnostics,
+                    &BuildReport::new(),
+                )
+                .wrap_err_with(|| format!("Failed to render page '{}'", page.uri()))?;
+                diagnostics
+                    .check()
                     .wrap_err_with(|| format!("Failed to render page '{}'", page.uri()))?;
 
                 if render_behavior == RenderBehavior::Write {
@@ -329,11 +358,22 @@ mod handle_msg {
                             assets.drop_offsite();
                             assets
                         })?;
-                let missing_assets = step::run_pipelines(engine, &missing_assets)
-                    .wrap_err("Failed to run pipelines during single page render")?
-                    .into_iter()
-                    .filter(|asset| step::filter::not_on_disk(*asset))
-                    .collect::<HashSet<_>>();
+                let missing_assets = step::run_pipelines(
+                    engine,
+                    &missing_assets,
+                    None,
+                    &BuildOutputs::new(engine.paths().abs_output_dir()),
+                    &diagnostics,
+                    &BuildReport::new(),
+                )
+                .wrap_err("Failed to run pipelines during single page render")?
+                .into_iter()
+                .filter(|asset| step::filter::not_on_disk(*asset))
+                .collect::<HashSet<_>>();
+
+                diagnostics
+                    .check()
+                    .wrap_err("Failed to run pipelines during single page render")?;
 
                 if !missing_assets.is_empty() {
                     return Err(eyre!(missing_assets
@@ -344,11 +384,39 @@ mod handle_msg {
 
                 Ok(Some(rendered_page))
             }
+        } else if let Some(output) = engine
+            .rules()
+            .template_outputs()
+            .find(|output| output.uri() == search_key.as_ref())
+        {
+            let rendered = crate::core::page::render::render_template_output(engine, output)
+                .wrap_err_with(|| format!("Failed to render template output '{}'", output.uri()))?;
+            let rendered_collection = RenderedPageCollection::from_vec(vec![rendered]);
+
+            if render_behavior == RenderBehavior::Write {
+                rendered_collection.write_to_disk().wrap_err(
+                    "Failed to write template output to disk with RenderBehavior::Write",
+                )?;
+            }
+
+            Ok(rendered_collection.into_iter().next())
         } else {
             Ok(None)
         }
     }
 
+    pub fn render_not_found(
+        engine: &Engine,
+        render_behavior: RenderBehavior,
+    ) -> Result<Option<RenderedPage>> {
+        trace!("receive render 404 page message");
+
+        let uri = engine.rules().not_found_page();
+        // index pages are searched without the `index.html`
+        let search_key = Uri::new(uri, uri)?.as_str().to_owned();
+        render_page(engine, search_key, render_behavior)
+    }
+
     pub fn fs_event(engine: &mut Engine, events: &FilesystemUpdateEvents) -> Result<()> {
         trace!(events = ?events, "receive file system update message");
         let mut reload_templates = false;
@@ -376,13 +444,27 @@ mod handle_msg {
                         format!("Failed to confirm path on fsevent for path '{}'", path)
                     })?
                 };
-                let page = Page::from_file(engine.paths(), checked_path, engine.renderers())
+                let mut page = Page::from_file(engine.paths(), checked_path, engine.renderers())
                     .wrap_err_with(|| {
                         format!(
                             "Failed to create new page from filesystem event at '{}'",
                             path
                         )
                     })?;
+                {
+                    let all_templates = engine
+                        .renderers()
+                        .tera()
+                        .get_template_names()
+                        .into_iter()
+                        .collect::<HashSet<_>>();
+                    crate::core::page::util::assign_template(
+                        &mut page,
+                        engine.rules(),
+                        &all_templates,
+                    )
+                    .wrap_err_with(|| format!("Failed to assign template to page '{path}'"))?;
+                }
                 // update will automatically insert the page if it doesn't exist
                 let _ = engine.library_mut().update(page);
             }
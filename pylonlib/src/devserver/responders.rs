@@ -123,10 +123,61 @@ pub async fn try_rendered_file<S: AsRef<str>>(
     recv.recv().await?
 }
 
-pub fn serve_rendered_file<S: AsRef<str>>(html: S) -> Response {
-    Response::builder()
-        .content_type(mime::TEXT_HTML_UTF_8)
-        .body(html_with_live_reload_script(html.as_ref()))
+pub async fn try_not_found_page(broker: &EngineBroker) -> Result<Option<RenderedPage>> {
+    use crate::devserver::broker::{EngineMsg, EngineRequest};
+
+    trace!("try to serve 404 page");
+
+    let (send, recv) = EngineRequest::new(());
+    broker
+        .send_engine_msg(EngineMsg::RenderNotFound(send))
+        .await?;
+    recv.recv().await?
+}
+
+/// Serves the 404 page of the site with a 404 status. Falls back to a static `404.html`
+/// in the output directory, and then to the built-in page.
+pub async fn serve_not_found(
+    broker: &EngineBroker,
+    mount_point: &Data<&OutputRootDir>,
+) -> std::result::Result<Response, poem::error::Error> {
+    let mut response = match try_not_found_page(broker).await {
+        Ok(Some(page)) => serve_rendered_file("404.html", page.html()),
+        Ok(None) => match try_static_file("404.html", mount_point) {
+            Some(response) => response,
+            None => Response::builder()
+                .content_type(mime::TEXT_HTML_UTF_8)
+                .body(page_not_found()),
+        },
+        Err(e) => return Err(render_error(&e)),
+    };
+    response.set_status(StatusCode::NOT_FOUND);
+    Ok(response)
+}
+
+fn render_error(e: &eyre::Report) -> poem::error::Error {
+    let report = {
+        let msg = format!("{:?}", e);
+        let msg = ansi_to_html::convert_escaped(&msg)
+            .unwrap()
+            .replace('━', "=");
+        error_page_with_msg(format!("<pre>{}</pre>", &msg))
+    };
+    poem::error::Error::from_string(report, StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+pub fn serve_rendered_file<P: AsRef<str>, S: AsRef<str>>(path: P, rendered: S) -> Response {
+    // template outputs may be any type of file
+    match mime_guess::from_path(path.as_ref()).first() {
+        Some(mime_type) if mime_type.essence_str() != mime::TEXT_HTML.essence_str() => {
+            Response::builder()
+                .content_type(mime_type.essence_str())
+                .body(rendered.as_ref().to_owned())
+        }
+        _ => Response::builder()
+            .content_type(mime::TEXT_HTML_UTF_8)
+            .body(html_with_live_reload_script(rendered.as_ref())),
+    }
 }
 
 pub async fn run_pipelines<S: AsRef<str>>(broker: &EngineBroker, path: S) -> Result<()> {
@@ -172,20 +223,8 @@ pub async fn handle(
         .map_err(|e| poem::error::InternalServerError(AsStdError(e)))?;
 
     match try_rendered_file(*broker, &path).await {
-        Ok(Some(page)) => return Ok(serve_rendered_file(&page.html())),
-        Err(e) => {
-            let report = {
-                let msg = format!("{:?}", e);
-                let msg = ansi_to_html::convert_escaped(&msg)
-                    .unwrap()
-                    .replace('━', "=");
-                error_page_with_msg(format!("<pre>{}</pre>", &msg))
-            };
-            return Err(poem::error::Error::from_string(
-                report,
-                StatusCode::INTERNAL_SERVER_ERROR,
-            ));
-        }
+        Ok(Some(page)) => return Ok(serve_rendered_file(&path, &page.html())),
+        Err(e) => return Err(render_error(&e)),
         _ => (),
     }
 
@@ -195,9 +234,6 @@ pub async fn handle(
             .map_err(|e| poem::error::InternalServerError(AsStdError(e)))?;
         Ok(res)
     } else {
-        Ok(Response::builder()
-            .content_type(mime::TEXT_HTML_UTF_8)
-            .status(StatusCode::NOT_FOUND)
-            .body(page_not_found()))
+        serve_not_found(*broker, &mount_point).await
     }
 }
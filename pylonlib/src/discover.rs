@@ -1,4 +1,5 @@
 // pub mod css_asset;
+pub mod anchor;
 pub mod asset;
 pub mod html_asset;
 
@@ -0,0 +1,234 @@
+use std::collections::{HashMap, HashSet};
+
+use eyre::eyre;
+use serde::Serialize;
+
+use crate::discover::{self, UrlType};
+use crate::Result;
+
+/// Ids of the elements in each HTML file, keyed by the uri of the file.
+pub type PageAnchors = HashMap<String, HashSet<String>>;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct MissingAnchor {
+    /// Uri of the page containing the link.
+    pub page: String,
+    pub link: String,
+    pub reason: String,
+}
+
+/// Ids which can be linked to with a fragment. This includes the names of `<a>`
+/// elements, which browsers also scroll to.
+pub fn find_ids<S: AsRef<str>>(html: S) -> Result<HashSet<String>> {
+    use scraper::{Html, Selector};
+
+    let doc = Html::parse_document(html.as_ref());
+    let selector = Selector::parse("[id], a[name]").map_err(|e| eyre!("selector error: {e:?}"))?;
+
+    let mut ids = HashSet::new();
+    for el in doc.select(&selector) {
+        ids.extend(el.value().id().map(ToOwned::to_owned));
+        ids.extend(el.value().attr("name").map(ToOwned::to_owned));
+    }
+    Ok(ids)
+}
+
+/// Links on this site which have a fragment.
+pub fn find_fragment_links<S: AsRef<str>>(html: S) -> Result<Vec<String>> {
+    use scraper::{Html, Selector};
+
+    let doc = Html::parse_document(html.as_ref());
+    let selector = Selector::parse("a[href]").map_err(|e| eyre!("selector error: {e:?}"))?;
+
+    let mut links = vec![];
+    for el in doc.select(&selector) {
+        let Some(href) = el.value().attr("href") else {
+            continue;
+        };
+        if href.contains('#') && discover::get_url_type(href) != UrlType::Offsite {
+            links.push(href.to_owned());
+        }
+    }
+    Ok(links)
+}
+
+/// Checks that each link points to an id on the target page. `links` are keyed by
+/// the uri of the page containing them.
+pub fn verify(anchors: &PageAnchors, links: &HashMap<String, Vec<String>>) -> Vec<MissingAnchor> {
+    let mut missing = vec![];
+    for (page, links) in links {
+        for link in links {
+            let Some((target, fragment)) = resolve(page, link) else {
+                continue;
+            };
+            let reason = match find_page(anchors, &target) {
+                Some(ids) if ids.contains(&fragment) => continue,
+                Some(_) => format!("missing anchor '#{fragment}' on '{target}'"),
+                // links to other kinds of files can't be checked
+                None if !is_html(&target) => continue,
+                None => format!("page '{target}' not found"),
+            };
+            missing.push(MissingAnchor {
+                page: page.clone(),
+                link: link.clone(),
+                reason,
+            });
+        }
+    }
+    missing.sort_by(|a, b| (&a.page, &a.link).cmp(&(&b.page, &b.link)));
+    missing.dedup();
+    missing
+}
+
+/// The uri of the target page and the decoded fragment. Returns `None` for fragments
+/// which don't need an element, such as `#` and `#top`.
+fn resolve(page: &str, link: &str) -> Option<(String, String)> {
+    let (path, fragment) = link.split_once('#')?;
+    let fragment = percent_encoding::percent_decode_str(fragment)
+        .decode_utf8_lossy()
+        .into_owned();
+    if fragment.is_empty() || fragment.eq_ignore_ascii_case("top") {
+        return None;
+    }
+
+    let path = path.split('?').next().unwrap_or_default();
+    let target = if path.is_empty() {
+        page.to_owned()
+    } else if path.starts_with('/') {
+        path.to_owned()
+    } else {
+        let dir = &page[..=page.rfind('/').unwrap_or(0)];
+        normalize(&format!("{dir}{path}"))
+    };
+    Some((target, fragment))
+}
+
+/// Removes `.` and `..` segments.
+fn normalize(path: &str) -> String {
+    let mut segments: Vec<&str> = vec![];
+    let parts = path.split('/').collect::<Vec<_>>();
+    for (i, part) in parts.iter().enumerate() {
+        match *part {
+            "." => (),
+            ".." => {
+                segments.pop();
+            }
+            // keep the trailing slash of directories
+            "" if i + 1 < parts.len() => (),
+            part => segments.push(part),
+        }
+    }
+    format!("/{}", segments.join("/"))
+}
+
+/// Finds the page for the uri, trying the index page for directories.
+fn find_page<'a>(anchors: &'a PageAnchors, target: &str) -> Option<&'a HashSet<String>> {
+    if target.ends_with('/') {
+        return anchors.get(&format!("{target}index.html"));
+    }
+    anchors
+        .get(target)
+        .or_else(|| anchors.get(&format!("{target}/index.html")))
+        .or_else(|| anchors.get(&format!("{target}.html")))
+}
+
+fn is_html(target: &str) -> bool {
+    let extension = std::path::Path::new(target).extension();
+    target.ends_with('/')
+        || extension.is_none()
+        || extension.is_some_and(|ext| ext.eq_ignore_ascii_case("html"))
+}
+
+#[cfg(test)]
+mod test {
+
+    #![allow(warnings, unused)]
+
+    use super::*;
+
+    #[test]
+    fn finds_ids_and_names() {
+        let html = r#"<h1 id="title">t</h1><a name="legacy"></a><p id=plain></p>"#;
+        let ids = find_ids(html).unwrap();
+        assert_eq!(
+            ids,
+            HashSet::from(["title".to_owned(), "legacy".to_owned(), "plain".to_owned()])
+        );
+    }
+
+    #[test]
+    fn finds_fragment_links() {
+        let html = r##"
+            <a href="#local"></a>
+            <a href="/page.html#abs"></a>
+            <a href="other.html#rel"></a>
+            <a href="/page.html"></a>
+            <a href="https://example.com/#offsite"></a>
+        "##;
+        assert_eq!(
+            find_fragment_links(html).unwrap(),
+            vec!["#local", "/page.html#abs", "other.html#rel"]
+        );
+    }
+
+    #[test]
+    fn resolves_links() {
+        let resolved = |link| resolve("/blog/post.html", link);
+        assert_eq!(
+            resolved("#a"),
+            Some(("/blog/post.html".to_owned(), "a".to_owned()))
+        );
+        assert_eq!(
+            resolved("/about.html#a%20b"),
+            Some(("/about.html".to_owned(), "a b".to_owned()))
+        );
+        assert_eq!(
+            resolved("../index.html?q=1#a"),
+            Some(("/index.html".to_owned(), "a".to_owned()))
+        );
+        assert_eq!(
+            resolved("./2022/#a"),
+            Some(("/blog/2022/".to_owned(), "a".to_owned()))
+        );
+        assert_eq!(resolved("#"), None);
+        assert_eq!(resolved("#top"), None);
+    }
+
+    #[test]
+    fn verifies_anchors() {
+        let anchors = PageAnchors::from([
+            (
+                "/index.html".to_owned(),
+                HashSet::from(["intro".to_owned()]),
+            ),
+            ("/doc.html".to_owned(), HashSet::from(["usage".to_owned()])),
+        ]);
+        let links = HashMap::from([(
+            "/doc.html".to_owned(),
+            vec![
+                "#usage".to_owned(),
+                "#missing".to_owned(),
+                "/#intro".to_owned(),
+                "/gone.html#intro".to_owned(),
+                "/file.pdf#page=2".to_owned(),
+            ],
+        )]);
+
+        let missing = verify(&anchors, &links);
+        assert_eq!(
+            missing,
+            vec![
+                MissingAnchor {
+                    page: "/doc.html".to_owned(),
+                    link: "#missing".to_owned(),
+                    reason: "missing anchor '#missing' on '/doc.html'".to_owned(),
+                },
+                MissingAnchor {
+                    page: "/doc.html".to_owned(),
+                    link: "/gone.html#intro".to_owned(),
+                    reason: "page '/gone.html' not found".to_owned(),
+                },
+            ]
+        );
+    }
+}
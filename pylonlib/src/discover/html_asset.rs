@@ -162,6 +162,11 @@ impl HtmlAssets {
     pub fn remove(&mut self, target: &AbsPath) {
         self.inner.remove(target);
     }
+
+    /// All references to the target asset.
+    pub fn get(&self, target: &AbsPath) -> Option<&[HtmlAsset]> {
+        self.inner.get(target).map(Vec::as_slice)
+    }
 }
 
 impl Default for HtmlAssets {
@@ -220,6 +225,22 @@ impl<'a> FromIterator<&'a HtmlAsset> for HtmlAssets {
     }
 }
 
+/// Elements and attributes which link to other files.
+const LINK_ATTRIBUTES: &[(&str, &str)] = &[
+    ("a", "href"),
+    ("audio", "src"),
+    ("embed", "src"),
+    ("iframe", "src"),
+    ("img", "src"),
+    ("link", "href"),
+    ("object", "data"),
+    ("script", "src"),
+    ("source", "src"),
+    ("source", "srcset"),
+    ("track", "src"),
+    ("video", "src"),
+];
+
 #[allow(clippy::needless_pass_by_value)]
 pub fn find_all(engine_paths: GlobalEnginePaths, search_dir: &RelPath) -> Result<HtmlAssets> {
     let html_paths =
@@ -254,25 +275,10 @@ where
 {
     use scraper::{Html, Selector};
 
-    let selectors = [
-        ("a", "href"),
-        ("audio", "src"),
-        ("embed", "src"),
-        ("iframe", "src"),
-        ("img", "src"),
-        ("link", "href"),
-        ("object", "data"),
-        ("script", "src"),
-        ("source", "src"),
-        ("source", "srcset"),
-        ("track", "src"),
-        ("video", "src"),
-    ];
-
     let doc = Html::parse_document(html.as_ref());
 
     let mut assets = HtmlAssets::new();
-    for (tag, attr) in selectors {
+    for (tag, attr) in LINK_ATTRIBUTES.iter().copied() {
         let selector = Selector::parse(tag)
             .map_err(|e| eyre!("selector error: {:?}", e))
             .wrap_err_with(|| format!("Error parsing CSS selector '{}' (this is a bug)", tag))?;
@@ -290,9 +296,9 @@ where
                             HtmlAsset::new(&asset_path, tag, &UrlType::Absolute, html_path);
                         assets.insert(html_asset);
                     }
-                    // TODO: make sure the anchor exists in the page
+                    // checked after rendering, see `step::check_anchors`
                     UrlType::LocalAnchor(_) => (),
-                    // TODO: add this to the link checker once it exists
+                    // checked with `pylon check --external`, see `find_offsite`
                     UrlType::Offsite => (),
                     // relative links need to get converted to absolute links
                     UrlType::Relative(target) => {
@@ -305,9 +311,9 @@ where
                             HtmlAsset::new(&asset_path, tag, &UrlType::Relative(target), html_path);
                         assets.insert(html_asset);
                     }
-                    UrlType::InternalDoc(_) => panic!(
-                        "encountered internal doc link while discovering assets. this is a bug"
-                    ),
+                    // internal doc links to missing documents are left unchanged and
+                    // reported as broken links when rendering
+                    UrlType::InternalDoc(_) => (),
                 }
             }
         }
@@ -315,6 +321,37 @@ where
     Ok(assets)
 }
 
+/// Finds the HTTP and HTTPS links to other sites. Fragments are removed, since they
+/// aren't sent to the server.
+pub fn find_offsite<S: AsRef<str>>(html: S) -> Result<Vec<String>> {
+    use scraper::{Html, Selector};
+
+    let doc = Html::parse_document(html.as_ref());
+
+    let mut urls = vec![];
+    for (tag, attr) in LINK_ATTRIBUTES.iter().copied() {
+        let selector = Selector::parse(tag)
+            .map_err(|e| eyre!("selector error: {e:?}"))
+            .wrap_err_with(|| format!("Error parsing CSS selector '{tag}' (this is a bug)"))?;
+        for el in doc.select(&selector) {
+            let Some(url) = el.value().attr(attr) else {
+                continue;
+            };
+            if discover::get_url_type(url) != UrlType::Offsite {
+                continue;
+            }
+            let url = url.split('#').next().unwrap_or_default().trim();
+            let scheme = url.split(':').next().unwrap_or_default();
+            let is_http =
+                scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https");
+            if is_http && !urls.iter().any(|existing| existing == url) {
+                urls.push(url.to_owned());
+            }
+        }
+    }
+    Ok(urls)
+}
+
 fn canonicalized_uri_from_html_path<S: AsRef<str>>(
     html_path: &ConfirmedPath<pathmarker::HtmlFile>,
     relative_uri: S,
@@ -560,8 +597,7 @@ mod test {
     }
 
     #[test]
-    #[should_panic]
-    fn internal_doc_link_abort() {
+    fn skips_broken_internal_doc_link() {
         let tree = temptree! {
           "rules.rhai": "",
           templates: {},
@@ -576,7 +612,8 @@ mod test {
             .confirm(pathmarker::HtmlFile)
             .unwrap();
         let html = r#"<img src="@/whoops.md">"#;
-        super::find(paths, &html_path, html);
+        let assets = super::find(paths, &html_path, html).unwrap();
+        assert!(assets.is_empty());
     }
 
     #[test]
@@ -620,4 +657,21 @@ mod test {
         let relative_uri = "/test.txt";
         super::canonicalized_uri_from_html_path(&html_path, relative_uri);
     }
+
+    #[test]
+    fn finds_offsite_links() {
+        let html = r##"
+            <a href="https://example.com/a#section"></a>
+            <a href="https://example.com/a"></a>
+            <img src="http://example.com/img.png" />
+            <a href="mailto:someone@example.com"></a>
+            <a href="/local.html"></a>
+            <a href="#anchor"></a>
+        "##;
+        let urls = super::find_offsite(html).unwrap();
+        assert_eq!(
+            urls,
+            vec!["https://example.com/a", "http://example.com/img.png"]
+        );
+    }
 }
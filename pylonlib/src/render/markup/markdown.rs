@@ -1,5 +1,9 @@
 use crate::{
-    core::{page::RawMarkdown, Library, Page},
+    core::{
+        diagnostics::{Diagnostic, DiagnosticKind, Diagnostics},
+        page::RawMarkdown,
+        Library, Page,
+    },
     discover,
     render::highlight::SyntectHighlighter,
     Result,
@@ -15,6 +19,8 @@ impl MarkdownRenderer {
         Self
     }
 
+    /// Renders the Markdown. Internal links to missing documents are added to the
+    /// diagnostics and left as they are.
     #[allow(clippy::unused_self)]
     pub fn render(
         &self,
@@ -22,8 +28,9 @@ impl MarkdownRenderer {
         library: &Library,
         highlighter: &SyntectHighlighter,
         raw_markdown: &RawMarkdown,
+        diagnostics: &Diagnostics,
     ) -> Result<String> {
-        render(page, library, highlighter, raw_markdown)
+        render(page, library, highlighter, raw_markdown, diagnostics)
     }
 
     #[allow(clippy::unused_self)]
@@ -53,6 +60,7 @@ fn render(
     library: &Library,
     highlighter: &SyntectHighlighter,
     raw_markdown: &RawMarkdown,
+    diagnostics: &Diagnostics,
 ) -> Result<String> {
     use pulldown_cmark::{
         html, CodeBlockKind, CowStr, Event, HeadingLevel, LinkType, Options, Parser, Tag,
@@ -85,18 +93,27 @@ fn render(
                     match discover::get_url_type(&href) {
                         // internal doc links get converted into target Uri
                         UrlType::InternalDoc(ref target) => {
-                            // ignore any anchors on internal doc links
-                            let target = target.split('#').next().unwrap();
-                            let page = library.get(&target.into()).ok_or_else(|| {
-                                eyre!(
-                                    "unable to find internal link '{}' on page '{}'",
-                                    &target,
-                                    page.uri()
-                                )
-                            })?;
+                            // anchors are kept and verified once the site is built
+                            let (target, anchor) = match target.split_once('#') {
+                                Some((target, anchor)) => (target, Some(anchor)),
+                                None => (target.as_str(), None),
+                            };
+                            let Some(page) = library.get(&target.into()) else {
+                                diagnostics.push(Diagnostic::new(
+                                    DiagnosticKind::BrokenLink,
+                                    page.uri().to_string(),
+                                    href.to_string(),
+                                ));
+                                events.push(Event::Start(Tag::Link(LinkType::Inline, href, title)));
+                                continue;
+                            };
+                            let uri = match anchor {
+                                Some(anchor) => format!("{}#{anchor}", page.uri()),
+                                None => page.uri().to_string(),
+                            };
                             events.push(Event::Start(Tag::Link(
                                 LinkType::Inline,
-                                CowStr::Boxed(page.uri().into_boxed_str()),
+                                CowStr::Boxed(uri.into_boxed_str()),
                                 title,
                             )));
                         }
@@ -104,7 +121,7 @@ fn render(
                         UrlType::Absolute | UrlType::Offsite => {
                             events.push(Event::Start(Tag::Link(LinkType::Inline, href, title)));
                         }
-                        // TODO: check to see if the anchor is actually on the page
+                        // anchors are verified once the site is built
                         UrlType::LocalAnchor(_) => {
                             events.push(Event::Start(Tag::Link(LinkType::Inline, href, title)));
                         }
@@ -226,6 +243,7 @@ mod test {
 
     use crate::{
         core::{
+            diagnostics::{DiagnosticKind, Diagnostics},
             page::test_page::{new_page, new_page_with_tree},
             Library, Page,
         },
@@ -247,7 +265,13 @@ mod test {
             .get_with_key(key)
             .expect("page is missing from page store");
         let rendered_page = md_renderer
-            .render(&test_page, &store, &highlighter, test_page.raw_markdown())
+            .render(
+                &test_page,
+                &store,
+                &highlighter,
+                test_page.raw_markdown(),
+                &Diagnostics::new(),
+            )
             .expect("failed to render test page");
         rendered_page
     }
@@ -327,6 +351,39 @@ mod test {
         assert_eq!(href, "/doc.html");
     }
 
+    #[test]
+    fn records_broken_internal_doc_link() {
+        let page = new_page(
+            r#"+++
+            +++
+            [one](@/missing.md) [two](@/gone.md)"#,
+            "test.md",
+        )
+        .unwrap();
+
+        let mut store = Library::new();
+        let key = store.insert(page);
+        let page = store.get_with_key(key).unwrap();
+
+        let diagnostics = Diagnostics::new();
+        let highlighter = SyntectHighlighter::new().unwrap();
+        let rendered = super::render(
+            page,
+            &store,
+            &highlighter,
+            page.raw_markdown(),
+            &diagnostics,
+        )
+        .expect("broken links should not fail the render");
+
+        assert!(rendered.contains("@/missing.md"));
+        let found = diagnostics.to_vec();
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].kind, DiagnosticKind::BrokenLink);
+        assert_eq!(found[0].page, "/test.html");
+        assert_eq!(found[0].target, "@/gone.md");
+    }
+
     #[test]
     fn handles_code_fence_with_no_language_specified() {
         let page = new_page(
@@ -349,8 +406,14 @@ code sample here
 
         let highlighter = SyntectHighlighter::new().unwrap();
 
-        let rendered = super::render(page, &store, &highlighter, page.raw_markdown())
-            .expect("failed to render markdown");
+        let rendered = super::render(
+            page,
+            &store,
+            &highlighter,
+            page.raw_markdown(),
+            &Diagnostics::new(),
+        )
+        .expect("failed to render markdown");
         assert_eq!(rendered, "<pre><code>code sample here</code></pre>");
     }
 
@@ -374,8 +437,14 @@ code sample here
 
         let highlighter = SyntectHighlighter::new().unwrap();
 
-        let rendered = super::render(page, &store, &highlighter, page.raw_markdown())
-            .expect("failed to render markdown");
+        let rendered = super::render(
+            page,
+            &store,
+            &highlighter,
+            page.raw_markdown(),
+            &Diagnostics::new(),
+        )
+        .expect("failed to render markdown");
 
         assert_eq!(rendered, "<p>inline <code>let x = 1;</code> code</p>\n");
     }
@@ -402,8 +471,14 @@ let x = 1;
 
         let highlighter = SyntectHighlighter::new().unwrap();
 
-        let rendered = super::render(page, &store, &highlighter, page.raw_markdown())
-            .expect("failed to render markdown");
+        let rendered = super::render(
+            page,
+            &store,
+            &highlighter,
+            page.raw_markdown(),
+            &Diagnostics::new(),
+        )
+        .expect("failed to render markdown");
         let expected = include_str!("test_data/handles_code_fence_with_language_specified.html")
             .replace("syn-", THEME_CLASS_PREFIX);
         assert_eq!(rendered, expected);
@@ -433,8 +508,14 @@ let x = 1;
 
         let highlighter = SyntectHighlighter::new().unwrap();
 
-        let rendered = super::render(page, &store, &highlighter, page.raw_markdown())
-            .expect("failed to render markdown");
+        let rendered = super::render(
+            page,
+            &store,
+            &highlighter,
+            page.raw_markdown(),
+            &Diagnostics::new(),
+        )
+        .expect("failed to render markdown");
         assert_eq!(
             rendered,
             include_str!("test_data/adds_ids_to_headings_for_toc_anchors.html")
@@ -465,8 +546,14 @@ let x = 1;
 
         let highlighter = SyntectHighlighter::new().unwrap();
 
-        let rendered = super::render(page, &store, &highlighter, page.raw_markdown())
-            .expect("failed to render markdown");
+        let rendered = super::render(
+            page,
+            &store,
+            &highlighter,
+            page.raw_markdown(),
+            &Diagnostics::new(),
+        )
+        .expect("failed to render markdown");
         assert_eq!(rendered, include_str!("test_data/dashifies_headers.html"));
     }
 
@@ -513,8 +600,14 @@ sample[^1]
 
         let highlighter = SyntectHighlighter::new().unwrap();
 
-        let rendered = super::render(page, &store, &highlighter, page.raw_markdown())
-            .expect("failed to render markdown");
+        let rendered = super::render(
+            page,
+            &store,
+            &highlighter,
+            page.raw_markdown(),
+            &Diagnostics::new(),
+        )
+        .expect("failed to render markdown");
 
         assert_eq!(
             rendered,
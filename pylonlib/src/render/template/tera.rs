@@ -1,5 +1,5 @@
 use eyre::WrapErr;
-use parking_lot::Mutex;
+use parking_lot::RwLock;
 
 use std::sync::Arc;
 use tera::Tera;
@@ -12,9 +12,11 @@ use super::TemplateName;
 
 mod functions;
 
+/// Templates are rendered from several threads at once, so the lock is only written
+/// when templates are added or reloaded.
 #[derive(Debug)]
 pub struct TeraRenderer {
-    renderer: Arc<Mutex<Tera>>,
+    renderer: Arc<RwLock<Tera>>,
 }
 
 impl TeraRenderer {
@@ -29,23 +31,25 @@ impl TeraRenderer {
         register_builtin_functions(engine_paths, &mut tera);
 
         Ok(Self {
-            renderer: Arc::new(Mutex::new(tera)),
+            renderer: Arc::new(RwLock::new(tera)),
         })
     }
 
     pub fn render(&self, template: &TemplateName, context: &tera::Context) -> Result<String> {
-        let renderer = self.renderer.lock();
+        let renderer = self.renderer.read();
         Ok(renderer.render(template.as_ref(), context)?)
     }
 
+    /// Renders a template which isn't in the template directory. This needs exclusive
+    /// access, because Tera adds the template before rendering it.
     pub fn one_off<S: AsRef<str>>(&self, input: S, context: &tera::Context) -> Result<String> {
-        let mut renderer = self.renderer.lock();
+        let mut renderer = self.renderer.write();
         Ok(renderer.render_str(input.as_ref(), context)?)
     }
 
     #[allow(clippy::redundant_closure_for_method_calls)]
     pub fn get_template_names(&self) -> Vec<String> {
-        let renderer = self.renderer.lock();
+        let renderer = self.renderer.read();
         renderer
             .get_template_names()
             .map(|s| s.to_string())
@@ -53,7 +57,7 @@ impl TeraRenderer {
     }
 
     pub fn reload(&mut self) -> Result<()> {
-        let mut renderer = self.renderer.lock();
+        let mut renderer = self.renderer.write();
         Ok(renderer.full_reload()?)
     }
 }
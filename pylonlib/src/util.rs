@@ -54,6 +54,15 @@ pub fn based_uri_from_sys_path<S: Into<String>>(path: &SysPath, uri: S) -> Resul
     Ok(AssetUri::new(&checked_html, &uri))
 }
 
+/// Escapes text for use in XML documents.
+pub fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('\'', "&apos;")
+        .replace('"', "&quot;")
+}
+
 #[derive(Debug)]
 pub struct GlobCandidate<'a>(globset::Candidate<'a>);
 
@@ -110,6 +119,14 @@ mod test {
     use crate::test::{abs, rel};
     use temptree::temptree;
 
+    #[test]
+    fn escapes_xml() {
+        assert_eq!(
+            escape_xml("/a&b.html?q=\"<x>'"),
+            "/a&amp;b.html?q=&quot;&lt;x&gt;&apos;"
+        );
+    }
+
     #[test]
     fn glob_try_into_str() {
         let glob = PylonGlob::try_from("/*.*");
@@ -1,7 +1,9 @@
 use clap::Parser;
 use color_eyre::Section;
 use eyre::{eyre, WrapErr};
-use pylonlib::core::engine::{Engine, EnginePaths};
+use pylonlib::core::build_report::{BuildReport, ReportFormat};
+use pylonlib::core::engine::{BuildOptions, Engine, EnginePaths, PipelineBehavior};
+use pylonlib::core::export::{ExportField, ExportFormat, ExportOptions};
 use pylonlib::devserver::broker::RenderBehavior;
 use pylonlib::render::highlight::SyntectHighlighter;
 use pylonlib::{AbsPath, RelPath, USER_LOG};
@@ -59,6 +61,10 @@ struct Args {
 enum SubCommand {
     /// Build site
     Build(CmdBuild),
+    /// Build site and check links
+    Check(CmdCheck),
+    /// Empty the output directory
+    Clean,
     /// Initialize a new site
     Init(CmdInit),
     /// Run dev server
@@ -72,6 +78,86 @@ struct CmdBuild {
     /// Export frontmatter to provided directory
     #[clap(long)]
     frontmatter: Option<PathBuf>,
+
+    /// Export all documents to a single file
+    #[clap(long)]
+    export: Option<PathBuf>,
+
+    /// Format of the export file: json, ndjson, or csv
+    #[clap(long, default_value = "json", value_parser = parse_export_format, requires = "export")]
+    export_format: ExportFormat,
+
+    /// Data to export along with the frontmatter: uri, path, html, text, toc
+    #[clap(long, value_delimiter = ',', value_parser = parse_export_field, requires = "export")]
+    export_fields: Vec<ExportField>,
+
+    /// Write a report of the build steps, lints, and problems to this file
+    #[clap(long)]
+    report_file: Option<PathBuf>,
+
+    /// Format of the report file: json, sarif, or junit
+    #[clap(long, default_value = "json", value_parser = parse_report_format, requires = "report-file")]
+    report_format: ReportFormat,
+
+    /// Write a JSON breakdown of the time spent in each step, pipeline, and template
+    #[clap(long)]
+    timings: Option<PathBuf>,
+
+    /// Run every pipeline, even when its inputs haven't changed
+    #[clap(long)]
+    force_pipelines: bool,
+
+    /// Empty the output directory before building
+    #[clap(long)]
+    clean: bool,
+
+    /// Delete files in the output directory which weren't produced by the build
+    #[clap(long)]
+    prune: bool,
+
+    /// Build into a temporary directory which replaces the output directory only
+    /// when the build succeeds
+    #[clap(long)]
+    atomic: bool,
+
+    /// Maximum number of pipelines to run at the same time [default: number of CPUs]
+    #[clap(short, long)]
+    jobs: Option<usize>,
+}
+
+#[derive(Debug, clap::Args)]
+struct CmdCheck {
+    /// Check links to other sites
+    #[clap(long)]
+    external: bool,
+
+    /// Maximum number of simultaneous requests
+    #[clap(long, default_value = "8", requires = "external")]
+    concurrency: usize,
+
+    /// Time limit for each request (in seconds)
+    #[clap(long, default_value = "10", requires = "external")]
+    timeout: u64,
+
+    /// How long to cache reachable links (in seconds)
+    #[clap(long, default_value = "86400", requires = "external")]
+    cache_ttl: u64,
+
+    /// Send requests to this URL instead of the linked site, keeping the path
+    #[clap(long, requires = "external")]
+    external_base: Option<String>,
+}
+
+fn parse_export_format(s: &str) -> std::result::Result<ExportFormat, String> {
+    s.parse().map_err(|e: eyre::Report| e.to_string())
+}
+
+fn parse_export_field(s: &str) -> std::result::Result<ExportField, String> {
+    s.parse().map_err(|e: eyre::Report| e.to_string())
+}
+
+fn parse_report_format(s: &str) -> std::result::Result<ReportFormat, String> {
+    s.parse().map_err(|e: eyre::Report| e.to_string())
 }
 
 #[derive(Debug, clap::Args)]
@@ -160,12 +246,76 @@ fn main() -> Result<()> {
 
                 export_frontmatter(&engine, pages, &target_dir)
                     .wrap_err("Failed to export frontmatter")?;
+            } else if let Some(path) = &options.export {
+                use pylonlib::core::engine::step::export_documents;
+
+                let engine =
+                    Engine::new(Arc::new(paths)).wrap_err("Failed to create new engine")?;
+
+                let target = AbsPath::new(std::env::current_dir()?.join(path))?;
+                let pages = engine.library().iter().map(|(_, page)| page);
+                let export_options =
+                    ExportOptions::new(options.export_format, options.export_fields.clone());
+
+                export_documents(&engine, pages, &target, &export_options)
+                    .wrap_err("Failed to export documents")?;
             } else {
                 let engine =
                     Engine::new(Arc::new(paths)).wrap_err("Failed to create new engine")?;
-                engine.build_site().wrap_err("Failed to build site")?;
+                let build_options = BuildOptions {
+                    pipeline_behavior: if options.force_pipelines {
+                        PipelineBehavior::Overwrite
+                    } else {
+                        PipelineBehavior::NoOverwrite
+                    },
+                    clean: options.clean,
+                    prune: options.prune,
+                    atomic: options.atomic,
+                    jobs: options.jobs,
+                };
+                let report = BuildReport::new();
+                let result = engine.build_site_with_options(&build_options, &report);
+                if let Some(path) = &options.report_file {
+                    report
+                        .write(options.report_format, path)
+                        .wrap_err("Failed to write build report")?;
+                }
+                if let Some(path) = &options.timings {
+                    report
+                        .write_timings(path)
+                        .wrap_err("Failed to write build timings")?;
+                }
+                result.wrap_err("Failed to build site")?;
+            }
+        }
+        SubCommand::Check(options) => {
+            use pylonlib::core::engine::step::check_offsite_links;
+            use pylonlib::core::link_check::ExternalCheckOptions;
+            use std::time::Duration;
+
+            let paths = engine_paths(&args)?;
+            let engine = Engine::new(Arc::new(paths)).wrap_err("Failed to create new engine")?;
+            engine.build_site().wrap_err("Failed to build site")?;
+
+            if options.external {
+                let check_options = ExternalCheckOptions {
+                    concurrency: options.concurrency,
+                    timeout: Duration::from_secs(options.timeout),
+                    cache_ttl: Duration::from_secs(options.cache_ttl),
+                    base_override: options.external_base.clone(),
+                };
+                check_offsite_links(&engine, &check_options)
+                    .wrap_err("Failed to check offsite links")?;
             }
         }
+        SubCommand::Clean => {
+            use pylonlib::core::engine::step::clean_output_dir;
+
+            let paths = engine_paths(&args)?;
+            let engine = Engine::new(Arc::new(paths)).wrap_err("Failed to create new engine")?;
+            let removed = clean_output_dir(&engine).wrap_err("Failed to clean output directory")?;
+            info!(target: USER_LOG, "removed {removed} files");
+        }
         SubCommand::Init(options) => {
             use pylonlib::init;
             let cwd = std::env::current_dir()?;
@@ -113,3 +113,44 @@ fn sample() {
 
     assert_ok_body(body, "<p>sample</p>\n");
 }
+
+#[test]
+#[serial]
+fn serves_not_found_page() {
+    setup();
+    let not_found_md = r#"+++
+    published = true
+    +++
+    not here"#;
+    let default_template = r#"{{ content | safe }}"#;
+
+    let tree = temptree! {
+        "rules.rhai": "",
+        src: {
+            "404.md": not_found_md,
+        },
+        templates: {
+            "default.tera": default_template,
+        },
+        target: {},
+        syntax_themes: {}
+    };
+
+    let engine_paths = engine_paths(&tree);
+
+    let addr = SocketAddrV4::from_str("127.0.0.1:38384").unwrap();
+    let addr: SocketAddr = addr.into();
+
+    let (_handle, _broker) =
+        Engine::with_broker(engine_paths, addr, 0, RenderBehavior::Write).unwrap();
+
+    std::thread::sleep(std::time::Duration::from_millis(500));
+
+    let response = reqwest::blocking::get("http://127.0.0.1:38384/missing.html").unwrap();
+
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+    let body = response.text().unwrap();
+
+    assert_ok_body(body, "<p>not here</p>\n");
+}
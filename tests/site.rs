@@ -1,4 +1,9 @@
-use pylonlib::core::engine::{step, Engine, EnginePaths, GlobalEnginePaths};
+use pylonlib::core::build_outputs::BuildOutputs;
+use pylonlib::core::build_report::{BuildReport, ReportFormat, ReportLevel};
+use pylonlib::core::diagnostics::Diagnostics;
+use pylonlib::core::engine::{
+    step, BuildMode, BuildOptions, Engine, EnginePaths, GlobalEnginePaths, PipelineBehavior,
+};
 use std::path::Path;
 use std::sync::Arc;
 use tempfile::TempDir;
@@ -251,6 +256,8 @@ sample two"#;
             "use_breadcrumbs": false,
             "published": false,
             "searchable": true,
+            "sitemap": true,
+            "noindex": false,
             "meta": {}
         })
         .to_string();
@@ -574,7 +581,8 @@ fn doesnt_reprocess_existing_assets() {
     // located before running the pipeline.
     {
         let pages = engine.library().iter().map(|(_, page)| page);
-        step::render(&engine, pages).expect("failed to render");
+        step::render(&engine, pages, &Diagnostics::new(), &BuildReport::new())
+            .expect("failed to render");
 
         step::mount_directories(engine.rules().mounts()).expect("failed to process mounts");
 
@@ -582,8 +590,15 @@ fn doesnt_reprocess_existing_assets() {
             pylonlib::discover::html_asset::find_all(engine.paths(), engine.paths().output_dir())
                 .expect("failed to discover html assets");
 
-        let unhandled_assets =
-            step::run_pipelines(&engine, &html_assets).expect("failed to run pipelines");
+        let unhandled_assets = step::run_pipelines(
+            &engine,
+            &html_assets,
+            None,
+            &BuildOutputs::new(engine.paths().abs_output_dir()),
+            &Diagnostics::new(),
+            &BuildReport::new(),
+        )
+        .expect("failed to run pipelines");
 
         assert!(unhandled_assets.is_empty());
     }
@@ -681,12 +696,88 @@ doc2"#;
 
     let engine = Engine::new(paths).unwrap();
 
-    let rendered = step::render(&engine, engine.library().iter().map(|(_, page)| page))
-        .expect("failed to render pages");
+    let rendered = step::render(
+        &engine,
+        engine.library().iter().map(|(_, page)| page),
+        &Diagnostics::new(),
+        &BuildReport::new(),
+    )
+    .expect("failed to render pages");
 
     assert_eq!(rendered.iter().count(), 2);
 }
 
+#[test]
+fn renders_in_parallel_with_deterministic_results() {
+    setup();
+    let tree = temptree! {
+      "rules.rhai": r#"
+            rules.add_lint(WARN, "Missing author", "**", |doc| {
+                type_of(doc.meta("author")) == "()"
+            });
+        "#,
+      templates: {
+          "test.tera": "content: {{content}}",
+          "broken.tera": "{{ missing_variable }}"
+      },
+      target: {},
+      src: {},
+      syntax_themes: {}
+    };
+    for i in 0..32 {
+        let template = if i % 10 == 5 {
+            "broken.tera"
+        } else {
+            "test.tera"
+        };
+        let doc = format!("+++\ntemplate_name = \"{template}\"\npublished = true\n+++\ndoc {i}");
+        std::fs::write(tree.path().join(format!("src/doc{i:02}.md")), doc).unwrap();
+    }
+
+    let engine = Engine::new(engine_paths(&tree)).unwrap();
+    let pages = || engine.library().iter().map(|(_, page)| page);
+
+    let lints = step::run_lints(&engine, pages()).unwrap();
+    let linted = lints
+        .into_iter()
+        .map(|lint| lint.md_file.as_sys_path().to_relative_path().to_string())
+        .collect::<Vec<_>>();
+    let expected = pages()
+        .map(|page| page.path().as_sys_path().to_relative_path().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(linted, expected);
+
+    let first_error = format!(
+        "{:#}",
+        step::render(&engine, pages(), &Diagnostics::new(), &BuildReport::new()).unwrap_err()
+    );
+    for _ in 0..4 {
+        let error = format!(
+            "{:#}",
+            step::render(&engine, pages(), &Diagnostics::new(), &BuildReport::new()).unwrap_err()
+        );
+        assert_eq!(error, first_error);
+    }
+
+    // rendered pages are in the same order as the pages given
+    let working = pages()
+        .filter(|page| page.template_name().as_str() == "test.tera")
+        .collect::<Vec<_>>();
+    let rendered = step::render(
+        &engine,
+        working.iter().copied(),
+        &Diagnostics::new(),
+        &BuildReport::new(),
+    )
+    .unwrap();
+    let rendered_keys = rendered
+        .iter()
+        .map(|page| page.page_key())
+        .collect::<Vec<_>>();
+    let expected = working.iter().map(|page| page.page_key).collect::<Vec<_>>();
+    assert_eq!(rendered_keys, expected);
+}
+
 #[test]
 fn skips_unpublished_docs() {
     setup();
@@ -899,29 +990,41 @@ fn internal_doc_link_anchor_renders_without_errors() {
 
     let engine = Engine::new(paths).unwrap();
 
-    let rendered = step::render(&engine, engine.library().iter().map(|(_, page)| page))
-        .expect("failed to render pages");
+    let rendered = step::render(
+        &engine,
+        engine.library().iter().map(|(_, page)| page),
+        &Diagnostics::new(),
+        &BuildReport::new(),
+    )
+    .expect("failed to render pages");
 
     assert_eq!(rendered.iter().count(), 2);
 }
 
 #[test]
-fn offsite_anchors_work() {
+fn internal_doc_link_keeps_anchor() {
     setup();
     let doc1 = r#"+++
             template_name = "test.tera"
             published = true
             +++
-[test](https://example.com/#anchor)"#;
+# anchor 1"#;
+
+    let doc2 = r#"+++
+            template_name = "test.tera"
+            published = true
+            +++
+[doc1](@/doc1.md#anchor-1)"#;
 
     let tree = temptree! {
-      "rules.rhai": "",
+      "rules.rhai": r#"rules.set_anchor_check("DENY");"#,
       templates: {
           "test.tera": "content: {{content}}"
       },
       target: {},
       src: {
           "doc1.md": doc1,
+          "doc2.md": doc2,
       },
       syntax_themes: {}
     };
@@ -929,39 +1032,2078 @@ fn offsite_anchors_work() {
     let paths = engine_paths(&tree);
 
     let engine = Engine::new(paths).unwrap();
+    engine.build_site().unwrap();
+
+    assert_content(
+        tree.path().join("target/doc2.html"),
+        "content: <p><a href=/doc1.html#anchor-1>doc1</a></p>",
+    );
+}
+
+#[test]
+fn denies_missing_anchors() {
+    setup();
+    let doc1 = r#"+++
+            template_name = "test.tera"
+            published = true
+            +++
+# anchor 1"#;
+
+    let doc2 = r#"+++
+            template_name = "test.tera"
+            published = true
+            +++
+[doc1](@/doc1.md#anchor-2)"#;
+
+    let tree = temptree! {
+      "rules.rhai": r#"rules.set_anchor_check("DENY");"#,
+      templates: {
+          "test.tera": "content: {{content}}"
+      },
+      target: {},
+      src: {
+          "doc1.md": doc1,
+          "doc2.md": doc2,
+      },
+      syntax_themes: {}
+    };
+
+    let paths = engine_paths(&tree);
 
-    step::render(&engine, engine.library().iter().map(|(_, page)| page))
-        .expect("failed to render pages");
+    let engine = Engine::new(paths).unwrap();
+    let err = engine.build_site().unwrap_err();
+    assert!(format!("{err:?}").contains("1 links to missing anchors found"));
 }
 
 #[test]
-fn builds_site_index() {
+fn reports_all_broken_links_and_assets_together() {
     setup();
-    let sample_md = r#"+++
+    let doc1 = r#"+++
+            template_name = "test.tera"
+            published = true
+            +++
+[gone](@/gone.md)
+
+![logo](/logo.png)"#;
+
+    let doc2 = r#"+++
+            template_name = "test.tera"
+            published = true
+            +++
+[gone](@/gone.md) [doc1](@/doc1.md)
+
+<link href="/style.css">"#;
+
+    let tree = temptree! {
+      "rules.rhai": r#"rules.add_pipeline("", "**/*.css", ["false"]);"#,
+      templates: {
+          "test.tera": "content: {{content}}"
+      },
+      target: {},
+      src: {
+          "doc1.md": doc1,
+          "doc2.md": doc2,
+      },
+      syntax_themes: {}
+    };
+
+    let paths = engine_paths(&tree);
+
+    let engine = Engine::new(paths).unwrap();
+    let err = engine.build_site().unwrap_err();
+    // broken links on both pages, the missing logo, and the failed stylesheet pipeline
+    assert!(format!("{err:?}").contains("4 problems found during site build"));
+
+    // rendering continues past the broken links
+    let doc2 = std::fs::read_to_string(tree.path().join("target/doc2.html")).unwrap();
+    assert!(doc2.contains(r#"href="/doc1.html""#));
+}
+
+#[test]
+fn reports_lints_and_failed_step() {
+    setup();
+    let rules = r#"
+            rules.add_lint(WARN, "Missing author", "**", |doc| {
+                doc.meta("author") == "" || type_of(doc.meta("author")) == "()"
+            });
+        "#;
+
+    let doc1 = r#"+++
+            template_name = "test.tera"
+            published = true
+            +++
+        "#;
+
+    let doc2 = r#"+++
+            template_name = "broken.tera"
+            published = true
+            [meta]
+            author = "test"
+            +++
+        "#;
+
+    let tree = temptree! {
+      "rules.rhai": rules,
+      templates: {
+          "test.tera": "content: {{content}}",
+          "broken.tera": "{{ missing_variable }}"
+      },
+      target: {},
+      src: {
+          "doc1.md": doc1,
+          "doc2.md": doc2,
+      },
+      syntax_themes: {}
+    };
+
+    let paths = engine_paths(&tree);
+
+    let engine = Engine::new(paths).unwrap();
+    let report = BuildReport::new();
+    assert!(engine.build_site_with_report(&report).is_err());
+    assert!(!report.is_success());
+
+    let steps = report.steps();
+    assert_eq!(steps[0].name, "lints");
+    assert_eq!(steps[0].error, None);
+    assert_eq!(steps.last().unwrap().name, "render");
+
+    let problems = report.problems();
+    assert_eq!(problems.len(), 2);
+    assert_eq!(problems[0].rule, "lint");
+    assert_eq!(problems[0].level, ReportLevel::Warning);
+    assert_eq!(problems[0].file.as_deref(), Some("src/doc1.md"));
+    assert_eq!(problems[1].rule, "failed_step");
+    assert_eq!(problems[1].file.as_deref(), Some("src/doc2.md"));
+
+    let sarif: serde_json::Value =
+        serde_json::from_str(&report.render(ReportFormat::Sarif).unwrap()).unwrap();
+    assert_eq!(sarif["runs"][0]["results"][1]["level"], "error");
+}
+
+#[test]
+fn records_build_stats() {
+    setup();
+    let doc = r#"+++
     published = true
-    +++
-    sample"#;
-    let default_template = r#"{{ content | safe }}"#;
+    +++"#;
+
+    let rules = r#"rules.add_pipeline("", "**/*.png", [OP_COPY]);"#;
+
+    let tree = temptree! {
+        "rules.rhai": rules,
+        src: {
+            "a.md": doc,
+            "b.md": doc,
+            "logo.png": "png",
+        },
+        templates: {
+            "default.tera": r#"<html>  <body>  <img src="logo.png">  </body>  </html>"#,
+        },
+        target: {},
+        syntax_themes: {}
+    };
+
+    let engine = Engine::new(engine_paths(&tree)).unwrap();
+    let report = BuildReport::new();
+    engine.build_site_with_report(&report).unwrap();
+
+    let stats = report.stats();
+    assert_eq!(stats.pages_rendered, 2);
+    assert_eq!(stats.assets_piped, 1);
+    assert_eq!(stats.files_minified, 2);
+    assert!(stats.bytes_saved > 0);
+    assert_eq!(stats.pipelines["**/*.png"].count, 1);
+    assert_eq!(stats.templates["default.tera"].count, 2);
+    assert!(report.steps().iter().all(|step| step.error.is_none()));
+}
+
+#[test]
+fn renders_only_changed_pages() {
+    setup();
+    let doc = |body: &str| format!("+++\npublished = true\n+++\n{body}");
+
+    let tree = temptree! {
+        "rules.rhai": "",
+        src: {},
+        templates: {
+            "default.tera": "{{ content | safe }}",
+        },
+        target: {},
+        syntax_themes: {}
+    };
+    for name in ["a", "b", "c"] {
+        std::fs::write(tree.path().join(format!("src/{name}.md")), doc(name)).unwrap();
+    }
+    let build = || {
+        let engine = Engine::new(engine_paths(&tree)).unwrap();
+        let report = BuildReport::new();
+        engine.build_site_with_report(&report).unwrap();
+        report.stats()
+    };
+
+    let stats = build();
+    assert_eq!((stats.pages_rendered, stats.pages_unchanged), (3, 0));
+    assert!(tree.path().join(".pylon/build_manifest.json").exists());
+
+    let stats = build();
+    assert_eq!((stats.pages_rendered, stats.pages_unchanged), (0, 3));
+
+    // changed source
+    std::fs::write(tree.path().join("src/a.md"), doc("changed")).unwrap();
+    let stats = build();
+    assert_eq!((stats.pages_rendered, stats.pages_unchanged), (1, 2));
+    assert_content(tree.path().join("target/a.html"), "<p>changed</p>");
+
+    // missing output
+    std::fs::remove_file(tree.path().join("target/b.html")).unwrap();
+    let stats = build();
+    assert_eq!((stats.pages_rendered, stats.pages_unchanged), (1, 2));
+    assert_exists(tree.path().join("target/b.html"));
+
+    // changed template
+    std::fs::write(
+        tree.path().join("templates/default.tera"),
+        "<div>{{ content | safe }}</div>",
+    )
+    .unwrap();
+    let stats = build();
+    assert_eq!((stats.pages_rendered, stats.pages_unchanged), (3, 0));
+
+    // removed source, which also changes the library context of the other pages
+    std::fs::remove_file(tree.path().join("src/c.md")).unwrap();
+    let stats = build();
+    assert_eq!((stats.pages_rendered, stats.pages_unchanged), (2, 0));
+    assert_eq!(stats.outputs_removed, 1);
+    assert!(!tree.path().join("target/c.html").exists());
+}
 
+#[test]
+fn renders_every_page_when_frontmatter_changes() {
+    setup();
     let tree = temptree! {
         "rules.rhai": "",
         src: {
-            "index.md": sample_md,
-            inner: {
-                "index.md": sample_md,
-            }
+            "a.md": "+++\npublished = true\n+++\na",
+            "b.md": "+++\npublished = true\n+++\nb",
         },
         templates: {
-            "default.tera": default_template,
+            "default.tera": "{{ content | safe }}",
         },
         target: {},
         syntax_themes: {}
     };
+    let build = || {
+        let engine = Engine::new(engine_paths(&tree)).unwrap();
+        let report = BuildReport::new();
+        engine.build_site_with_report(&report).unwrap();
+        report.stats()
+    };
+    build();
+
+    // the frontmatter of every page is in the library context of every template
+    std::fs::write(
+        tree.path().join("src/a.md"),
+        "+++\npublished = true\n[meta]\ntitle = \"A\"\n+++\na",
+    )
+    .unwrap();
+    let stats = build();
+    assert_eq!((stats.pages_rendered, stats.pages_unchanged), (2, 0));
+}
 
-    let engine_paths = engine_paths(&tree);
-    let engine = Engine::new(engine_paths).unwrap();
-    engine.build_site().unwrap();
+#[test]
+fn ignores_invalid_uris_in_build_manifest() {
+    setup();
+    let tree = temptree! {
+        "rules.rhai": "",
+        "x": "outside of the output directory",
+        src: {
+            "a.md": "+++\npublished = true\n+++\na",
+        },
+        templates: {
+            "default.tera": "{{ content | safe }}",
+        },
+        target: {},
+        syntax_themes: {}
+    };
+    let build = || {
+        let engine = Engine::new(engine_paths(&tree)).unwrap();
+        let report = BuildReport::new();
+        engine.build_site_with_report(&report).unwrap();
+        report.stats()
+    };
+    build();
+
+    let manifest_path = tree.path().join(".pylon/build_manifest.json");
+    let manifest = std::fs::read_to_string(&manifest_path).unwrap();
+    let mut manifest: serde_json::Value = serde_json::from_str(&manifest).unwrap();
+    let inputs = manifest["outputs"]["/a.html"].clone();
+    for uri in ["", "é.html", "/../x"] {
+        manifest["outputs"][uri] = inputs.clone();
+    }
+    std::fs::write(&manifest_path, manifest.to_string()).unwrap();
 
-    assert_content(tree.path().join("target/index.html"), "<p>sample</p>");
-    assert_content(tree.path().join("target/inner/index.html"), "<p>sample</p>");
+    let stats = build();
+    assert_eq!(stats.outputs_removed, 0);
+    assert_exists(tree.path().join("x"));
+    assert_exists(tree.path().join("target/a.html"));
+}
+
+#[test]
+fn reruns_pipelines_when_inputs_change() {
+    setup();
+    let sample_md = r#"+++
+    published = true
+    +++"#;
+    let rules = r#"
+rules.add_pipeline("/web", "/style.css", ["cat main.scss > $TARGET"], ["*.scss"]);
+rules.add_pipeline("/web", "/script.js", ["cat main.js > $TARGET"]);
+    "#;
+
+    let tree = temptree! {
+        "rules.rhai": rules,
+        src: {
+            "sample.md": sample_md,
+        },
+        templates: {
+            "default.tera": r#"<link href="/style.css"><script src="/script.js"></script>"#,
+        },
+        web: {
+            "main.scss": "one",
+            "main.js": "one",
+        },
+        target: {},
+        syntax_themes: {}
+    };
+    let build = |behavior| {
+        let engine = Engine::new(engine_paths(&tree)).unwrap();
+        let report = BuildReport::new();
+        let options = BuildOptions {
+            pipeline_behavior: behavior,
+            ..BuildOptions::default()
+        };
+        engine.build_site_with_options(&options, &report).unwrap();
+        report.stats()
+    };
+
+    let stats = build(PipelineBehavior::NoOverwrite);
+    assert_eq!((stats.assets_piped, stats.assets_unchanged), (2, 0));
+    assert_content(tree.path().join("target/style.css"), "one");
+
+    let stats = build(PipelineBehavior::NoOverwrite);
+    assert_eq!((stats.assets_piped, stats.assets_unchanged), (0, 2));
+
+    // declared input
+    std::fs::write(tree.path().join("web/main.scss"), "two").unwrap();
+    let stats = build(PipelineBehavior::NoOverwrite);
+    assert_eq!((stats.assets_piped, stats.assets_unchanged), (1, 1));
+    assert_content(tree.path().join("target/style.css"), "two");
+
+    // undeclared inputs aren't tracked
+    std::fs::write(tree.path().join("web/main.js"), "two").unwrap();
+    let stats = build(PipelineBehavior::NoOverwrite);
+    assert_eq!((stats.assets_piped, stats.assets_unchanged), (0, 2));
+
+    let stats = build(PipelineBehavior::Overwrite);
+    assert_eq!((stats.assets_piped, stats.assets_unchanged), (2, 0));
+    assert_content(tree.path().join("target/script.js"), "two");
+}
+
+#[test]
+fn tracks_extra_pipeline_outputs() {
+    setup();
+    let sample_md = r#"+++
+    published = true
+    +++"#;
+    let rules = r#"
+rules.add_pipeline(
+    "/web",
+    "/img/*.png",
+    ["cp $SOURCE $TARGET", "cp $SOURCE $OUTPUT(webp)"],
+    #{ webp: "$TARGET_STEM.webp" }
+);
+    "#;
+
+    let tree = temptree! {
+        "rules.rhai": rules,
+        src: {
+            "sample.md": sample_md,
+        },
+        templates: {
+            "default.tera": r#"<img src="/img/logo.png"><img src="/img/logo.webp">"#,
+        },
+        web: {
+            "logo.png": "logo",
+        },
+        target: {},
+        syntax_themes: {}
+    };
+    let build = || {
+        let engine = Engine::new(engine_paths(&tree)).unwrap();
+        let report = BuildReport::new();
+        let options = BuildOptions {
+            prune: true,
+            ..BuildOptions::default()
+        };
+        engine.build_site_with_options(&options, &report).unwrap();
+        report.stats()
+    };
+
+    // the linked output doesn't need a pipeline of its own
+    let stats = build();
+    assert_eq!((stats.assets_piped, stats.assets_unchanged), (1, 0));
+    assert_content(tree.path().join("target/img/logo.webp"), "logo");
+    let manifest = std::fs::read_to_string(tree.path().join(".pylon/build_manifest.json")).unwrap();
+    assert!(manifest.contains("/img/logo.webp"));
+
+    // kept when pruning
+    let stats = build();
+    assert_eq!((stats.assets_piped, stats.assets_unchanged), (0, 1));
+    assert!(tree.path().join("target/img/logo.webp").exists());
+
+    // generated again when missing
+    std::fs::remove_file(tree.path().join("target/img/logo.webp")).unwrap();
+    let stats = build();
+    assert_eq!((stats.assets_piped, stats.assets_unchanged), (1, 0));
+    assert!(tree.path().join("target/img/logo.webp").exists());
+}
+
+#[test]
+fn runs_structured_pipeline_ops_for_build_mode() {
+    setup();
+    let sample_md = r#"+++
+    published = true
+    +++"#;
+    let rules = r#"
+rules.add_pipeline(#{
+    dir: "/web",
+    glob: "/app.js",
+    ops: [
+        #{ name: "bundle", run: "echo \"$BANNER\" > $TARGET && cat app.js >> $TARGET", env: #{ BANNER: "// app" } },
+        #{ run: "echo prod >> $TARGET", only: "prod" },
+        #{ run: "cat debug.js >> $TARGET", only: "dev", dir: "dev" },
+    ],
+});
+    "#;
+
+    let tree = temptree! {
+        "rules.rhai": rules,
+        src: {
+            "sample.md": sample_md,
+        },
+        templates: {
+            "default.tera": r#"<script src="/app.js"></script>"#,
+        },
+        web: {
+            "app.js": "app\n",
+            dev: {
+                "debug.js": "debug\n",
+            },
+        },
+        target: {},
+        syntax_themes: {}
+    };
+
+    let engine = Engine::new(engine_paths(&tree)).unwrap();
+    engine.build_site().unwrap();
+    assert_content(tree.path().join("target/app.js"), "// app\napp\nprod\n");
+
+    // the definition changed, so the pipeline runs again
+    let engine = Engine::with_build_mode(engine_paths(&tree), BuildMode::Development).unwrap();
+    engine.build_site().unwrap();
+    assert_content(tree.path().join("target/app.js"), "// app\napp\ndebug\n");
+}
+
+#[test]
+fn rebuilds_outputs_written_by_other_build_mode() {
+    setup();
+    let sample_md = r#"+++
+    published = true
+    +++"#;
+    let rules = r#"
+rules.set_global_context(#{ mode: BUILD_MODE });
+rules.add_pipeline(#{
+    dir: "/web",
+    glob: "/app.js",
+    ops: [
+        #{ run: "echo prod > $TARGET", only: "prod" },
+        #{ run: "echo dev > $TARGET", only: "dev" },
+    ],
+});
+    "#;
+
+    let tree = temptree! {
+        "rules.rhai": rules,
+        src: {
+            "sample.md": sample_md,
+        },
+        templates: {
+            "default.tera": r#"{{ global.mode }}<script src="/app.js"></script>"#,
+        },
+        web: {},
+        target: {},
+        syntax_themes: {}
+    };
+    let manifest = tree.path().join(".pylon/build_manifest.json");
+    let page = || std::fs::read_to_string(tree.path().join("target/sample.html")).unwrap();
+
+    let engine = Engine::new(engine_paths(&tree)).unwrap();
+    engine.build_site().unwrap();
+    let prod_manifest = std::fs::read_to_string(&manifest).unwrap();
+
+    // `pylon serve` writes to the output directory without saving the manifest
+    let engine = Engine::with_build_mode(engine_paths(&tree), BuildMode::Development).unwrap();
+    engine.build_site().unwrap();
+    assert_content(tree.path().join("target/app.js"), "dev\n");
+    assert!(page().starts_with("dev"));
+    std::fs::write(&manifest, prod_manifest).unwrap();
+
+    let engine = Engine::new(engine_paths(&tree)).unwrap();
+    let report = BuildReport::new();
+    engine.build_site_with_report(&report).unwrap();
+    assert_eq!(report.stats().pages_rendered, 1);
+    assert_eq!(report.stats().assets_piped, 1);
+    assert_content(tree.path().join("target/app.js"), "prod\n");
+    assert!(page().starts_with("prod"));
+
+    // nothing changed since
+    let report = BuildReport::new();
+    engine.build_site_with_report(&report).unwrap();
+    assert_eq!(report.stats().pages_unchanged, 1);
+    assert_eq!(report.stats().assets_unchanged, 1);
+}
+
+#[test]
+fn cleans_output_dir() {
+    setup();
+    let sample_md = r#"+++
+    published = true
+    +++"#;
+
+    let tree = temptree! {
+        "rules.rhai": "",
+        src: {
+            "sample.md": sample_md,
+        },
+        templates: {
+            "default.tera": "sample",
+        },
+        target: {
+            "stray.html": "",
+            "CNAME": "example.com",
+            ".git": {
+                "HEAD": "",
+            },
+            old: {
+                "page.html": "",
+            },
+        },
+        syntax_themes: {}
+    };
+    let engine = Engine::new(engine_paths(&tree)).unwrap();
+    engine.build_site().unwrap();
+
+    let report = BuildReport::new();
+    let options = BuildOptions {
+        clean: true,
+        ..BuildOptions::default()
+    };
+    engine.build_site_with_options(&options, &report).unwrap();
+
+    assert_eq!(report.stats().pages_rendered, 1);
+    assert_exists(tree.path().join("target/sample.html"));
+    assert_exists(tree.path().join("target/CNAME"));
+    assert_exists(tree.path().join("target/.git/HEAD"));
+    assert!(!tree.path().join("target/stray.html").exists());
+    assert!(!tree.path().join("target/old").exists());
+}
+
+#[test]
+fn prunes_outputs_not_produced_by_build() {
+    setup();
+    let sample_md = r#"+++
+    published = true
+    +++"#;
+    let rules = r#"
+rules.mount("static");
+rules.add_pipeline("/web", "/style.css", ["cat main.scss > $TARGET"]);
+rules.preserve_output("downloads/**");
+rules.set_base_url("https://example.com");
+    "#;
+
+    let tree = temptree! {
+        "rules.rhai": rules,
+        src: {
+            "sample.md": sample_md,
+        },
+        templates: {
+            "default.tera": r#"<link href="/style.css">"#,
+        },
+        static: {
+            "favicon.ico": "",
+        },
+        web: {
+            "main.scss": "",
+        },
+        target: {
+            "stray.html": "",
+            "CNAME": "example.com",
+            downloads: {
+                "release.zip": "",
+            },
+            old: {
+                "page.html": "",
+            },
+        },
+        syntax_themes: {}
+    };
+    let engine = Engine::new(engine_paths(&tree)).unwrap();
+    let report = BuildReport::new();
+    let options = BuildOptions {
+        prune: true,
+        ..BuildOptions::default()
+    };
+    engine.build_site_with_options(&options, &report).unwrap();
+
+    assert_eq!(report.stats().outputs_removed, 2);
+    assert_exists(tree.path().join("target/sample.html"));
+    assert_exists(tree.path().join("target/favicon.ico"));
+    assert_exists(tree.path().join("target/style.css"));
+    assert_exists(tree.path().join("target/sitemap.xml"));
+    assert_exists(tree.path().join("target/CNAME"));
+    assert_exists(tree.path().join("target/downloads/release.zip"));
+    assert!(!tree.path().join("target/stray.html").exists());
+    assert!(!tree.path().join("target/old").exists());
+
+    // unchanged pages and assets are kept too
+    engine.build_site_with_options(&options, &report).unwrap();
+    assert_exists(tree.path().join("target/sample.html"));
+    assert_exists(tree.path().join("target/style.css"));
+}
+
+#[test]
+fn prune_keeps_colocated_and_existing_assets() {
+    setup();
+    let post_md = r#"+++
+    published = true
+    +++
+![pic](pic.png)"#;
+
+    let tree = temptree! {
+        "rules.rhai": "",
+        src: {
+            blog: {
+                "post.md": post_md,
+                "pic.png": "",
+            },
+        },
+        templates: {
+            "default.tera": r#"<img src="/logo.png">{{ content | safe }}"#,
+        },
+        target: {
+            "logo.png": "",
+            "stray.html": "",
+        },
+        syntax_themes: {}
+    };
+    let engine = Engine::new(engine_paths(&tree)).unwrap();
+    let options = BuildOptions {
+        prune: true,
+        ..BuildOptions::default()
+    };
+    let report = BuildReport::new();
+    engine.build_site_with_options(&options, &report).unwrap();
+
+    assert_eq!(report.stats().outputs_removed, 1);
+    assert_exists(tree.path().join("target/blog/post.html"));
+    assert_exists(tree.path().join("target/blog/pic.png"));
+    assert_exists(tree.path().join("target/logo.png"));
+    assert!(!tree.path().join("target/stray.html").exists());
+
+    // the copy already exists on the next build
+    engine.build_site_with_options(&options, &report).unwrap();
+    assert_exists(tree.path().join("target/blog/pic.png"));
+}
+
+#[test]
+fn replaces_output_dir_after_atomic_build() {
+    setup();
+    let sample_md = r#"+++
+    published = true
+    +++"#;
+
+    let tree = temptree! {
+        "rules.rhai": "",
+        src: {
+            "sample.md": sample_md,
+        },
+        templates: {
+            "default.tera": "sample",
+        },
+        target: {
+            "CNAME": "example.com",
+        },
+        syntax_themes: {}
+    };
+    let engine = Engine::new(engine_paths(&tree)).unwrap();
+    let options = BuildOptions {
+        atomic: true,
+        ..BuildOptions::default()
+    };
+    engine
+        .build_site_with_options(&options, &BuildReport::new())
+        .unwrap();
+
+    assert_exists(tree.path().join("target/sample.html"));
+    assert_exists(tree.path().join("target/CNAME"));
+    assert_exists(tree.path().join("target.prev/CNAME"));
+    assert!(!tree.path().join("target.prev/sample.html").exists());
+    assert!(!tree.path().join("target.tmp").exists());
+
+    // unchanged pages are copied from the previous output
+    let report = BuildReport::new();
+    engine.build_site_with_options(&options, &report).unwrap();
+    assert_eq!(report.stats().pages_unchanged, 1);
+    assert_exists(tree.path().join("target/sample.html"));
+    assert_exists(tree.path().join("target.prev/sample.html"));
+}
+
+#[test]
+fn keeps_output_dir_when_atomic_build_fails() {
+    setup();
+    let sample_md = r#"+++
+    published = true
+    +++"#;
+
+    let tree = temptree! {
+        "rules.rhai": "",
+        src: {
+            "sample.md": sample_md,
+        },
+        templates: {
+            "default.tera": r#"<link href="/missing.css">"#,
+        },
+        target: {
+            "old.html": "",
+        },
+        syntax_themes: {}
+    };
+    let engine = Engine::new(engine_paths(&tree)).unwrap();
+    let options = BuildOptions {
+        atomic: true,
+        ..BuildOptions::default()
+    };
+    assert!(engine
+        .build_site_with_options(&options, &BuildReport::new())
+        .is_err());
+
+    assert_exists(tree.path().join("target/old.html"));
+    assert!(!tree.path().join("target/sample.html").exists());
+    assert!(!tree.path().join("target.tmp").exists());
+    assert!(!tree.path().join("target.prev").exists());
+}
+
+#[test]
+fn restores_output_dir_when_swap_fails() {
+    setup();
+    let tree = temptree! {
+        "rules.rhai": "",
+        src: {},
+        templates: {},
+        target: {
+            "old.html": "",
+        },
+        syntax_themes: {}
+    };
+    let engine = Engine::new(engine_paths(&tree)).unwrap();
+    let missing = AbsPath::new(tree.path().join("target.tmp")).unwrap();
+    assert!(step::swap_output_dir(&engine, &missing).is_err());
+
+    assert_exists(tree.path().join("target/old.html"));
+    assert!(!tree.path().join("target.prev").exists());
+}
+
+#[test]
+fn runs_pipelines_in_parallel() {
+    setup();
+    let sample_md = r#"+++
+    published = true
+    +++"#;
+    // each pipeline waits until both have started, so this only finishes when they
+    // run at the same time
+    let rules = r#"
+rules.add_pipeline("/web", "/*.txt", [
+  "touch $SOURCE.started",
+  "for i in $(seq 50); do [ $(ls *.started | wc -l) -eq 2 ] && break; sleep 0.1; done",
+  "[ $(ls *.started | wc -l) -eq 2 ]",
+  "cp $SOURCE $TARGET"
+]);
+    "#;
+
+    let tree = temptree! {
+        "rules.rhai": rules,
+        src: {
+            "sample.md": sample_md,
+        },
+        templates: {
+            "default.tera": r#"<img src="/a.txt"><img src="/b.txt">"#,
+        },
+        web: {
+            "a.txt": "a",
+            "b.txt": "b",
+        },
+        target: {},
+        syntax_themes: {}
+    };
+    let engine = Engine::new(engine_paths(&tree)).unwrap();
+    let report = BuildReport::new();
+    let options = BuildOptions {
+        jobs: Some(2),
+        ..BuildOptions::default()
+    };
+    engine.build_site_with_options(&options, &report).unwrap();
+
+    assert_eq!(report.stats().assets_piped, 2);
+    assert_content(tree.path().join("target/a.txt"), "a");
+    assert_content(tree.path().join("target/b.txt"), "b");
+}
+
+#[test]
+fn cancels_pipelines_after_failure() {
+    setup();
+    let sample_md = r#"+++
+    published = true
+    +++"#;
+    let rules = r#"
+rules.add_pipeline("/web", "/*.txt", ["false"]);
+    "#;
+
+    let tree = temptree! {
+        "rules.rhai": rules,
+        src: {
+            "sample.md": sample_md,
+        },
+        templates: {
+            "default.tera": r#"<img src="/a.txt"><img src="/b.txt"><img src="/c.txt">"#,
+        },
+        web: {},
+        target: {},
+        syntax_themes: {}
+    };
+    let engine = Engine::new(engine_paths(&tree)).unwrap();
+    let report = BuildReport::new();
+    let options = BuildOptions {
+        jobs: Some(1),
+        ..BuildOptions::default()
+    };
+    assert!(engine.build_site_with_options(&options, &report).is_err());
+
+    // only the first pipeline ran, and the others aren't reported as missing
+    let stats = report.stats();
+    assert_eq!(stats.pipelines["/*.txt"].count, 1);
+    // the failed asset isn't counted as generated
+    assert_eq!(stats.assets_piped, 0);
+    let rules = report
+        .problems()
+        .into_iter()
+        .map(|problem| problem.rule)
+        .filter(|rule| rule != "failed_step")
+        .collect::<Vec<_>>();
+    assert_eq!(rules, vec!["failed_pipeline"]);
+}
+
+#[test]
+fn runs_build_steps_for_unlinked_files() {
+    setup();
+    let sample_md = r#"+++
+    published = true
+    +++"#;
+    let rules = r#"
+rules.add_build_step("/web/styles", "*.scss", "/css/*.css", ["cat $SOURCE > $TARGET"]);
+rules.add_build_step("/web", "/worker.js", ["cp $SOURCE $TARGET"]);
+    "#;
+
+    let tree = temptree! {
+        "rules.rhai": rules,
+        src: {
+            "sample.md": sample_md,
+        },
+        templates: {
+            // the other files are only referenced from CSS and JS
+            "default.tera": r#"<link href="/css/main.css">"#,
+        },
+        web: {
+            "worker.js": "worker",
+            styles: {
+                "main.scss": "main",
+                "print.scss": "print",
+            }
+        },
+        target: {},
+        syntax_themes: {}
+    };
+    let engine = Engine::new(engine_paths(&tree)).unwrap();
+    let report = BuildReport::new();
+    let options = BuildOptions {
+        prune: true,
+        ..BuildOptions::default()
+    };
+    engine.build_site_with_options(&options, &report).unwrap();
+
+    assert_eq!(report.stats().assets_piped, 3);
+    assert_content(tree.path().join("target/css/main.css"), "main");
+    assert_content(tree.path().join("target/css/print.css"), "print");
+    assert_content(tree.path().join("target/worker.js"), "worker");
+
+    // build steps run on every build
+    std::fs::write(tree.path().join("web/worker.js"), "changed").unwrap();
+    engine.build_site().unwrap();
+    assert_content(tree.path().join("target/worker.js"), "changed");
+}
+
+#[test]
+fn warns_on_missing_anchors_by_default() {
+    setup();
+    let doc1 = r#"+++
+            template_name = "test.tera"
+            published = true
+            +++
+[missing](#nowhere)"#;
+
+    let tree = temptree! {
+      "rules.rhai": "",
+      templates: {
+          "test.tera": "content: {{content}}"
+      },
+      target: {},
+      src: {
+          "doc1.md": doc1,
+      },
+      syntax_themes: {}
+    };
+
+    let paths = engine_paths(&tree);
+
+    let engine = Engine::new(paths).unwrap();
+    engine.build_site().unwrap();
+}
+
+#[test]
+fn offsite_anchors_work() {
+    setup();
+    let doc1 = r#"+++
+            template_name = "test.tera"
+            published = true
+            +++
+[test](https://example.com/#anchor)"#;
+
+    let tree = temptree! {
+      "rules.rhai": "",
+      templates: {
+          "test.tera": "content: {{content}}"
+      },
+      target: {},
+      src: {
+          "doc1.md": doc1,
+      },
+      syntax_themes: {}
+    };
+
+    let paths = engine_paths(&tree);
+
+    let engine = Engine::new(paths).unwrap();
+
+    step::render(
+        &engine,
+        engine.library().iter().map(|(_, page)| page),
+        &Diagnostics::new(),
+        &BuildReport::new(),
+    )
+    .expect("failed to render pages");
+}
+
+#[test]
+fn builds_site_index() {
+    setup();
+    let sample_md = r#"+++
+    published = true
+    +++
+    sample"#;
+    let default_template = r#"{{ content | safe }}"#;
+
+    let tree = temptree! {
+        "rules.rhai": "",
+        src: {
+            "index.md": sample_md,
+            inner: {
+                "index.md": sample_md,
+            }
+        },
+        templates: {
+            "default.tera": default_template,
+        },
+        target: {},
+        syntax_themes: {}
+    };
+
+    let engine_paths = engine_paths(&tree);
+    let engine = Engine::new(engine_paths).unwrap();
+    engine.build_site().unwrap();
+
+    assert_content(tree.path().join("target/index.html"), "<p>sample</p>");
+    assert_content(tree.path().join("target/inner/index.html"), "<p>sample</p>");
+}
+
+#[test]
+fn uses_template_rules() {
+    setup();
+    let no_template = r#"+++
+    published = true
+    +++
+    sample"#;
+
+    let with_template = r#"+++
+    published = true
+    template_name = "other.tera"
+    +++
+    sample"#;
+
+    let rules = r#"rules.set_template("blog/**/*.md", "blog/post.tera");"#;
+
+    let tree = temptree! {
+        "rules.rhai": rules,
+        src: {
+            "about.md": no_template,
+            blog: {
+                "post.md": no_template,
+                "special.md": with_template,
+            }
+        },
+        templates: {
+            "default.tera": "default",
+            "other.tera": "other",
+            blog: {
+                "post.tera": "post",
+            }
+        },
+        target: {},
+        syntax_themes: {}
+    };
+
+    let engine_paths = engine_paths(&tree);
+    let engine = Engine::new(engine_paths).unwrap();
+    engine.build_site().unwrap();
+
+    assert_content(tree.path().join("target/about.html"), "default");
+    assert_content(tree.path().join("target/blog/post.html"), "post");
+    assert_content(tree.path().join("target/blog/special.html"), "other");
+}
+
+#[test]
+fn renders_generated_pages() {
+    setup();
+    let index = r#"+++
+    published = true
+    +++
+    [widget](@/products/widget.md)"#;
+
+    let rules = r#"
+    let products = rules.load_data("products.json");
+    for product in products {
+        rules.add_page(`/products/${product.slug}.html`, "product.tera", product);
+    }
+    rules.add_page("/products/", "product.tera", #{ name: "All" });
+    "#;
+
+    let products = r#"[
+        { "slug": "widget", "name": "Widget" },
+        { "slug": "gadget", "name": "Gadget" }
+    ]"#;
+
+    let tree = temptree! {
+        "rules.rhai": rules,
+        "products.json": products,
+        src: {
+            "index.md": index,
+        },
+        templates: {
+            "default.tera": "{{ content | safe }}",
+            "product.tera": "{{ page.meta.name }}",
+        },
+        target: {},
+        syntax_themes: {}
+    };
+
+    let engine_paths = engine_paths(&tree);
+    let engine = Engine::new(engine_paths).unwrap();
+    engine.build_site().unwrap();
+
+    assert_content(
+        tree.path().join("target/index.html"),
+        "<p><a href=/products/widget.html>widget</a></p>",
+    );
+    assert_content(tree.path().join("target/products/widget.html"), "Widget");
+    assert_content(tree.path().join("target/products/gadget.html"), "Gadget");
+    assert_content(tree.path().join("target/products/index.html"), "All");
+}
+
+#[test]
+fn lints_generated_pages() {
+    setup();
+    let rules = r#"
+    rules.add_page("/products/widget.html", "product.tera", #{});
+    rules.add_lint(DENY, "missing name", "/products/**", |page| {
+        type_of(page.meta("name")) == "()"
+    });
+    "#;
+
+    let tree = temptree! {
+        "rules.rhai": rules,
+        src: {},
+        templates: {
+            "product.tera": "{{ page.meta.name }}",
+        },
+        target: {},
+        syntax_themes: {}
+    };
+
+    let engine_paths = engine_paths(&tree);
+    let engine = Engine::new(engine_paths).unwrap();
+    assert!(engine.build_site().is_err());
+}
+
+#[test]
+fn renders_template_outputs() {
+    setup();
+    let doc = r#"+++
+    published = true
+    +++
+    sample"#;
+
+    let rules = r#"
+    rules.set_global_context(#{ title: "site" });
+    rules.render_template("feed.tera", "/feed.xml");
+    rules.render_template("raw.tera", "/raw.html");
+    "#;
+
+    let tree = temptree! {
+        "rules.rhai": rules,
+        src: {
+            "a.md": doc,
+            "b.md": doc,
+        },
+        templates: {
+            "default.tera": "",
+            "feed.tera": "<feed>{{ global.title }}{% for key, page in library %}<entry>{{ page.uri }}</entry>{% endfor %}</feed>",
+            "raw.tera": "<p>  not   minified  </p>\n",
+        },
+        target: {},
+        syntax_themes: {}
+    };
+
+    let engine_paths = engine_paths(&tree);
+    let engine = Engine::new(engine_paths).unwrap();
+    engine.build_site().unwrap();
+
+    assert_content(
+        tree.path().join("target/feed.xml"),
+        "<feed>site<entry>/a.html</entry><entry>/b.html</entry></feed>",
+    );
+    assert_content(
+        tree.path().join("target/raw.html"),
+        "<p>  not   minified  </p>\n",
+    );
+}
+
+#[test]
+fn generates_sitemap() {
+    setup();
+    let dated = r#"+++
+    published = true
+
+    [meta]
+    date = 2022-01-02
+    +++
+    sample"#;
+
+    let excluded = r#"+++
+    published = true
+    sitemap = false
+    +++
+    sample"#;
+
+    let rules = r#"rules.set_base_url("https://example.com/");"#;
+
+    let tree = temptree! {
+        "rules.rhai": rules,
+        src: {
+            "dated.md": dated,
+            "excluded.md": excluded,
+        },
+        templates: {
+            "default.tera": "",
+        },
+        target: {},
+        syntax_themes: {}
+    };
+
+    let engine_paths = engine_paths(&tree);
+    let engine = Engine::new(engine_paths).unwrap();
+    engine.build_site().unwrap();
+
+    assert_content(
+        tree.path().join("target/sitemap.xml"),
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <url><loc>https://example.com/dated.html</loc><lastmod>2022-01-02</lastmod></url>
+</urlset>
+"#,
+    );
+}
+
+#[test]
+fn disables_sitemap() {
+    setup();
+    let doc = r#"+++
+    published = true
+    +++
+    sample"#;
+
+    let rules = r#"
+    rules.set_base_url("https://example.com");
+    rules.set_sitemap(false);
+    "#;
+
+    let tree = temptree! {
+        "rules.rhai": rules,
+        src: {
+            "doc.md": doc,
+        },
+        templates: {
+            "default.tera": "",
+        },
+        target: {},
+        syntax_themes: {}
+    };
+
+    let engine_paths = engine_paths(&tree);
+    let engine = Engine::new(engine_paths).unwrap();
+    engine.build_site().unwrap();
+
+    assert!(!tree.path().join("target/sitemap.xml").exists());
+}
+
+#[test]
+fn excludes_noindex_pages() {
+    setup();
+    let indexed = r#"+++
+    published = true
+    +++
+    sample"#;
+
+    let hidden = r#"+++
+    published = true
+    noindex = true
+    +++
+    sample"#;
+
+    let rules = r#"rules.set_base_url("https://example.com");"#;
+
+    let tree = temptree! {
+        "rules.rhai": rules,
+        src: {
+            "indexed.md": indexed,
+            "hidden.md": hidden,
+        },
+        templates: {
+            "default.tera": "<html><head><title>t</title></head><body>{{ page.noindex }}</body></html>",
+        },
+        target: {},
+        syntax_themes: {}
+    };
+
+    let engine_paths = engine_paths(&tree);
+    let engine = Engine::new(engine_paths).unwrap();
+    engine.build_site().unwrap();
+
+    let sitemap = std::fs::read_to_string(tree.path().join("target/sitemap.xml")).unwrap();
+    assert!(sitemap.contains("https://example.com/indexed.html"));
+    assert!(!sitemap.contains("hidden"));
+
+    let hidden = std::fs::read_to_string(tree.path().join("target/hidden.html")).unwrap();
+    assert!(hidden.contains("<meta content=noindex name=robots>"));
+    assert!(hidden.contains("true"));
+    let indexed = std::fs::read_to_string(tree.path().join("target/indexed.html")).unwrap();
+    assert!(!indexed.contains("robots"));
+}
+
+#[test]
+fn generates_robots_txt() {
+    setup();
+    let rules = r#"
+    rules.set_base_url("https://example.com");
+    rules.set_robots(#{
+        groups: [
+            #{ user_agent: "*", disallow: ["/drafts/"] },
+        ],
+    });
+    "#;
+
+    let tree = temptree! {
+        "rules.rhai": rules,
+        src: {},
+        templates: {},
+        target: {},
+        syntax_themes: {}
+    };
+
+    let engine_paths = engine_paths(&tree);
+    let engine = Engine::new(engine_paths).unwrap();
+    engine.build_site().unwrap();
+
+    assert_content(
+        tree.path().join("target/robots.txt"),
+        "User-agent: *\nDisallow: /drafts/\n\nSitemap: https://example.com/sitemap.xml\n",
+    );
+}
+
+#[test]
+fn generates_security_txt() {
+    setup();
+    let rules = r#"
+    rules.set_base_url("https://example.com");
+    rules.set_security_txt(#{
+        contact: ["mailto:security@example.com", "https://example.com/contact.html"],
+        expires: "2030-01-02",
+        preferred_languages: "en",
+    });
+    "#;
+
+    let tree = temptree! {
+        "rules.rhai": rules,
+        src: {},
+        templates: {},
+        target: {},
+        syntax_themes: {}
+    };
+
+    let engine_paths = engine_paths(&tree);
+    let engine = Engine::new(engine_paths).unwrap();
+    engine.build_site().unwrap();
+
+    assert_content(
+        tree.path().join("target/.well-known/security.txt"),
+        "Contact: mailto:security@example.com
+Contact: https://example.com/contact.html
+Expires: 2030-01-02T00:00:00Z
+Preferred-Languages: en
+Canonical: https://example.com/.well-known/security.txt
+",
+    );
+}
+
+#[test]
+fn generates_atom_feed() {
+    setup();
+    let old = r#"+++
+    published = true
+
+    [meta]
+    title = "Old & Busted"
+    date = 2022-01-02
+    summary = "the old one"
+    +++
+    sample"#;
+
+    let new = r#"+++
+    published = true
+
+    [meta]
+    title = "New"
+    date = 2022-02-03T04:05:06Z
+    +++
+    sample"#;
+
+    let index = r#"+++
+    published = true
+    +++
+    sample"#;
+
+    let rules = r#"
+    rules.set_base_url("https://example.com");
+    rules.add_feed("/blog/**", "/blog/atom.xml", #{ title: "Blog", author: "Me" });
+    "#;
+
+    let tree = temptree! {
+        "rules.rhai": rules,
+        src: {
+            "about.md": index,
+            blog: {
+                "index.md": index,
+                "old.md": old,
+                "new.md": new,
+            },
+        },
+        templates: {
+            "default.tera": "",
+        },
+        target: {},
+        syntax_themes: {}
+    };
+
+    let engine_paths = engine_paths(&tree);
+    let engine = Engine::new(engine_paths).unwrap();
+    engine.build_site().unwrap();
+
+    assert_content(
+        tree.path().join("target/blog/atom.xml"),
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>Blog</title>
+  <id>https://example.com/blog/atom.xml</id>
+  <link rel="self" href="https://example.com/blog/atom.xml"/>
+  <link href="https://example.com/"/>
+  <updated>2022-02-03T04:05:06Z</updated>
+  <author><name>Me</name></author>
+  <entry>
+    <title>New</title>
+    <id>https://example.com/blog/new.html</id>
+    <link href="https://example.com/blog/new.html"/>
+    <published>2022-02-03T04:05:06Z</published>
+    <updated>2022-02-03T04:05:06Z</updated>
+  </entry>
+  <entry>
+    <title>Old &amp; Busted</title>
+    <id>https://example.com/blog/old.html</id>
+    <link href="https://example.com/blog/old.html"/>
+    <published>2022-01-02T00:00:00Z</published>
+    <updated>2022-01-02T00:00:00Z</updated>
+    <summary>the old one</summary>
+  </entry>
+</feed>
+"#,
+    );
+}
+
+#[test]
+fn generates_rss_feed_with_full_content() {
+    setup();
+    let post = r#"+++
+    published = true
+
+    [meta]
+    title = "Post"
+    date = 2022-01-02
+    +++
+    [link](/other.html)"#;
+
+    let rules = r#"
+    rules.set_base_url("https://example.com");
+    rules.add_feed("**", "rss.xml", #{ format: "rss", content: "full", title: "Site" });
+    "#;
+
+    let tree = temptree! {
+        "rules.rhai": rules,
+        src: {
+            "post.md": post,
+        },
+        templates: {
+            "default.tera": "",
+        },
+        target: {},
+        syntax_themes: {}
+    };
+
+    let engine_paths = engine_paths(&tree);
+    let engine = Engine::new(engine_paths).unwrap();
+    engine.build_site().unwrap();
+
+    assert_content(
+        tree.path().join("target/rss.xml"),
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0">
+  <channel>
+    <title>Site</title>
+    <link>https://example.com/</link>
+    <description>Site</description>
+    <item>
+      <title>Post</title>
+      <link>https://example.com/post.html</link>
+      <guid>https://example.com/post.html</guid>
+      <pubDate>Sun, 02 Jan 2022 00:00:00 +0000</pubDate>
+      <description>&lt;p&gt;&lt;a href=&quot;https://example.com/other.html&quot;&gt;link&lt;/a&gt;&lt;/p&gt;
+</description>
+    </item>
+  </channel>
+</rss>
+"#,
+    );
+}
+
+#[test]
+fn generates_keyword_feed() {
+    setup();
+    let tagged = r#"+++
+    published = true
+    keywords = ["rust"]
+
+    [meta]
+    title = "Tagged"
+    date = 2022-01-02
+    +++
+    sample"#;
+
+    let untagged = r#"+++
+    published = true
+
+    [meta]
+    title = "Untagged"
+    date = 2022-01-02
+    +++
+    sample"#;
+
+    // only entries of the feed need a title and a date
+    let undated = r#"+++
+    published = true
+    +++
+    sample"#;
+
+    let rules = r#"
+    rules.set_base_url("https://example.com");
+    rules.add_feed("**", "/tags/rust.xml", #{ keyword: "rust" });
+    "#;
+
+    let tree = temptree! {
+        "rules.rhai": rules,
+        src: {
+            "tagged.md": tagged,
+            "untagged.md": untagged,
+            "undated.md": undated,
+        },
+        templates: {
+            "default.tera": "",
+        },
+        target: {},
+        syntax_themes: {}
+    };
+
+    let engine_paths = engine_paths(&tree);
+    let engine = Engine::new(engine_paths).unwrap();
+    engine.build_site().unwrap();
+
+    let feed = std::fs::read_to_string(tree.path().join("target/tags/rust.xml")).unwrap();
+    assert!(feed.contains("https://example.com/tagged.html"));
+    assert!(!feed.contains("https://example.com/untagged.html"));
+}
+
+#[test]
+fn generates_same_empty_feed_every_build() {
+    setup();
+    let rules = r#"
+    rules.set_base_url("https://example.com");
+    rules.add_feed("/blog/**", "/blog/atom.xml");
+    "#;
+
+    let tree = temptree! {
+        "rules.rhai": rules,
+        src: {},
+        templates: {},
+        target: {},
+        syntax_themes: {}
+    };
+
+    let engine = Engine::new(engine_paths(&tree)).unwrap();
+    let feed = || {
+        engine.build_site().unwrap();
+        std::fs::read_to_string(tree.path().join("target/blog/atom.xml")).unwrap()
+    };
+    let first = feed();
+    assert!(first.contains("<updated>1970-01-01T00:00:00Z</updated>"));
+    assert_eq!(feed(), first);
+}
+
+#[test]
+fn feed_requires_title_and_date() {
+    setup();
+    let doc = r#"+++
+    published = true
+
+    [meta]
+    title = "No date"
+    +++
+    sample"#;
+
+    let rules = r#"
+    rules.set_base_url("https://example.com");
+    rules.add_feed("**", "/atom.xml");
+    "#;
+
+    let tree = temptree! {
+        "rules.rhai": rules,
+        src: {
+            "doc.md": doc,
+        },
+        templates: {
+            "default.tera": "",
+        },
+        target: {},
+        syntax_themes: {}
+    };
+
+    let engine_paths = engine_paths(&tree);
+    let engine = Engine::new(engine_paths).unwrap();
+    assert!(engine.build_site().is_err());
+}
+
+#[test]
+fn feed_requires_base_url() {
+    setup();
+    let rules = r#"rules.add_feed("**", "/atom.xml");"#;
+
+    let tree = temptree! {
+        "rules.rhai": rules,
+        src: {},
+        templates: {},
+        target: {},
+        syntax_themes: {}
+    };
+
+    let engine_paths = engine_paths(&tree);
+    let engine = Engine::new(engine_paths).unwrap();
+    assert!(engine.build_site().is_err());
+}
+
+#[test]
+fn generates_search_index() {
+    setup();
+    let post = r#"+++
+    published = true
+    keywords = ["cats"]
+
+    [meta]
+    title = "Running Cats"
+    +++
+    Cats **running** fast"#;
+
+    let hidden = r#"+++
+    published = true
+    searchable = false
+    +++
+    hidden"#;
+
+    let rules = r#"rules.enable_search(#{ fields: #{ title: 10, content: 1 } });"#;
+
+    let tree = temptree! {
+        "rules.rhai": rules,
+        src: {
+            "post.md": post,
+            "hidden.md": hidden,
+        },
+        templates: {
+            "default.tera": "",
+        },
+        target: {},
+        syntax_themes: {}
+    };
+
+    let engine_paths = engine_paths(&tree);
+    let engine = Engine::new(engine_paths).unwrap();
+    engine.build_site().unwrap();
+
+    let index = std::fs::read_to_string(tree.path().join("target/search_index.json")).unwrap();
+    let index: serde_json::Value = serde_json::from_str(&index).unwrap();
+    assert_eq!(
+        index["documents"],
+        serde_json::json!([{
+            "uri": "/post.html",
+            "title": "Running Cats",
+            "keywords": ["cats"],
+            "text": "Cats running fast",
+        }])
+    );
+    assert_eq!(index["index"]["cat"], serde_json::json!([[0, 11.0]]));
+    assert_eq!(index["index"]["run"], serde_json::json!([[0, 11.0]]));
+    assert_eq!(index["index"]["fast"], serde_json::json!([[0, 1.0]]));
+    assert!(index["index"].get("hidden").is_none());
+}
+
+#[test]
+fn splits_search_index_by_section() {
+    setup();
+    let doc = r#"+++
+    published = true
+    +++
+    sample"#;
+
+    let rules = r#"rules.enable_search(#{ uri: "/search.json", split_sections: true });"#;
+
+    let tree = temptree! {
+        "rules.rhai": rules,
+        src: {
+            "about.md": doc,
+            blog: {
+                "post.md": doc,
+            },
+            "_root": {
+                "page.md": doc,
+            },
+        },
+        templates: {
+            "default.tera": "",
+        },
+        target: {},
+        syntax_themes: {}
+    };
+
+    let engine_paths = engine_paths(&tree);
+    let engine = Engine::new(engine_paths).unwrap();
+    engine.build_site().unwrap();
+
+    let manifest = std::fs::read_to_string(tree.path().join("target/search.json")).unwrap();
+    let manifest: serde_json::Value = serde_json::from_str(&manifest).unwrap();
+    assert_eq!(
+        manifest,
+        serde_json::json!({ "sections": {
+            "/": "/search.root.json",
+            "/_root/": "/search/_root.json",
+            "/blog/": "/search/blog.json",
+        }})
+    );
+
+    let blog = std::fs::read_to_string(tree.path().join("target/search/blog.json")).unwrap();
+    assert!(blog.contains("/blog/post.html"));
+    assert!(!blog.contains("/about.html"));
+    let root = std::fs::read_to_string(tree.path().join("target/search.root.json")).unwrap();
+    assert!(root.contains("/about.html"));
+    assert!(!root.contains("/_root/page.html"));
+    assert_exists(tree.path().join("target/search/_root.json"));
+}
+
+#[test]
+fn exports_documents() {
+    use pylonlib::core::export::{ExportField, ExportFormat, ExportOptions};
+
+    setup();
+    let doc = r#"+++
+    published = true
+    keywords = ["a", "b"]
+    +++
+# Heading
+Some *text*, with a comma"#;
+
+    let tree = temptree! {
+        "rules.rhai": "",
+        src: {
+            blog: {
+                "post.md": doc,
+            },
+        },
+        templates: {
+            "default.tera": "",
+        },
+        target: {},
+        syntax_themes: {}
+    };
+
+    let engine_paths = engine_paths(&tree);
+    let engine = Engine::new(engine_paths).unwrap();
+
+    let export = |format, fields, file: &str| {
+        let target = AbsPath::new(tree.path().join(file)).unwrap();
+        let pages = engine.library().iter().map(|(_, page)| page);
+        step::export_documents(&engine, pages, &target, &ExportOptions::new(format, fields))
+            .expect("failed to export documents");
+        std::fs::read_to_string(tree.path().join(file)).unwrap()
+    };
+
+    // json
+    {
+        let all = vec![
+            ExportField::Uri,
+            ExportField::Path,
+            ExportField::Html,
+            ExportField::Text,
+            ExportField::Toc,
+        ];
+        let exported = export(ExportFormat::Json, all, "export/all.json");
+        let exported: serde_json::Value = serde_json::from_str(&exported).unwrap();
+        let record = &exported[0];
+        assert_eq!(record["uri"], "/blog/post.html");
+        assert_eq!(record["path"], "src/blog/post.md");
+        assert_eq!(record["published"], true);
+        assert_eq!(record["keywords"], serde_json::json!(["a", "b"]));
+        assert!(record["html"].as_str().unwrap().contains("<em>text</em>"));
+        assert_eq!(record["text"], "Heading Some text, with a comma");
+        assert!(record["toc"].as_str().unwrap().contains("#heading"));
+    }
+
+    // ndjson
+    {
+        let exported = export(
+            ExportFormat::Ndjson,
+            vec![ExportField::Uri],
+            "export.ndjson",
+        );
+        let lines = exported.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 1);
+        let record: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(record["uri"], "/blog/post.html");
+        assert!(record.get("html").is_none());
+    }
+
+    // csv
+    {
+        let exported = export(
+            ExportFormat::Csv,
+            vec![ExportField::Uri, ExportField::Text],
+            "export.csv",
+        );
+        let mut lines = exported.lines();
+        let header = lines.next().unwrap();
+        assert!(header.starts_with("uri,"));
+        assert!(header.ends_with(",text"));
+        let row = lines.next().unwrap();
+        assert!(row.starts_with("/blog/post.html,"));
+        assert!(row.contains(r#""[""a"",""b""]""#));
+        assert!(row.ends_with(r#","Heading Some text, with a comma""#));
+    }
+}
+
+#[test]
+fn exports_csv_columns_of_every_page() {
+    use pylonlib::core::export::{ExportField, ExportFormat, ExportOptions};
+
+    setup();
+    let doc = r#"+++
+    published = true
+    +++
+    sample"#;
+
+    let tree = temptree! {
+        // sorts before the page with a Markdown file
+        "rules.rhai": r#"rules.add_page("/a.html", "default.tera", #{});"#,
+        src: {
+            "b.md": doc,
+        },
+        templates: {
+            "default.tera": "",
+        },
+        target: {},
+        syntax_themes: {}
+    };
+
+    let engine = Engine::new(engine_paths(&tree)).unwrap();
+    let target = AbsPath::new(tree.path().join("export.csv")).unwrap();
+    let pages = engine.library().iter().map(|(_, page)| page);
+    let options = ExportOptions::new(ExportFormat::Csv, vec![ExportField::Uri, ExportField::Path]);
+    step::export_documents(&engine, pages, &target, &options).expect("failed to export documents");
+
+    let exported = std::fs::read_to_string(tree.path().join("export.csv")).unwrap();
+    let mut lines = exported.lines();
+    assert!(lines.next().unwrap().starts_with("uri,path,"));
+    assert!(lines.next().unwrap().starts_with("/a.html,,"));
+    assert!(lines.next().unwrap().starts_with("/b.html,src/b.md,"));
+}
+
+#[test]
+fn generates_content_api() {
+    setup();
+    let post = r#"+++
+    published = true
+
+    [meta]
+    title = "Post"
+    +++
+# Heading
+content"#;
+
+    let draft = r#"+++
+    published = false
+    +++
+    draft"#;
+
+    let rules = r#"rules.set_content_api(true);"#;
+
+    let tree = temptree! {
+        "rules.rhai": rules,
+        src: {
+            "index.md": post,
+            blog: {
+                "post.md": post,
+                "draft.md": draft,
+            },
+        },
+        templates: {
+            "default.tera": "<html>{{ content | safe }}</html>",
+        },
+        target: {},
+        syntax_themes: {}
+    };
+
+    let engine_paths = engine_paths(&tree);
+    let engine = Engine::new(engine_paths).unwrap();
+    engine.build_site().unwrap();
+
+    let read_json = |path: &str| -> serde_json::Value {
+        let json = std::fs::read_to_string(tree.path().join(path)).unwrap();
+        serde_json::from_str(&json).unwrap()
+    };
+
+    let post = read_json("target/blog/post.json");
+    assert_eq!(post["uri"], "/blog/post.html");
+    assert_eq!(post["json"], "/blog/post.json");
+    assert_eq!(post["frontmatter"]["meta"]["title"], "Post");
+    assert_eq!(
+        post["content"],
+        "<h1 id=\"heading\">Heading</h1>\n<p>content</p>\n"
+    );
+    assert!(post["toc"].as_str().unwrap().contains("#heading"));
+
+    let index = read_json("target/index.json");
+    assert_eq!(index["uri"], "/");
+
+    let root = read_json("target/_index.json");
+    assert_eq!(root["uri"], "/");
+    assert_eq!(root["pages"][0]["json"], "/index.json");
+    assert_eq!(
+        root["directories"],
+        serde_json::json!(["/blog/_index.json"])
+    );
+
+    let blog = read_json("target/blog/_index.json");
+    assert_eq!(blog["pages"].as_array().unwrap().len(), 1);
+    assert_eq!(blog["pages"][0]["uri"], "/blog/post.html");
+    assert!(!tree.path().join("target/blog/draft.json").exists());
+}
+
+#[test]
+fn renders_not_found_page() {
+    setup();
+    let doc = r#"+++
+    published = true
+    +++
+    sample"#;
+
+    let rules = r#"rules.set_base_url("https://example.com");"#;
+
+    let tree = temptree! {
+        "rules.rhai": rules,
+        src: {
+            "404.md": doc,
+            "page.md": doc,
+        },
+        templates: {
+            "default.tera": "{{ content | safe }}",
+        },
+        target: {},
+        syntax_themes: {}
+    };
+
+    let engine_paths = engine_paths(&tree);
+    let engine = Engine::new(engine_paths).unwrap();
+    engine.build_site().unwrap();
+
+    assert_content(tree.path().join("target/404.html"), "<p>sample</p>");
+    let sitemap = std::fs::read_to_string(tree.path().join("target/sitemap.xml")).unwrap();
+    assert!(sitemap.contains("/page.html"));
+    assert!(!sitemap.contains("/404.html"));
+}
+
+#[test]
+fn copies_configured_not_found_page() {
+    setup();
+    let doc = r#"+++
+    published = true
+    +++
+    missing"#;
+
+    let rules = r#"rules.set_not_found_page("/errors/index.html");"#;
+
+    let tree = temptree! {
+        "rules.rhai": rules,
+        src: {
+            errors: {
+                "index.md": doc,
+            },
+        },
+        templates: {
+            "default.tera": "{{ content | safe }}",
+        },
+        target: {},
+        syntax_themes: {}
+    };
+
+    let engine_paths = engine_paths(&tree);
+    let engine = Engine::new(engine_paths).unwrap();
+    engine.build_site().unwrap();
+
+    assert_content(tree.path().join("target/404.html"), "<p>missing</p>");
+    assert_content(
+        tree.path().join("target/errors/index.html"),
+        "<p>missing</p>",
+    );
+}
+
+#[test]
+fn fails_when_configured_not_found_page_is_missing() {
+    setup();
+    let rules = r#"rules.set_not_found_page("/errors/missing.html");"#;
+
+    let tree = temptree! {
+        "rules.rhai": rules,
+        src: {},
+        templates: {},
+        target: {},
+        syntax_themes: {}
+    };
+
+    let engine_paths = engine_paths(&tree);
+    let engine = Engine::new(engine_paths).unwrap();
+    assert!(engine.build_site().is_err());
+}
+
+/// Renders `count` pages and returns the average time spent on each page.
+fn time_per_page(count: usize) -> std::time::Duration {
+    let tree = temptree! {
+      "rules.rhai": "",
+      templates: {
+          "default.tera": "{{ content | safe }}"
+      },
+      target: {},
+      src: {},
+      syntax_themes: {}
+    };
+    for i in 0..count {
+        let doc = format!("+++\npublished = true\n[meta]\nauthor = \"a\"\n+++\ndoc {i}");
+        std::fs::write(tree.path().join(format!("src/doc{i:04}.md")), doc).unwrap();
+    }
+
+    let engine = Engine::new(engine_paths(&tree)).unwrap();
+    let pages = engine.library().iter().map(|(_, page)| page);
+
+    let started = std::time::Instant::now();
+    let rendered = step::render(&engine, pages, &Diagnostics::new(), &BuildReport::new()).unwrap();
+    let elapsed = started.elapsed();
+    assert_eq!(rendered.len(), count);
+    elapsed / u32::try_from(count).unwrap()
+}
+
+// benchmark: `cargo test --release --test site -- --ignored --nocapture render_time`
+#[test]
+#[ignore]
+fn render_time_per_page_does_not_grow_with_site_size() {
+    setup();
+    // warm up the template engine and thread pool
+    time_per_page(50);
+
+    let small = time_per_page(250);
+    let large = time_per_page(2000);
+    println!("per page: {small:?} with 250 pages, {large:?} with 2000 pages");
+
+    // 8x the pages would be 8x slower per page if every render had to build the
+    // library context, so leave plenty of room for noise
+    assert!(
+        large < small * 3,
+        "{large:?} per page is much slower than {small:?}"
+    );
 }